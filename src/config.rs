@@ -1,72 +1,796 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use tracing::level_filters::LevelFilter;
+
+/// Retry attempts per tick when a config omits `retries`
+const DEFAULT_RETRIES: u8 = 1;
+/// Ping timeout when a config omits `timeout_millis`
+const DEFAULT_TIMEOUT_MILLIS: u64 = 2000;
+/// Ping interval when a config omits `interval_millis`
+const DEFAULT_INTERVAL_MILLIS: u64 = 5000;
+
+pub(crate) fn default_dns_timeout_millis() -> u64 {
+    1000
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
 
 /// HTTP client implementation to use
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 pub enum HttpPinger {
     Hyper,
+    #[default]
     Reqwest,
+    /// HTTP/3 over QUIC, only available with the `http3` feature
+    H3,
+}
+
+/// Interval backoff applied to an entry after repeated ping failures
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct FailureBackoff {
+    /// Consecutive failures before the interval starts growing
+    pub threshold: u32,
+    /// Factor applied to the interval multiplier on each further failure
+    pub factor: u32,
+    /// Upper bound of the interval multiplier
+    pub max_multiplier: u32,
+}
+
+/// Circuit breaker that stops pinging an entry after repeated failures
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct CircuitBreaker {
+    /// Consecutive failures before the circuit opens
+    pub failure_threshold: u32,
+    /// Time the circuit stays open before a single probe is let through
+    pub cooldown_millis: u64,
+}
+
+/// How retry attempts within one tick are scheduled
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum RetryMode {
+    /// Attempts run one after another until one completes
+    #[default]
+    Serial,
+    /// All attempts start at once and the first success wins; this opens up to `retries`
+    /// connections per tick instead of one
+    Parallel,
+}
+
+/// Retry attempts per tick: a plain count, `retry_delay_millis` apart, or an exponential
+/// backoff policy
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Retries {
+    Count(u8),
+    Backoff(RetryBackoff),
+}
+
+impl Default for Retries {
+    fn default() -> Self {
+        Retries::Count(DEFAULT_RETRIES)
+    }
+}
+
+fn default_retry_multiplier() -> f64 {
+    2.0
+}
+
+/// Exponential backoff between the retry attempts of one tick, replacing `retry_delay_millis`
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RetryBackoff {
+    pub max_attempts: u8,
+    /// Delay before the second attempt, at most the section's `interval_millis`
+    pub initial_delay_millis: u64,
+    /// Factor applied to the delay before each further attempt, 2 when unset
+    #[serde(default = "default_retry_multiplier")]
+    pub multiplier: f64,
+    /// Upper bound of the delay, unbounded when unset
+    #[serde(default)]
+    pub max_delay_millis: Option<u64>,
+}
+
+/// Retry and tick timing settings of a ping section, flattened into each section
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Delay between retry attempts within one tick, unused with a `retries` backoff policy
+    pub retry_delay_millis: u64,
+    pub retry_mode: RetryMode,
+    /// Bound on all attempts of one tick including retry delays; once it passes, retrying
+    /// stops and the tick records a timeout
+    pub total_deadline_millis: Option<u64>,
+    /// Upper bound of the random delay applied to the first tick of each entry, spreading
+    /// the entries of a section over the interval
+    pub jitter_millis: u64,
+    /// Delay every tick, not just the first, by a fresh random amount up to `jitter_millis`;
+    /// ticks stay on the interval's grid, so the average rate is unchanged
+    pub jitter_every_tick: bool,
 }
 
 /// HTTP endpoint configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct HttpPingerEntry {
     pub url: String,
+    #[serde(default = "default_method")]
     pub method: String,
+    /// Methods sent to the same URL instead of `method`, each pinged as a separate entry,
+    /// e.g. `["GET", "OPTIONS"]`
+    #[serde(default)]
+    pub methods: Option<Vec<String>>,
+    /// Backend for this entry, overriding the group's `pinger`
+    #[serde(default)]
+    pub pinger: Option<HttpPinger>,
+    /// Forward proxy URL for this entry, overriding the group's `proxy` and `no_proxy`;
+    /// `direct` connects without any proxy
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Fail the ping unless the response body contains this string
+    #[serde(default)]
+    pub expect_body_contains: Option<String>,
+    /// Fail the ping unless the response body matches this regular expression
+    #[serde(default)]
+    pub expect_body_regex: Option<String>,
+    /// Hex SHA-256 fingerprint of the DER-encoded leaf certificate the server must present,
+    /// colons allowed; https only
+    #[serde(default)]
+    pub pinned_cert_sha256: Option<String>,
+    /// Response time objective, successful pings slower than this count as `slo_breach`
+    #[serde(default)]
+    pub slo_millis: Option<u64>,
+}
+
+impl HttpPingerEntry {
+    /// Split into one single-method entry per configured method
+    pub fn expand(self) -> Vec<HttpPingerEntry> {
+        let Some(methods) = self.methods.clone() else {
+            return vec![self];
+        };
+        let mut all: Vec<String> = Vec::with_capacity(methods.len());
+        for method in methods {
+            if !all.contains(&method) {
+                all.push(method);
+            }
+        }
+        all.into_iter()
+            .map(|method| HttpPingerEntry {
+                method,
+                methods: None,
+                ..self.clone()
+            })
+            .collect()
+    }
 }
 
-/// HTTP ping configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_max_retry_after_millis() -> u64 {
+    300_000
+}
+
+/// HTTP ping configuration, omitted fields take the values of [`HttpPingerConfig::default`]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct HttpPingerConfig {
     pub pinger: HttpPinger,
-    pub retries: u8,
+    pub retries: Retries,
     pub timeout_millis: u64,
+    /// Bound on connection setup (TCP connect, proxy and TLS handshakes) within
+    /// `timeout_millis`, so that unreachable targets fail as `ConnectTimeout` rather than
+    /// `Timeout`; `timeout_millis` when unset
+    pub connect_timeout_millis: Option<u64>,
     pub interval_millis: u64,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub failure_backoff: Option<FailureBackoff>,
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Forward proxy URL, e.g. `http://proxy.local:3128`; connections are direct when unset
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Hosts reached directly even when a proxy is set, e.g. `internal.example.com`, `*`
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// User-Agent sent with each ping, `pinger/<version>` when unset; an empty string sends none
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Keep connections open between pings to measure warm request latency instead of
+    /// connection setup
+    #[serde(default)]
+    pub reuse_connections: bool,
+    /// Close a reused connection once it is this old and open a fresh one for the next ping,
+    /// so that server restarts and DNS changes show up; ignored without `reuse_connections`
+    #[serde(default)]
+    pub connection_max_age_millis: Option<u64>,
+    /// Read up to this many bytes of each response body and include the download in the
+    /// total time; only the headers are awaited when unset, unless an entry asserts on the
+    /// body, which then reads up to 64 KiB
+    #[serde(default)]
+    pub read_body_bytes: Option<usize>,
+    /// Send HEAD instead of GET to save the body transfer; entries that read the body keep
+    /// GET and other methods are sent as configured
+    #[serde(default)]
+    pub auto_head: bool,
+    /// Response headers whose values are logged with each ping, e.g. `Server` or `Via`;
+    /// `Server`, `X-Cache` and `CF-Cache-Status` are also counted by value in a metric
+    #[serde(default)]
+    pub capture_headers: Vec<String>,
+    /// Only resolve and connect, including TLS for https, then close without sending the
+    /// request, to measure the network path without server processing; Hyper backend only
+    #[serde(default)]
+    pub connect_only: bool,
+    /// Longest the next ping is deferred for a 429 or 503 response's `Retry-After`, 5
+    /// minutes when unset
+    #[serde(default = "default_max_retry_after_millis")]
+    pub max_retry_after_millis: u64,
+    /// Alternate each tick between a fresh connection and a reused one, recording their
+    /// latencies in `http_ping_cold_us` and `http_ping_warm_us`; Hyper backend only
+    #[serde(default)]
+    pub compare_cold_warm: bool,
     pub entries: Vec<HttpPingerEntry>,
 }
 
+impl Default for HttpPingerConfig {
+    fn default() -> Self {
+        Self {
+            pinger: HttpPinger::default(),
+            retries: Retries::default(),
+            timeout_millis: DEFAULT_TIMEOUT_MILLIS,
+            connect_timeout_millis: None,
+            interval_millis: DEFAULT_INTERVAL_MILLIS,
+            schedule: ScheduleConfig::default(),
+            failure_backoff: None,
+            circuit_breaker: None,
+            proxy: None,
+            no_proxy: Vec::new(),
+            user_agent: None,
+            reuse_connections: false,
+            connection_max_age_millis: None,
+            read_body_bytes: None,
+            auto_head: false,
+            capture_headers: Vec::new(),
+            connect_only: false,
+            max_retry_after_millis: default_max_retry_after_millis(),
+            compare_cold_warm: false,
+            entries: Vec::new(),
+        }
+    }
+}
+
 /// TCP endpoint configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TcpPingerEntry {
+    /// Host to ping, may be omitted when `srv` is set
+    #[serde(default)]
     pub host: String,
+    /// Port to ping, may be omitted when `ports` or `srv` is set
+    #[serde(default)]
     pub port: u16,
+    /// SRV record name such as `_ldap._tcp.example.com`, looked up on every ping; its targets
+    /// are pinged instead of `host` and `port`
+    #[serde(default)]
+    pub srv: Option<String>,
+    /// Further ports on the same host, each pinged as a separate entry
+    #[serde(default)]
+    pub ports: Option<Vec<u16>>,
+    /// Data exchanged after connecting, to catch ports that accept connections but not traffic
+    #[serde(default)]
+    pub probe: Option<TcpProbe>,
+    /// Connect time objective, successful pings slower than this count as `slo_breach`
+    #[serde(default)]
+    pub slo_millis: Option<u64>,
+}
+
+/// Post-connect exchange of a TCP entry
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TcpProbe {
+    /// Bytes written once connected
+    pub send: String,
+    /// Fail unless the reply contains this string; when unset any reply passes, but the
+    /// connection closing without one fails
+    #[serde(default)]
+    pub expect: Option<String>,
 }
 
-/// TCP ping configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TcpPingerEntry {
+    /// `host:port`, or the SRV name of an SRV entry
+    pub fn target(&self) -> String {
+        match &self.srv {
+            Some(srv) => srv.clone(),
+            None => format!("{}:{}", self.host, self.port),
+        }
+    }
+
+    /// Split into one single-port entry per configured port
+    pub fn expand(self) -> Vec<TcpPingerEntry> {
+        let Some(ports) = self.ports else {
+            return vec![self];
+        };
+        let mut all = Vec::with_capacity(ports.len() + 1);
+        if self.port != 0 {
+            all.push(self.port);
+        }
+        for port in ports {
+            if !all.contains(&port) {
+                all.push(port);
+            }
+        }
+        if all.is_empty() {
+            // Keep the entry so that the missing port is reported when it is constructed
+            all.push(self.port);
+        }
+        all.into_iter()
+            .map(|port| TcpPingerEntry {
+                host: self.host.clone(),
+                port,
+                srv: self.srv.clone(),
+                ports: None,
+                probe: self.probe.clone(),
+                slo_millis: self.slo_millis,
+            })
+            .collect()
+    }
+}
+
+/// TCP ping configuration, omitted fields take the values of [`TcpPingerConfig::default`]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct TcpPingerConfig {
-    pub retries: u8,
+    pub retries: Retries,
     pub timeout_millis: u64,
+    /// Bound on the TCP connect, and the SOCKS5 handshake when `socks5_proxy` is set, within
+    /// `timeout_millis`, so that a target that does not accept the connection in time fails as
+    /// `ConnectTimeout` rather than `Timeout`; `timeout_millis` when unset
+    pub connect_timeout_millis: Option<u64>,
     pub interval_millis: u64,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub failure_backoff: Option<FailureBackoff>,
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// SOCKS5 proxy to tunnel TCP pings through; connections are direct when unset
+    #[serde(default)]
+    pub socks5_proxy: Option<SocketAddr>,
+    /// Let the SOCKS5 proxy resolve host names instead of resolving them locally
+    #[serde(default)]
+    pub socks5_remote_dns: bool,
+    /// Add the resolved IP as an `ip` label to TCP ping series; raises cardinality for hosts
+    /// with many addresses
+    #[serde(default)]
+    pub ip_label: bool,
+    /// Add the local IP each successful ping was sent from as a `local_ip` label to TCP ping
+    /// series, e.g. to tell uplinks apart with source-based routing; raises cardinality on
+    /// multi-homed hosts
+    #[serde(default)]
+    pub local_ip_label: bool,
+    /// Local address TCP pings are sent from, e.g. to pick the uplink on a multi-homed host;
+    /// must be of the same address family as the targets
+    #[serde(default)]
+    pub source_addr: Option<IpAddr>,
+    /// Set TCP_NODELAY on probe sockets, OS default when unset. Like `keepalive` this only
+    /// affects data sent after the handshake, not the connect time itself
+    #[serde(default)]
+    pub tcp_nodelay: Option<bool>,
+    /// Set SO_KEEPALIVE on probe sockets, OS default when unset
+    #[serde(default)]
+    pub keepalive: Option<bool>,
+    /// DSCP value (0-63) marking the packets of probe sockets, e.g. 46 for Expedited
+    /// Forwarding, to measure a particular traffic class; Unix only
+    #[serde(default)]
+    pub dscp: Option<u8>,
     pub entries: Vec<TcpPingerEntry>,
 }
 
+impl Default for TcpPingerConfig {
+    fn default() -> Self {
+        Self {
+            retries: Retries::default(),
+            timeout_millis: DEFAULT_TIMEOUT_MILLIS,
+            connect_timeout_millis: None,
+            interval_millis: DEFAULT_INTERVAL_MILLIS,
+            schedule: ScheduleConfig::default(),
+            failure_backoff: None,
+            circuit_breaker: None,
+            socks5_proxy: None,
+            socks5_remote_dns: false,
+            ip_label: false,
+            local_ip_label: false,
+            source_addr: None,
+            tcp_nodelay: None,
+            keepalive: None,
+            dscp: None,
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// gRPC health check endpoint configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GrpcPingerEntry {
+    /// `http://host:port` for plaintext HTTP/2 or `https://host:port` for TLS
+    pub url: String,
+    /// Service name sent in the health check request, empty for the overall server health
+    #[serde(default)]
+    pub service: String,
+}
+
+/// gRPC health check configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GrpcPingerConfig {
+    pub retries: Retries,
+    pub timeout_millis: u64,
+    pub interval_millis: u64,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+    pub entries: Vec<GrpcPingerEntry>,
+}
+
+/// WebSocket probe endpoint configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WsPingerEntry {
+    /// `ws://` or `wss://` URL the upgrade request is sent to
+    pub url: String,
+    /// Also send a ping frame once connected and wait for the pong
+    #[serde(default)]
+    pub ping_frame: bool,
+}
+
+/// WebSocket probe configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WsPingerConfig {
+    pub retries: Retries,
+    pub timeout_millis: u64,
+    pub interval_millis: u64,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+    pub entries: Vec<WsPingerEntry>,
+}
+
+fn default_record_type() -> String {
+    "A".to_string()
+}
+
+/// DNS query probe endpoint configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DnsPingerEntry {
+    /// Name to query, e.g. `example.com`
+    pub query_name: String,
+    /// Record type to query, e.g. `A`, `AAAA`, `MX` or `TXT`
+    #[serde(default = "default_record_type")]
+    pub record_type: String,
+}
+
+/// DNS query probe configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DnsPingerConfig {
+    pub retries: Retries,
+    pub timeout_millis: u64,
+    pub interval_millis: u64,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+    pub entries: Vec<DnsPingerEntry>,
+}
+
+fn default_ntp_port() -> u16 {
+    123
+}
+
+/// NTP server probe endpoint configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NtpPingerEntry {
+    /// Host name or IP address of the server
+    pub server: String,
+    #[serde(default = "default_ntp_port")]
+    pub port: u16,
+}
+
+/// NTP server probe configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NtpPingerConfig {
+    pub retries: Retries,
+    pub timeout_millis: u64,
+    pub interval_millis: u64,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+    pub entries: Vec<NtpPingerEntry>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// Upstream DNS server queries are spread across
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NameserverConfig {
+    /// IP address, with an optional port (53 when omitted), e.g. `1.1.1.1` or `[::1]:5353`
+    pub address: String,
+    /// Relative share of queries sent to this server first
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+impl NameserverConfig {
+    pub fn socket_addr(&self) -> Result<SocketAddr, String> {
+        let address = self.address.trim();
+        SocketAddr::from_str(address)
+            .or_else(|_| IpAddr::from_str(address).map(|ip| SocketAddr::new(ip, 53)))
+            .map_err(|_| format!("Invalid name server address: {}", address))
+    }
+}
+
+/// OpenTelemetry trace export configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OtelConfig {
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`
+    pub endpoint: String,
+    /// Service name reported to the collector, `pinger` when unset
+    #[serde(default)]
+    pub service_name: Option<String>,
+}
+
+/// Prometheus remote-write push configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteWriteConfig {
+    /// Remote-write receiver, e.g. `http://localhost:9090/api/v1/write`
+    pub url: String,
+    /// How often all metrics are pushed, at least 1
+    pub interval_millis: u64,
+    /// Extra request headers, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Metrics exposition configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct MetricsConfig {
+    /// Also expose rolling p50/p90/p99 response time gauges per endpoint
+    #[serde(default)]
+    pub use_summary: bool,
+    /// Number of recent successful pings the quantiles are computed over, 1000 when unset
+    #[serde(default)]
+    pub summary_window: Option<usize>,
+    /// Also expose response times smoothed by an exponentially weighted moving average with
+    /// this weight of the newest ping, in `(0, 1]`
+    #[serde(default)]
+    pub ewma_alpha: Option<f64>,
+}
+
 /// Main application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PingerConfig {
+    #[serde(default)]
     pub http: HttpPingerConfig,
+    #[serde(default)]
     pub tcp: TcpPingerConfig,
+    /// `grpc.health.v1.Health/Check` probes, disabled when unset
+    #[serde(default)]
+    pub grpc: Option<GrpcPingerConfig>,
+    /// WebSocket upgrade probes, disabled when unset
+    #[serde(default)]
+    pub ws: Option<WsPingerConfig>,
+    /// DNS query probes sent straight to the system name servers, disabled when unset
+    #[serde(default)]
+    pub dns: Option<DnsPingerConfig>,
+    /// SNTP probes measuring clock offset and round trip to NTP servers, disabled when unset
+    #[serde(default)]
+    pub ntp: Option<NtpPingerConfig>,
+    /// Name servers to query instead of the system ones, balanced by weight and failing over to
+    /// the others when one does not answer
+    #[serde(default)]
+    pub nameservers: Vec<NameserverConfig>,
+    /// Timeout of each DNS lookup, 1000 when unset
+    #[serde(default = "default_dns_timeout_millis")]
     pub dns_timeout_millis: u64,
+    /// Time every lookup; unless `dns_cache_size` is set this also disables the DNS cache so
+    /// each ping performs a fresh lookup
+    #[serde(default)]
     pub measure_dns_stats: bool,
+    /// Number of DNS records to cache, 10 (or 0 with `measure_dns_stats`) when unset
+    #[serde(default)]
+    pub dns_cache_size: Option<usize>,
+    /// Lower bound applied to record TTLs in the DNS cache
+    #[serde(default)]
+    pub dns_min_ttl_secs: Option<u64>,
+    /// Upper bound applied to record TTLs in the DNS cache
+    #[serde(default)]
+    pub dns_max_ttl_secs: Option<u64>,
+    /// Extra DNS lookup attempts after a transient failure such as SERVFAIL, all within
+    /// `dns_timeout_millis`
+    #[serde(default)]
+    pub dns_retries: u8,
+    /// Delay before the first DNS retry, doubled for each further one and jittered, 50 when
+    /// unset
+    #[serde(default)]
+    pub dns_retry_delay_millis: Option<u64>,
+    /// Pings sent by each entry at startup before any is recorded, keeping cold DNS caches and
+    /// connection setup out of the metrics
+    #[serde(default)]
+    pub warmup_cycles: u32,
+    /// Maximum number of pings in flight at once, at least 1; unlimited when unset
+    #[serde(default)]
+    pub max_concurrent_pings: Option<usize>,
+    /// Pings per second allowed to each target host, shared by all entries targeting it, at
+    /// least one per day; unlimited when unset
+    #[serde(default)]
+    pub host_rate_limit: Option<f64>,
+    /// Approximate bytes all pings may send and receive per minute; once used up, pings pause
+    /// until the next minute. Unlimited when unset
+    #[serde(default)]
+    pub max_bytes_per_minute: Option<u64>,
+    /// Exit with an error when any entry fails to start, e.g. on an invalid URL, instead of
+    /// logging it and pinging the remaining entries
+    #[serde(default)]
+    pub strict_startup: bool,
+    /// Export ping spans to an OpenTelemetry collector, disabled when unset
+    #[serde(default)]
+    pub otel: Option<OtelConfig>,
+    /// Metrics exposition options
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Help texts replacing the built-in ones, keyed by metric family name without the
+    /// `_total` suffix of counters, e.g. `http_ping_failure`
+    #[serde(default)]
+    pub metric_help: HashMap<String, String>,
+    /// Labels added to every exported series, e.g. `environment` to tell probe nodes apart
+    #[serde(default)]
+    pub global_labels: BTreeMap<String, String>,
+    /// Push metrics with the Prometheus remote-write protocol, requires the `remote-write`
+    /// feature
+    #[serde(default)]
+    pub remote_write: Option<RemoteWriteConfig>,
+}
+
+/// Log output format
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Configuration file syntax
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+/// Log verbosity
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// CORS policy of the metrics server
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum CorsPolicy {
+    /// No CORS headers are sent
+    #[default]
+    Disabled,
+    /// Any origin is allowed
+    Permissive,
+    /// Only the listed origins are allowed
+    Origins(Vec<String>),
+}
+
+impl FromStr for CorsPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disabled" => Ok(CorsPolicy::Disabled),
+            "permissive" => Ok(CorsPolicy::Permissive),
+            _ => match s.strip_prefix("origins=") {
+                Some(origins) => Ok(CorsPolicy::Origins(
+                    origins
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|origin| !origin.is_empty())
+                        .map(String::from)
+                        .collect(),
+                )),
+                None => Err(format!(
+                    "invalid CORS policy {}, expected disabled, permissive or origins=<list>",
+                    s
+                )),
+            },
+        }
+    }
 }
 
 /// Command line arguments
 #[derive(Debug, Clone, Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    /// Configuration file path
-    #[arg(short, long)]
-    pub config: String,
+    /// Configuration file path, or `-` to read it from stdin; required unless `--print-schema`
+    /// is given. Repeat it to deep-merge several files, later ones taking precedence
+    #[arg(short, long, required_unless_present = "print_schema")]
+    pub config: Vec<String>,
+
+    /// Configuration syntax, detected from the file extension when unset; required with
+    /// `--config -`
+    #[arg(long, value_enum)]
+    pub config_format: Option<ConfigFormat>,
 
-    /// Enable debug mode
+    /// Enable debug mode, shorthand for `--log-level debug` that also serves the task states
+    /// on `/debug/tasks`
     #[arg(short, long, default_value_t = false)]
     pub debug: bool,
 
-    /// Metrics server bind address
+    /// Log level, overridden per target by `RUST_LOG` when set
+    #[arg(long, value_enum)]
+    pub log_level: Option<LogLevel>,
+
+    /// Log output format
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Metrics server bind address, or `unix:/path/to.sock` for a Unix domain socket; several
+    /// comma-separated addresses, e.g. `127.0.0.1,::1`, each get a listener
     #[arg(long, default_value = "0.0.0.0")]
     pub bind: String,
 
     /// Metrics server port
     #[arg(long, default_value_t = 3000)]
     pub port: u16,
+
+    /// Metrics server CORS policy: `disabled`, `permissive` or `origins=<comma separated list>`
+    #[arg(long, default_value = "disabled")]
+    pub cors: CorsPolicy,
+
+    /// Expose `POST /metrics/reset`, which clears all collected metrics
+    #[arg(long, default_value_t = false)]
+    pub allow_reset: bool,
+
+    /// Expose `POST /reload`, which re-reads the config files and starts or stops the entries
+    /// that were added or removed
+    #[arg(long, default_value_t = false)]
+    pub allow_reload: bool,
+
+    /// Time in-flight pings may take to finish after a shutdown signal before they are abandoned
+    #[arg(long, default_value_t = 5000)]
+    pub shutdown_grace_millis: u64,
+
+    /// Stop each entry after this many ping cycles and exit once all are done, like `ping -c`
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub count: Option<u64>,
+
+    /// Ping every entry once, or `--count` times, print a summary and exit non-zero if any
+    /// failed
+    #[arg(long, default_value_t = false)]
+    pub once: bool,
+
+    /// Validate the configuration and construct all pingers without sending traffic
+    #[arg(long, default_value_t = false)]
+    pub check: bool,
+
+    /// Print the JSON Schema of the configuration file and exit
+    #[arg(long, default_value_t = false)]
+    pub print_schema: bool,
 }
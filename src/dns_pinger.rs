@@ -0,0 +1,183 @@
+use crate::config::DnsPingerEntry;
+use anyhow::Result;
+use hickory_resolver::config::{ResolveHosts, ResolverOpts};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::proto::ProtoErrorKind;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{Name, RecordType};
+use hickory_resolver::proto::serialize::binary::BinEncodable;
+use hickory_resolver::{ResolveError, ResolveErrorKind, Resolver};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::instrument;
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct DnsPingResponse {
+    pub query_name: String,
+    pub record_type: RecordType,
+    pub send_time: Instant,
+    /// 1-based retry attempt that produced this response
+    pub attempt: u8,
+    pub result: DnsPingResult,
+    /// Approximate DNS message bytes: the query, and the answer when one was received
+    pub bytes: u64,
+}
+
+impl DnsPingResponse {
+    pub fn is_success(&self) -> bool {
+        matches!(self.result, DnsPingResult::Answered { .. })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum DnsPingResult {
+    /// The server answered with `records` records of the queried type
+    Answered {
+        query_time: Duration,
+        records: usize,
+    },
+    /// The query failed; `rcode` is the response code when the server answered at all,
+    /// `NoError` meaning it has no records of the queried type
+    Failure {
+        rcode: Option<ResponseCode>,
+        reason: String,
+    },
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsPinger {
+    query_name: Name,
+    record_type: RecordType,
+    /// Uncached and ignoring the hosts file, so that every ping queries a name server
+    resolver: Resolver<TokioConnectionProvider>,
+    timeout: Duration,
+}
+
+impl DnsPinger {
+    pub fn new(entry: DnsPingerEntry, timeout: Duration) -> Result<Self> {
+        let query_name = Name::from_str(entry.query_name.trim())?;
+        let record_type = RecordType::from_str(&entry.record_type.to_uppercase())?;
+
+        let mut options = ResolverOpts::default();
+        options.cache_size = 0;
+        options.use_hosts_file = ResolveHosts::Never;
+        options.timeout = timeout;
+        // Retries are left to the probe retry policy
+        options.attempts = 1;
+        let resolver = Resolver::builder(TokioConnectionProvider::default())?
+            .with_options(options)
+            .build();
+        Ok(DnsPinger {
+            query_name,
+            record_type,
+            resolver,
+            timeout,
+        })
+    }
+
+    pub fn query_name(&self) -> &Name {
+        &self.query_name
+    }
+
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+
+    fn response(&self, begin: Instant, result: DnsPingResult) -> DnsPingResponse {
+        DnsPingResponse {
+            query_name: self.query_name.to_string(),
+            record_type: self.record_type,
+            send_time: begin,
+            attempt: 1,
+            result,
+            bytes: 0,
+        }
+    }
+
+    /// Timeout response for a tick whose attempts ran past the total deadline
+    pub fn deadline_exceeded(&self, begin: Instant) -> DnsPingResponse {
+        self.response(begin, DnsPingResult::Timeout)
+    }
+
+    /// Bytes of the query: header and question, both echoed by the answer
+    fn query_bytes(&self) -> u64 {
+        12 + self
+            .query_name
+            .to_bytes()
+            .map_or(0, |name| name.len() as u64)
+            + 4
+    }
+
+    #[instrument(fields(query_name = %self.query_name, record_type = %self.record_type), skip(self))]
+    async fn ping_inner(&self) -> Result<DnsPingResponse> {
+        let begin = Instant::now();
+        let query_bytes = self.query_bytes();
+        let (result, bytes) = match self
+            .resolver
+            .lookup(self.query_name.clone(), self.record_type)
+            .await
+        {
+            Ok(lookup) => {
+                let answer_bytes: usize = lookup
+                    .record_iter()
+                    .map(|record| record.to_bytes().map_or(0, |record| record.len()))
+                    .sum();
+                let result = DnsPingResult::Answered {
+                    query_time: begin.elapsed(),
+                    records: lookup
+                        .record_iter()
+                        .filter(|record| record.record_type() == self.record_type)
+                        .count(),
+                };
+                (result, 2 * query_bytes + answer_bytes as u64)
+            }
+            Err(e) if is_timeout(&e) => (DnsPingResult::Timeout, query_bytes),
+            Err(e) => {
+                let rcode = response_code(&e);
+                // Without a response code the query was sent but not answered
+                let bytes = match rcode {
+                    Some(_) => 2 * query_bytes,
+                    None => query_bytes,
+                };
+                let reason = e.to_string();
+                (DnsPingResult::Failure { rcode, reason }, bytes)
+            }
+        };
+        Ok(DnsPingResponse {
+            bytes,
+            ..self.response(begin, result)
+        })
+    }
+
+    pub async fn ping(&self) -> Result<DnsPingResponse> {
+        let begin = Instant::now();
+        match tokio::time::timeout(self.timeout, self.ping_inner()).await {
+            Ok(result) => result,
+            Err(_) => Ok(DnsPingResponse {
+                bytes: self.query_bytes(),
+                ..self.response(begin, DnsPingResult::Timeout)
+            }),
+        }
+    }
+}
+
+fn is_timeout(error: &ResolveError) -> bool {
+    match error.kind() {
+        ResolveErrorKind::Proto(error) => matches!(error.kind(), ProtoErrorKind::Timeout),
+        _ => false,
+    }
+}
+
+/// Response code of a query the server answered without records
+fn response_code(error: &ResolveError) -> Option<ResponseCode> {
+    match error.kind() {
+        ResolveErrorKind::Proto(error) => match error.kind() {
+            ProtoErrorKind::NoRecordsFound { response_code, .. } => Some(*response_code),
+            _ => None,
+        },
+        _ => None,
+    }
+}
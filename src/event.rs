@@ -0,0 +1,34 @@
+use crate::dns_pinger::DnsPingResponse;
+use crate::grpc_pinger::GrpcPingResponse;
+use crate::http_pinger::PingResponse;
+use crate::ntp_pinger::NtpPingResponse;
+use crate::tcp_pinger::TcpPingResult;
+use crate::ws_pinger::WsPingResponse;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::warn;
+
+/// Channel the ping tasks forward their results to, e.g. to push them to a custom sink
+pub type EventSender = mpsc::Sender<PingEvent>;
+
+/// Final response of one ping cycle, forwarded after it is recorded in the metrics; cycles
+/// whose attempts all errored without a response are not forwarded
+#[derive(Debug, Clone)]
+pub enum PingEvent {
+    Http(PingResponse),
+    Tcp(TcpPingResult),
+    Grpc(GrpcPingResponse),
+    Ws(WsPingResponse),
+    Dns(DnsPingResponse),
+    Ntp(NtpPingResponse),
+}
+
+/// Forward `event` without waiting, so that a slow receiver never delays the probes; events
+/// that don't fit the channel are dropped
+pub fn forward(events: &EventSender, event: PingEvent) {
+    match events.try_send(event) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => warn!("Ping event channel is full, dropping event"),
+        Err(TrySendError::Closed(_)) => {}
+    }
+}
@@ -0,0 +1,379 @@
+use crate::config::GrpcPingerEntry;
+use crate::http_pinger::{STATUS_LINE_BYTES, head_bytes, request_line_bytes};
+use crate::metric::{ByteTally, FailureType, TCP_CONNECT_BYTES, TLS_HANDSHAKE_BYTES};
+use crate::resolver::{Resolve, ResolveDeadline, resolve_str};
+use crate::tls;
+use anyhow::Result;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::header::{CONTENT_TYPE, HeaderMap, TE};
+use hyper::{Method, Request};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tracing::{Instrument, info_span, instrument};
+use url::Host;
+
+const HEALTH_CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+
+/// `grpc.health.v1.HealthCheckResponse.ServingStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServingStatus {
+    Unknown,
+    Serving,
+    NotServing,
+    ServiceUnknown,
+}
+
+impl ServingStatus {
+    fn from_proto(value: u64) -> Self {
+        match value {
+            1 => ServingStatus::Serving,
+            2 => ServingStatus::NotServing,
+            3 => ServingStatus::ServiceUnknown,
+            _ => ServingStatus::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct GrpcPingResponse {
+    pub url: url::Url,
+    pub service: String,
+    pub send_time: Instant,
+    /// 1-based retry attempt that produced this response
+    pub attempt: u8,
+    pub result: GrpcPingResult,
+    /// Approximate bytes of the health RPC: estimated handshakes, request and response heads,
+    /// bodies and trailers
+    pub bytes: u64,
+}
+
+impl GrpcPingResponse {
+    pub fn is_success(&self) -> bool {
+        matches!(self.result, GrpcPingResult::Serving { .. })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum GrpcPingResult {
+    /// The service reported `SERVING`; `rtt` covers the health RPC only, not the connection
+    /// setup in `total_time`
+    Serving {
+        rtt: Duration,
+        total_time: Duration,
+    },
+    /// The RPC completed with a status other than `SERVING`
+    NotServing(ServingStatus),
+    Failure {
+        failure_type: FailureType,
+        reason: String,
+    },
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcPinger {
+    url: url::Url,
+    host: Host<String>,
+    port: u16,
+    service: String,
+    /// Set for `https` targets, negotiating HTTP/2 through ALPN
+    tls_config: Option<Arc<ClientConfig>>,
+    timeout: Duration,
+    resolver: Arc<dyn Resolve>,
+}
+
+impl GrpcPinger {
+    pub fn new(
+        entry: GrpcPingerEntry,
+        timeout: Duration,
+        resolver: Arc<dyn Resolve>,
+    ) -> Result<Self> {
+        let url = entry.url.trim().parse::<url::Url>()?;
+        let Some(host) = url.host().map(|host| host.to_owned()) else {
+            anyhow::bail!("Invalid URL: Host is missing in {}", url);
+        };
+        let tls_config = match url.scheme() {
+            "http" => None,
+            "https" => Some(Arc::new(tls::client_config(&[b"h2"]))),
+            scheme => anyhow::bail!("Unsupported gRPC URL scheme: {}", scheme),
+        };
+        Ok(GrpcPinger {
+            port: url.port_or_known_default().unwrap(),
+            url,
+            host,
+            service: entry.service,
+            tls_config,
+            timeout,
+            resolver,
+        })
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    fn response(&self, begin: Instant, result: GrpcPingResult) -> GrpcPingResponse {
+        GrpcPingResponse {
+            url: self.url.clone(),
+            service: self.service.clone(),
+            send_time: begin,
+            attempt: 1,
+            result,
+            bytes: 0,
+        }
+    }
+
+    /// Timeout response for a tick whose attempts ran past the total deadline
+    pub fn deadline_exceeded(&self, begin: Instant) -> GrpcPingResponse {
+        self.response(begin, GrpcPingResult::Timeout)
+    }
+
+    fn wrap_soft_err<E: std::fmt::Display>(
+        &self,
+        failure_type: FailureType,
+        e: E,
+        begin: Instant,
+    ) -> GrpcPingResponse {
+        self.response(
+            begin,
+            GrpcPingResult::Failure {
+                failure_type,
+                reason: e.to_string(),
+            },
+        )
+    }
+
+    async fn resolve(&self, deadline: Instant) -> Result<IpAddr> {
+        match &self.host {
+            Host::Domain(name) => resolve_str(self.resolver.as_ref(), name, deadline).await,
+            Host::Ipv4(ip) => Ok((*ip).into()),
+            Host::Ipv6(ip) => Ok((*ip).into()),
+        }
+    }
+
+    /// `HealthCheckRequest { service }` in a gRPC length-prefixed message
+    fn request_body(&self) -> Bytes {
+        let mut message = Vec::new();
+        if !self.service.is_empty() {
+            message.push(0x0a);
+            put_varint(&mut message, self.service.len() as u64);
+            message.extend_from_slice(self.service.as_bytes());
+        }
+        let mut body = vec![0];
+        body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        body.extend_from_slice(&message);
+        body.into()
+    }
+
+    #[instrument(fields(url = %self.url, service = %self.service), skip(self, bytes))]
+    async fn ping_inner(&self, bytes: &ByteTally) -> Result<GrpcPingResponse> {
+        let begin = Instant::now();
+        let ip = match self.resolve(begin + self.timeout).await {
+            Ok(ip) => ip,
+            Err(e) if e.is::<ResolveDeadline>() => {
+                return Ok(self.response(begin, GrpcPingResult::Timeout));
+            }
+            Err(e) => return Ok(self.wrap_soft_err(FailureType::Dns, e, begin)),
+        };
+        let addr = SocketAddr::new(ip, self.port);
+        bytes.add(TCP_CONNECT_BYTES);
+        let tcp = match TcpStream::connect(addr)
+            .instrument(info_span!("connect", %addr))
+            .await
+        {
+            Ok(tcp) => tcp,
+            Err(e) => return Ok(self.wrap_soft_err(FailureType::Connect, e, begin)),
+        };
+
+        let handshake = match &self.tls_config {
+            Some(tls_config) => {
+                let server_name = match &self.host {
+                    Host::Domain(name) => ServerName::try_from(name.clone())?,
+                    Host::Ipv4(ip) => ServerName::from(IpAddr::from(*ip)),
+                    Host::Ipv6(ip) => ServerName::from(IpAddr::from(*ip)),
+                };
+                bytes.add(TLS_HANDSHAKE_BYTES);
+                let stream = match TlsConnector::from(tls_config.clone())
+                    .connect(server_name, tcp)
+                    .instrument(info_span!("tls_handshake"))
+                    .await
+                {
+                    Ok(stream) => stream,
+                    Err(e) => return Ok(self.wrap_soft_err(FailureType::Tls, e, begin)),
+                };
+                hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                    .instrument(info_span!("http2_handshake"))
+                    .await
+                    .map(|(sender, conn)| (sender, tokio::spawn(conn)))
+            }
+            None => hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(tcp))
+                .instrument(info_span!("http2_handshake"))
+                .await
+                .map(|(sender, conn)| (sender, tokio::spawn(conn))),
+        };
+        let (mut sender, handle) = match handshake {
+            Ok(handshake) => handshake,
+            Err(e) => return Ok(self.wrap_soft_err(FailureType::Protocol, e, begin)),
+        };
+
+        let request_body = self.request_body();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!(
+                "{}://{}:{}{}",
+                self.url.scheme(),
+                self.host,
+                self.port,
+                HEALTH_CHECK_PATH
+            ))
+            .header(CONTENT_TYPE, "application/grpc")
+            .header(TE, "trailers")
+            .body(Full::new(request_body.clone()))?;
+        let request_line = request_line_bytes(request.method(), HEALTH_CHECK_PATH);
+        bytes.add(head_bytes(request_line, request.headers()) + request_body.len() as u64);
+
+        let rpc_begin = Instant::now();
+        let exchange = async {
+            let response = sender.send_request(request).await?;
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await?;
+            anyhow::Ok((parts, body))
+        };
+        let (parts, body) = match exchange.instrument(info_span!("health_check")).await {
+            Ok(exchange) => exchange,
+            Err(e) => return Ok(self.wrap_soft_err(classify(&e), e, begin)),
+        };
+        let rtt = rpc_begin.elapsed();
+        let total_time = begin.elapsed();
+        drop(sender);
+        handle.abort();
+        // A trailers-only response carries the status in the headers
+        let trailers = body.trailers().cloned().unwrap_or_default();
+        let body = body.to_bytes();
+        bytes.add(
+            head_bytes(STATUS_LINE_BYTES, &parts.headers)
+                + head_bytes(0, &trailers)
+                + body.len() as u64,
+        );
+
+        if !parts.status.is_success() {
+            let reason = format!("HTTP status {}", parts.status);
+            return Ok(self.wrap_soft_err(FailureType::Protocol, reason, begin));
+        }
+        let (code, message) = grpc_status(&trailers)
+            .or(grpc_status(&parts.headers))
+            .unwrap_or((u32::MAX, "missing grpc-status".to_string()));
+        if code != 0 {
+            let reason = format!("grpc-status {}: {}", code, message);
+            return Ok(self.wrap_soft_err(FailureType::Protocol, reason, begin));
+        }
+
+        let result = match parse_serving_status(&body) {
+            Some(ServingStatus::Serving) => GrpcPingResult::Serving { rtt, total_time },
+            Some(status) => GrpcPingResult::NotServing(status),
+            None => {
+                let reason = "malformed HealthCheckResponse";
+                return Ok(self.wrap_soft_err(FailureType::Protocol, reason, begin));
+            }
+        };
+        Ok(self.response(begin, result))
+    }
+
+    pub async fn ping(&self) -> Result<GrpcPingResponse> {
+        let begin = Instant::now();
+        let bytes = ByteTally::default();
+        let response = match tokio::time::timeout(self.timeout, self.ping_inner(&bytes)).await {
+            Ok(result) => result?,
+            Err(_) => self.response(begin, GrpcPingResult::Timeout),
+        };
+        // Responses are built before knowing what was sent, so every outcome takes the tally
+        Ok(GrpcPingResponse {
+            bytes: bytes.total(),
+            ..response
+        })
+    }
+}
+
+fn classify(e: &anyhow::Error) -> FailureType {
+    if e.chain().any(|e| e.is::<std::io::Error>()) {
+        FailureType::Connect
+    } else {
+        FailureType::Protocol
+    }
+}
+
+/// `grpc-status` code and `grpc-message` from response headers or trailers
+fn grpc_status(headers: &HeaderMap) -> Option<(u32, String)> {
+    let code = headers.get("grpc-status")?.to_str().ok()?.parse().ok()?;
+    let message = headers
+        .get("grpc-message")
+        .and_then(|message| message.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    Some((code, message))
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn get_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Status of the first length-prefixed `HealthCheckResponse` message in `body`
+fn parse_serving_status(body: &[u8]) -> Option<ServingStatus> {
+    if body.first()? & 1 != 0 {
+        // Compressed messages are never requested
+        return None;
+    }
+    let len = u32::from_be_bytes(body.get(1..5)?.try_into().ok()?) as usize;
+    let mut message = body.get(5..5 + len)?;
+    // Proto3 omits the default `UNKNOWN` status
+    let mut status = ServingStatus::Unknown;
+    while !message.is_empty() {
+        let key = get_varint(&mut message)?;
+        match key & 7 {
+            0 => {
+                let value = get_varint(&mut message)?;
+                if key >> 3 == 1 {
+                    status = ServingStatus::from_proto(value);
+                }
+            }
+            1 => message = message.get(8..)?,
+            2 => {
+                let len = get_varint(&mut message)? as usize;
+                message = message.get(len..)?;
+            }
+            5 => message = message.get(4..)?,
+            _ => return None,
+        }
+    }
+    Some(status)
+}
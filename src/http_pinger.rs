@@ -1,20 +1,37 @@
+#[cfg(feature = "http3")]
+pub mod h3_pinger;
 pub mod hyper_pinger;
 pub mod reqwest_pinger;
 
-use crate::config::HttpPingerEntry;
-use crate::resolver::Resolve;
+use crate::config::{HttpPingerConfig, HttpPingerEntry};
+use crate::metric::{FailureType, TimeoutCause, wall_clock};
+use crate::resolver::{Resolve, system_resolver};
 use anyhow::Result;
 use async_trait::async_trait;
-use hyper::Method;
+use hickory_resolver::ResolveError;
+use hyper::header::{self, HeaderMap, HeaderName};
+use hyper::{Method, StatusCode};
+use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[async_trait]
 pub trait AsyncHttpPinger {
     async fn ping(&self) -> Result<PingResponse>;
 
-    fn new(entry: HttpPingerEntry, timeout: Duration, resolver: Arc<dyn Resolve>) -> Result<Self>
+    /// Called once per tick before its first attempt, so that retries share the tick's setup
+    fn start_tick(&self) {}
+
+    fn new(
+        entry: HttpPingerEntry,
+        timeout: Duration,
+        options: HttpClientOptions,
+        resolver: Arc<dyn Resolve>,
+    ) -> Result<Self>
     where
         Self: Sized;
 
@@ -22,14 +39,561 @@ pub trait AsyncHttpPinger {
 
     fn method(&self) -> &Method;
 
-    fn wrap_soft_err<E: Display>(&self, e: E, begin: Instant) -> PingResponse {
+    fn wrap_soft_err<E: Display>(
+        &self,
+        failure_type: FailureType,
+        e: E,
+        begin: Instant,
+    ) -> PingResponse {
+        PingResponse {
+            url: self.url().to_string(),
+            ip: None,
+            send_time: begin,
+            sent_at: wall_clock(begin),
+            method: self.method().clone(),
+            attempt: 1,
+            result: PingResult::Failure {
+                failure_type,
+                reason: e.to_string(),
+            },
+            headers: Vec::new(),
+            retry_after: None,
+            bytes: 0,
+        }
+    }
+
+    fn wrap_timeout(&self, cause: TimeoutCause, begin: Instant) -> PingResponse {
         PingResponse {
             url: self.url().to_string(),
             ip: None,
             send_time: begin,
+            sent_at: wall_clock(begin),
             method: self.method().clone(),
-            result: PingResult::Failure(e.to_string()),
+            attempt: 1,
+            result: PingResult::Timeout { cause },
+            headers: Vec::new(),
+            retry_after: None,
+            bytes: 0,
+        }
+    }
+}
+
+/// Classify a ping error by the most specific cause in its source chain
+pub fn classify_error(error: &(dyn std::error::Error + 'static)) -> FailureType {
+    // `io::Error::source` skips the error it wraps, so step into it explicitly
+    let chain = || {
+        std::iter::successors(Some(error), |e| match e.downcast_ref::<std::io::Error>() {
+            Some(io) => io.get_ref().map(|inner| inner as _),
+            None => e.source(),
+        })
+    };
+    if chain().any(|e| e.is::<PinMismatch>()) {
+        FailureType::PinMismatch
+    } else if chain().any(|e| e.is::<ResolveError>()) {
+        FailureType::Dns
+    } else if chain().any(|e| e.is::<tokio_rustls::rustls::Error>() || e.is::<native_tls::Error>())
+    {
+        FailureType::Tls
+    } else if chain().any(|e| e.is::<std::io::Error>()) {
+        FailureType::Connect
+    } else if chain().any(|e| e.is::<hyper::Error>() || e.is::<hyper::http::Error>()) {
+        FailureType::Protocol
+    } else {
+        FailureType::Other
+    }
+}
+
+/// Body bytes read for an entry with a body assertion when `read_body_bytes` is unset
+const DEFAULT_ASSERT_BODY_BYTES: usize = 64 * 1024;
+
+/// Expectations an entry places on the response body
+#[derive(Debug, Clone)]
+pub struct BodyAssertion {
+    contains: Option<String>,
+    regex: Option<Regex>,
+}
+
+impl BodyAssertion {
+    pub fn from_entry(entry: &HttpPingerEntry) -> Result<Option<Self>> {
+        if entry.expect_body_contains.is_none() && entry.expect_body_regex.is_none() {
+            return Ok(None);
+        }
+        let regex = match &entry.expect_body_regex {
+            Some(pattern) => Some(
+                Regex::new(pattern)
+                    .map_err(|e| anyhow::anyhow!("Invalid body regex {}: {}", pattern, e))?,
+            ),
+            None => None,
+        };
+        Ok(Some(Self {
+            contains: entry.expect_body_contains.clone(),
+            regex,
+        }))
+    }
+
+    /// Check the (possibly truncated) body, returning why it does not match
+    pub fn check(&self, body: &[u8]) -> std::result::Result<(), String> {
+        let body = String::from_utf8_lossy(body);
+        if let Some(needle) = &self.contains
+            && !body.contains(needle.as_str())
+        {
+            return Err(format!("body does not contain {:?}", needle));
+        }
+        if let Some(regex) = &self.regex
+            && !regex.is_match(&body)
+        {
+            return Err(format!("body does not match /{}/", regex));
+        }
+        Ok(())
+    }
+}
+
+/// Target of an `http+unix:///path/to/app.sock:/request/path` URL
+#[derive(Debug, Clone)]
+pub struct UnixTarget {
+    pub socket: PathBuf,
+    /// Request path, `/` when the URL names only the socket
+    pub path: String,
+}
+
+impl UnixTarget {
+    pub const SCHEME: &str = "http+unix";
+
+    /// Split an `http+unix` URL into the socket and the request path, `None` for other schemes
+    pub fn parse(url: &url::Url) -> Option<Result<Self>> {
+        if url.scheme() != Self::SCHEME {
+            return None;
+        }
+        Some(match url.path().split_once(':') {
+            _ if url.host_str().is_some_and(|host| !host.is_empty()) => Err(anyhow::anyhow!(
+                "{} URLs take no host, use {}:///path/to/app.sock:/path",
+                Self::SCHEME,
+                Self::SCHEME
+            )),
+            Some((socket, path)) if !socket.is_empty() => Ok(UnixTarget {
+                socket: PathBuf::from(socket),
+                path: if path.is_empty() { "/" } else { path }.to_string(),
+            }),
+            None if !url.path().is_empty() => Ok(UnixTarget {
+                socket: PathBuf::from(url.path()),
+                path: "/".to_string(),
+            }),
+            _ => Err(anyhow::anyhow!("socket path is missing in {}", url)),
+        })
+    }
+}
+
+/// URL with `{{now}}` (Unix milliseconds) or `{{rand}}` (a random UUID) tokens, expanded
+/// before every ping so that each request bypasses caches
+#[derive(Debug, Clone)]
+pub struct UrlTemplate(String);
+
+impl UrlTemplate {
+    const NOW: &str = "{{now}}";
+    const RAND: &str = "{{rand}}";
+
+    /// Split a configured URL into the URL reported for it, with every token removed, and the
+    /// template expanded per ping, `None` when the URL has no tokens
+    pub fn parse(url: &str) -> Result<(url::Url, Option<Self>)> {
+        let url = url.trim();
+        if !url.contains(Self::NOW) && !url.contains(Self::RAND) {
+            return Ok((url.parse()?, None));
+        }
+        let template = UrlTemplate(url.to_string());
+        let base = url
+            .replace(Self::NOW, "")
+            .replace(Self::RAND, "")
+            .parse::<url::Url>()?;
+        let expanded = template.expand()?;
+        if (expanded.scheme(), expanded.host_str(), expanded.port())
+            != (base.scheme(), base.host_str(), base.port())
+        {
+            anyhow::bail!(
+                "URL tokens are only supported in the path and query: {}",
+                url
+            );
+        }
+        Ok((base, Some(template)))
+    }
+
+    pub fn expand(&self) -> Result<url::Url> {
+        let mut url = self.0.clone();
+        if url.contains(Self::NOW) {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+            url = url.replace(Self::NOW, &now.to_string());
+        }
+        if url.contains(Self::RAND) {
+            let uuid = uuid::Builder::from_random_bytes(rand::random()).into_uuid();
+            url = url.replace(Self::RAND, &uuid.to_string());
+        }
+        Ok(url.parse()?)
+    }
+}
+
+/// SHA-256 fingerprint the server's leaf certificate must match
+#[derive(Debug, Clone, Copy)]
+pub struct CertPin([u8; 32]);
+
+/// The server presented a certificate other than the pinned one
+#[derive(Debug)]
+pub struct PinMismatch {
+    expected: String,
+    actual: Option<String>,
+}
+
+impl Display for PinMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.actual {
+            Some(actual) => write!(
+                f,
+                "certificate pin mismatch: expected sha256 {}, got {}",
+                self.expected, actual
+            ),
+            None => write!(
+                f,
+                "certificate pin mismatch: expected sha256 {}, no certificate presented",
+                self.expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PinMismatch {}
+
+impl CertPin {
+    pub fn from_entry(entry: &HttpPingerEntry) -> Result<Option<Self>> {
+        entry
+            .pinned_cert_sha256
+            .as_deref()
+            .map(|fingerprint| {
+                Self::parse(fingerprint)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid pinned_cert_sha256 {:?}", fingerprint))
+            })
+            .transpose()
+    }
+
+    /// Parse 64 hex digits, optionally separated by colons
+    pub fn parse(fingerprint: &str) -> Option<Self> {
+        let digits: Vec<u8> = fingerprint
+            .trim()
+            .bytes()
+            .filter(|&b| b != b':')
+            .map(|b| (b as char).to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        if digits.len() != 64 {
+            return None;
         }
+        let bytes: Vec<u8> = digits
+            .chunks_exact(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect();
+        Some(Self(bytes.try_into().unwrap()))
+    }
+
+    /// Check the DER-encoded leaf certificate, `None` when the server presented none
+    pub fn check(&self, leaf: Option<&[u8]>) -> std::result::Result<(), PinMismatch> {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        let actual = leaf.map(Sha256::digest);
+        match actual {
+            Some(actual) if actual.as_slice() == self.0 => Ok(()),
+            _ => Err(PinMismatch {
+                expected: hex(&self.0),
+                actual: actual.map(|actual| hex(&actual)),
+            }),
+        }
+    }
+}
+
+/// Number of body bytes to read per response, `None` to only await the head
+pub fn body_read_limit(
+    read_body_bytes: Option<usize>,
+    assertion: Option<&BodyAssertion>,
+) -> Option<usize> {
+    read_body_bytes.or(assertion.map(|_| DEFAULT_ASSERT_BODY_BYTES))
+}
+
+/// Method actually sent for an entry, GET becomes HEAD with `auto_head` unless the body is read
+pub fn effective_method(method: Method, auto_head: bool, read_body_bytes: Option<usize>) -> Method {
+    if auto_head && method == Method::GET && read_body_bytes.is_none() {
+        Method::HEAD
+    } else {
+        method
+    }
+}
+
+/// User-Agent sent when the config does not set one
+pub const DEFAULT_USER_AGENT: &str = concat!("pinger/", env!("CARGO_PKG_VERSION"));
+
+/// Client settings shared by every HTTP entry
+#[derive(Debug, Clone)]
+pub struct HttpClientOptions {
+    pub proxy: Option<HttpProxy>,
+    /// User-Agent header value, no header is sent when empty
+    pub user_agent: String,
+    /// Keep connections open between pings instead of connecting every time
+    pub reuse_connections: bool,
+    /// Age after which a reused connection is replaced, only set with `reuse_connections`
+    pub connection_max_age: Option<Duration>,
+    /// Read up to this many body bytes per response
+    pub read_body_bytes: Option<usize>,
+    /// Send HEAD for GET entries that don't read the body
+    pub auto_head: bool,
+    /// Bound on connection setup, the whole ping timeout when unset
+    pub connect_timeout: Option<Duration>,
+    /// Response headers copied into [`PingResponse::headers`]
+    pub capture_headers: Vec<HeaderName>,
+    /// Stop once connected and report [`PingResult::Connected`]
+    pub connect_only: bool,
+    /// Keep a connection open but discard it before every other tick
+    pub compare_cold_warm: bool,
+}
+
+impl HttpClientOptions {
+    pub fn from_config(config: &HttpPingerConfig) -> Result<Self> {
+        Ok(Self {
+            proxy: HttpProxy::from_config(config)?,
+            user_agent: config
+                .user_agent
+                .clone()
+                .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+            reuse_connections: config.reuse_connections,
+            connection_max_age: config
+                .connection_max_age_millis
+                .filter(|_| config.reuse_connections)
+                .map(Duration::from_millis),
+            read_body_bytes: config.read_body_bytes,
+            auto_head: config.auto_head,
+            connect_timeout: config.connect_timeout_millis.map(Duration::from_millis),
+            capture_headers: config
+                .capture_headers
+                .iter()
+                .map(|name| {
+                    HeaderName::from_bytes(name.trim().as_bytes()).map_err(|e| {
+                        anyhow::anyhow!("Invalid capture_headers entry {:?}: {}", name, e)
+                    })
+                })
+                .collect::<Result<_>>()?,
+            connect_only: config.connect_only,
+            compare_cold_warm: config.compare_cold_warm,
+        })
+    }
+}
+
+/// Fluent construction of an HTTP pinger outside of a configuration, e.g.
+/// `HttpPingerBuilder::new("https://example.com/").method("HEAD").build::<HyperPinger>()`
+#[derive(Debug, Clone)]
+pub struct HttpPingerBuilder {
+    entry: HttpPingerEntry,
+    timeout: Duration,
+    options: HttpClientOptions,
+    /// The system resolver when unset
+    resolver: Option<Arc<dyn Resolve>>,
+}
+
+impl HttpPingerBuilder {
+    /// Builder for `GET url` with the defaults of an `http` config section
+    pub fn new(url: impl Into<String>) -> Self {
+        let config = HttpPingerConfig::default();
+        HttpPingerBuilder {
+            entry: HttpPingerEntry {
+                url: url.into(),
+                method: "GET".to_string(),
+                methods: None,
+                pinger: None,
+                proxy: None,
+                expect_body_contains: None,
+                expect_body_regex: None,
+                pinned_cert_sha256: None,
+                slo_millis: None,
+            },
+            timeout: Duration::from_millis(config.timeout_millis),
+            options: HttpClientOptions::from_config(&config)
+                .expect("the default HTTP client options are valid"),
+            resolver: None,
+        }
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.entry.url = url.into();
+        self
+    }
+
+    /// Request method, case-insensitive
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.entry.method = method.into();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// User-Agent header value, an empty string sends none
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.options.user_agent = user_agent.into();
+        self
+    }
+
+    /// Response headers copied into [`PingResponse::headers`]
+    pub fn headers(mut self, names: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.options.capture_headers = names.into_iter().collect();
+        self
+    }
+
+    /// Fail pings whose response body does not contain `needle`
+    pub fn expect_body_contains(mut self, needle: impl Into<String>) -> Self {
+        self.entry.expect_body_contains = Some(needle.into());
+        self
+    }
+
+    /// Client settings beyond the ones of this builder, replacing those already set
+    pub fn options(mut self, options: HttpClientOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn resolver(mut self, resolver: Arc<dyn Resolve>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Validate the settings and create a pinger of backend `P`
+    pub fn build<P: AsyncHttpPinger>(mut self) -> Result<P> {
+        self.entry.method = self.entry.method.trim().to_ascii_uppercase();
+        if self.timeout.is_zero() {
+            anyhow::bail!("HTTP {}: timeout must not be 0", self.entry.url);
+        }
+        if let Some(connect_timeout) = self.options.connect_timeout
+            && (connect_timeout.is_zero() || connect_timeout > self.timeout)
+        {
+            anyhow::bail!(
+                "HTTP {}: connect timeout must be non-zero and at most the timeout",
+                self.entry.url
+            );
+        }
+        let resolver = match self.resolver {
+            Some(resolver) => resolver,
+            None => system_resolver()?,
+        };
+        P::new(self.entry, self.timeout, self.options, resolver)
+    }
+}
+
+/// Captured headers that are also counted by value, their values being few per endpoint
+pub const LABELED_HEADERS: [HeaderName; 3] = [
+    header::SERVER,
+    HeaderName::from_static("x-cache"),
+    HeaderName::from_static("cf-cache-status"),
+];
+
+/// Delay asked for by the `Retry-After` header of a 429 or 503 response, in seconds or as an
+/// HTTP date; a date in the past asks for no delay
+pub fn retry_after(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    if !matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+    let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+        }
+    }
+}
+
+/// Length of a typical response status line, e.g. `HTTP/1.1 200 OK`
+pub const STATUS_LINE_BYTES: usize = 15;
+
+/// Approximate size of a message head with a start line of `start_line` bytes, counted as
+/// HTTP/1.1 text whatever the protocol version
+pub fn head_bytes(start_line: usize, headers: &HeaderMap) -> u64 {
+    let headers: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    (start_line + headers + 4) as u64
+}
+
+/// Length of the request line `METHOD target HTTP/1.1`
+pub fn request_line_bytes(method: &Method, target: &str) -> usize {
+    method.as_str().len() + target.len() + 10
+}
+
+/// Values of the `names` headers present in `headers`, repeated headers joined by ", "
+pub fn capture_headers(names: &[HeaderName], headers: &HeaderMap) -> Vec<(HeaderName, String)> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let values: Vec<_> = headers
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect();
+            (!values.is_empty()).then(|| (name.clone(), values.join(", ")))
+        })
+        .collect()
+}
+
+/// Forward proxy used to reach HTTP endpoints
+#[derive(Debug, Clone)]
+pub struct HttpProxy {
+    pub url: url::Url,
+    pub no_proxy: Vec<String>,
+}
+
+impl HttpProxy {
+    pub fn from_config(config: &HttpPingerConfig) -> Result<Option<Self>> {
+        let Some(proxy) = &config.proxy else {
+            return Ok(None);
+        };
+        Ok(Some(Self::parse(proxy, config.no_proxy.clone())?))
+    }
+
+    /// Proxy of `entry`: its own `proxy`, none for `direct`, or the group's `proxy` when unset
+    pub fn for_entry(entry: &HttpPingerEntry, group: Option<HttpProxy>) -> Result<Option<Self>> {
+        match entry.proxy.as_deref().map(str::trim) {
+            None => Ok(group),
+            Some(proxy) if proxy.eq_ignore_ascii_case("direct") => Ok(None),
+            Some(proxy) => Ok(Some(Self::parse(proxy, Vec::new())?)),
+        }
+    }
+
+    fn parse(proxy: &str, no_proxy: Vec<String>) -> Result<Self> {
+        let url = proxy
+            .trim()
+            .parse::<url::Url>()
+            .map_err(|e| anyhow::anyhow!("Invalid proxy URL {}: {}", proxy, e))?;
+        if url.host_str().is_none() || url.port_or_known_default().is_none() {
+            anyhow::bail!("Invalid proxy URL: host or port is missing in {}", url);
+        }
+        Ok(Self { url, no_proxy })
+    }
+
+    /// Whether `host` matches a `no_proxy` exclusion and must be reached directly
+    pub fn bypasses(&self, host: &str) -> bool {
+        self.no_proxy.iter().any(|pattern| {
+            let pattern = pattern.trim().trim_start_matches('.');
+            pattern == "*"
+                || host.eq_ignore_ascii_case(pattern)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", pattern.to_ascii_lowercase()))
+        })
     }
 }
 
@@ -39,8 +603,28 @@ pub struct PingResponse {
     pub url: String,
     pub ip: Option<String>,
     pub send_time: Instant,
+    /// Wall-clock counterpart of `send_time`, for correlating pings with external events
+    pub sent_at: SystemTime,
     pub method: Method,
+    /// 1-based retry attempt that produced this response
+    pub attempt: u8,
     pub result: PingResult,
+    /// Values of the configured `capture_headers` the response carried
+    pub headers: Vec<(HeaderName, String)>,
+    /// Delay a 429 or 503 response asked for with `Retry-After`
+    pub retry_after: Option<Duration>,
+    /// Approximate bytes exchanged: estimated handshakes, request and response heads and the
+    /// body bytes read
+    pub bytes: u64,
+}
+
+impl PingResponse {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self.result,
+            PingResult::Success { .. } | PingResult::Connected { .. }
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -48,9 +632,78 @@ pub struct PingResponse {
 pub enum PingResult {
     Success {
         http_status: u16,
-        response_time: Duration,
+        /// Time from the start of the attempt until the response head arrived. The attempt
+        /// starts before connecting (hyper) or before handing the request to the client
+        /// (reqwest), so this includes connection setup unless a kept-alive connection is used
+        ttfb: Duration,
+        /// Time from the start of the attempt until the response was done with: `ttfb` plus
+        /// the body download when the body is read, otherwise equal to `ttfb`
+        total_time: Duration,
         version: hyper::Version,
+        /// Time spent resolving the host, `None` when the backend resolves internally
+        /// (reqwest) and cannot report it separately
+        dns_time: Option<Duration>,
+        /// Whether the request went over a kept-alive connection, `None` when the backend
+        /// (reqwest) pools connections internally and cannot tell
+        connection_reused: Option<bool>,
+        /// Body bytes read, `None` unless the body is read
+        body_bytes: Option<usize>,
+        /// Local address the request was sent from, `None` when the backend (reqwest) does
+        /// not expose its connections
+        local_addr: Option<SocketAddr>,
+    },
+    /// The connection was set up in `connect_only` mode and closed without a request
+    Connected {
+        /// Time from the start of the attempt until the TCP connection, proxy tunnel and
+        /// TLS handshake were done
+        connect_time: Duration,
+        dns_time: Option<Duration>,
+        local_addr: Option<SocketAddr>,
     },
-    Failure(String),
-    Timeout,
+    Failure {
+        failure_type: FailureType,
+        reason: String,
+    },
+    /// The response arrived but its body failed the entry's assertion
+    AssertionFailure { http_status: u16, reason: String },
+    /// The ping ran out of time during the `cause` phase
+    Timeout { cause: TimeoutCause },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn cert_pin_parses_plain_hex() {
+        let pin = CertPin::parse(FINGERPRINT).unwrap();
+        assert_eq!(pin.0[..4], [0x01, 0x23, 0x45, 0x67]);
+        assert!(CertPin::parse(&FINGERPRINT.to_uppercase()).is_some());
+    }
+
+    #[test]
+    fn cert_pin_parses_colon_separated_hex() {
+        let separated = FINGERPRINT
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(
+            CertPin::parse(&separated).unwrap().0,
+            CertPin::parse(FINGERPRINT).unwrap().0
+        );
+    }
+
+    #[test]
+    fn cert_pin_rejects_odd_and_short_input() {
+        assert!(CertPin::parse("abc").is_none());
+        assert!(CertPin::parse(&FINGERPRINT[1..]).is_none());
+        assert!(CertPin::parse(&FINGERPRINT[..62]).is_none());
+        assert!(CertPin::parse("").is_none());
+        assert!(CertPin::parse(&format!("{}00", FINGERPRINT)).is_none());
+        assert!(CertPin::parse(&FINGERPRINT.replace('a', "g")).is_none());
+    }
 }
@@ -0,0 +1,317 @@
+use crate::config::HttpPingerEntry;
+use crate::http_pinger::{
+    AsyncHttpPinger, BodyAssertion, CertPin, HttpClientOptions, PinMismatch, PingResponse,
+    PingResult, STATUS_LINE_BYTES, UrlTemplate, body_read_limit, capture_headers, effective_method,
+    head_bytes, request_line_bytes, retry_after,
+};
+use crate::metric::{
+    ByteTally, FailureType, PingPhase, QUIC_HANDSHAKE_BYTES, TimeoutCause, wall_clock,
+};
+use crate::resolver::{Resolve, ResolveDeadline, resolve_str};
+use crate::tls;
+use async_trait::async_trait;
+use hyper::body::{Buf, Bytes};
+use hyper::header::HeaderName;
+use hyper::{Method, Request, Version};
+use quinn::crypto::rustls::QuicClientConfig;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tracing::{Instrument, info_span, instrument};
+
+/// HTTP/3 pinger: every ping performs a fresh QUIC handshake followed by one request
+#[derive(Clone, Debug)]
+pub struct H3Pinger {
+    /// Reported URL, with the tokens of a templated one removed
+    url: url::Url,
+    template: Option<UrlTemplate>,
+    port: u16,
+    method: Method,
+    timeout: Duration,
+    connect_timeout: Option<Duration>,
+    client_config: quinn::ClientConfig,
+    user_agent: String,
+    read_body_bytes: Option<usize>,
+    assertion: Option<BodyAssertion>,
+    capture_headers: Vec<HeaderName>,
+    pin: Option<CertPin>,
+    resolver: Arc<dyn Resolve>,
+}
+
+impl H3Pinger {
+    #[instrument(fields(url = %self.url, method = %self.method), skip(self, deadline))]
+    async fn resolve(&self, deadline: Instant) -> anyhow::Result<SocketAddr> {
+        let ip = match self.url.host().unwrap() {
+            url::Host::Domain(name) => resolve_str(self.resolver.as_ref(), name, deadline).await?,
+            url::Host::Ipv4(ip) => ip.into(),
+            url::Host::Ipv6(ip) => ip.into(),
+        };
+        Ok(SocketAddr::new(ip, self.port))
+    }
+
+    /// Complete the QUIC handshake, returning the HTTP/3 request sender and the local address
+    async fn connect(
+        &self,
+        addr: SocketAddr,
+    ) -> anyhow::Result<(
+        h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>,
+        SocketAddr,
+    )> {
+        let bind: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let mut endpoint = quinn::Endpoint::client(bind)?;
+        endpoint.set_default_client_config(self.client_config.clone());
+        let connection = endpoint
+            .connect(addr, self.url.host_str().unwrap())?
+            .instrument(info_span!("quic_handshake", %addr))
+            .await?;
+        if let Some(pin) = &self.pin {
+            let certs = connection
+                .peer_identity()
+                .and_then(|identity| identity.downcast::<Vec<CertificateDer>>().ok());
+            let leaf = certs.as_ref().and_then(|certs| certs.first());
+            pin.check(leaf.map(|cert| cert.as_ref()))?;
+        }
+        let bound = endpoint.local_addr()?;
+        let local_addr = SocketAddr::new(connection.local_ip().unwrap_or(bound.ip()), bound.port());
+
+        let (mut driver, sender) = h3::client::new(h3_quinn::Connection::new(connection))
+            .instrument(info_span!("http_handshake"))
+            .await?;
+        // The driver must be polled for requests to make progress, it ends with the connection
+        tokio::spawn(async move { driver.wait_idle().await });
+        Ok((sender, local_addr))
+    }
+
+    fn build_request(&self) -> anyhow::Result<Request<()>> {
+        let url = match &self.template {
+            Some(template) => template.expand()?.to_string(),
+            None => self.url.to_string(),
+        };
+        let mut builder = Request::builder().method(self.method.clone()).uri(url);
+        if !self.user_agent.is_empty() {
+            builder = builder.header(hyper::header::USER_AGENT, &self.user_agent);
+        }
+        Ok(builder.body(())?)
+    }
+
+    #[instrument(fields(url = %self.url, method = %self.method), skip(self, phase, bytes))]
+    async fn ping_inner(
+        &self,
+        phase: &PingPhase,
+        bytes: &ByteTally,
+    ) -> anyhow::Result<PingResponse> {
+        let req = self.build_request()?;
+        let request_bytes = head_bytes(
+            request_line_bytes(req.method(), &req.uri().to_string()),
+            req.headers(),
+        );
+
+        let resolve_begin = Instant::now();
+        let addr = match self.resolve(resolve_begin + self.timeout).await {
+            Ok(addr) => addr,
+            Err(e) if e.is::<ResolveDeadline>() => {
+                return Ok(self.wrap_timeout(TimeoutCause::Dns, resolve_begin));
+            }
+            Err(e) => return Ok(self.wrap_soft_err(FailureType::Dns, e, resolve_begin)),
+        };
+        let dns_time = resolve_begin.elapsed();
+
+        phase.enter(TimeoutCause::Connect);
+        bytes.add(QUIC_HANDSHAKE_BYTES);
+        let begin = Instant::now();
+        let connected = match self.connect_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, self.connect(addr)).await {
+                Ok(connected) => connected,
+                Err(_) => {
+                    let reason = format!("connect timed out after {}ms", timeout.as_millis());
+                    let failure_type = FailureType::ConnectTimeout;
+                    return Ok(self.wrap_soft_err(failure_type, reason, Instant::now()));
+                }
+            },
+            None => self.connect(addr).await,
+        };
+        let (mut sender, local_addr) = match connected {
+            Ok(connected) => connected,
+            Err(e) => {
+                // TLS alerts surface as QUIC CRYPTO_ERROR codes, not as `rustls::Error`
+                let failure_type = match e.downcast_ref::<quinn::ConnectionError>() {
+                    _ if e.is::<PinMismatch>() => FailureType::PinMismatch,
+                    Some(quinn::ConnectionError::TransportError(e))
+                        if (0x100..0x200).contains(&u64::from(e.code)) =>
+                    {
+                        FailureType::Tls
+                    }
+                    _ => FailureType::Connect,
+                };
+                return Ok(self.wrap_soft_err(failure_type, e, Instant::now()));
+            }
+        };
+
+        phase.enter(TimeoutCause::Response);
+        bytes.add(request_bytes);
+        let exchange = async {
+            let mut stream = sender.send_request(req).await?;
+            stream.finish().await?;
+            let response = stream.recv_response().await?;
+            anyhow::Ok((stream, response))
+        };
+        let (mut stream, response) = match exchange.await {
+            Ok(exchange) => exchange,
+            Err(e) => return Err(anyhow::anyhow!("Failed to send request: {}", e)),
+        };
+        let ttfb = begin.elapsed();
+        let status = response.status();
+        let headers = capture_headers(&self.capture_headers, response.headers());
+        let retry_after = retry_after(status, response.headers());
+        bytes.add(head_bytes(STATUS_LINE_BYTES, response.headers()));
+
+        let body_read = match self.read_body_bytes {
+            Some(limit) => {
+                let mut read = Vec::new();
+                while read.len() < limit {
+                    match stream.recv_data().await {
+                        Ok(Some(mut chunk)) => {
+                            bytes.add(chunk.remaining() as u64);
+                            read.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()))
+                        }
+                        Ok(None) => break,
+                        Err(e) => return Ok(self.wrap_soft_err(FailureType::Protocol, e, begin)),
+                    }
+                }
+                read.truncate(limit);
+                Some(read)
+            }
+            None => None,
+        };
+        let total_time = begin.elapsed();
+
+        let assertion = match (&self.assertion, &body_read) {
+            (Some(assertion), Some(read)) => assertion.check(read).err(),
+            _ => None,
+        };
+        let result = match assertion {
+            Some(reason) => PingResult::AssertionFailure {
+                http_status: status.as_u16(),
+                reason,
+            },
+            None => PingResult::Success {
+                http_status: status.as_u16(),
+                ttfb,
+                total_time,
+                version: Version::HTTP_3,
+                dns_time: Some(dns_time),
+                connection_reused: Some(false),
+                body_bytes: body_read.map(|read| read.len()),
+                local_addr: Some(local_addr),
+            },
+        };
+        Ok(PingResponse {
+            url: self.url.to_string(),
+            ip: Some(addr.ip().to_string()),
+            send_time: begin,
+            sent_at: wall_clock(begin),
+            method: self.method.clone(),
+            attempt: 1,
+            result,
+            headers,
+            retry_after,
+            bytes: bytes.total(),
+        })
+    }
+}
+
+#[async_trait]
+impl AsyncHttpPinger for H3Pinger {
+    #[instrument(fields(url = %self.url, method = %self.method), skip(self))]
+    async fn ping(&self) -> anyhow::Result<PingResponse> {
+        let begin = Instant::now();
+        let phase = PingPhase::new(TimeoutCause::Dns);
+        let bytes = ByteTally::default();
+        // Soft errors are built before knowing what was sent, so every outcome takes the tally
+        match tokio::time::timeout(self.timeout, self.ping_inner(&phase, &bytes)).await {
+            Ok(res) => res.map(|response| PingResponse {
+                bytes: bytes.total(),
+                ..response
+            }),
+            Err(_) => Ok(PingResponse {
+                url: self.url.to_string(),
+                ip: None,
+                send_time: begin,
+                sent_at: wall_clock(begin),
+                method: self.method.clone(),
+                attempt: 1,
+                result: PingResult::Timeout {
+                    cause: phase.current(),
+                },
+                headers: Vec::new(),
+                retry_after: None,
+                bytes: bytes.total(),
+            }),
+        }
+    }
+
+    fn new(
+        entry: HttpPingerEntry,
+        timeout: Duration,
+        options: HttpClientOptions,
+        resolver: Arc<dyn Resolve>,
+    ) -> anyhow::Result<Self> {
+        let assertion = BodyAssertion::from_entry(&entry)?;
+        let pin = CertPin::from_entry(&entry)?;
+        let HttpPingerEntry { url, method, .. } = entry;
+        let method = Method::from_str(&method)
+            .map_err(|e| anyhow::anyhow!("Invalid HTTP method: {}: {}", method, e))?;
+        let read_body_bytes = body_read_limit(options.read_body_bytes, assertion.as_ref());
+        let method = effective_method(method, options.auto_head, read_body_bytes);
+        let (url, template) = UrlTemplate::parse(&url)?;
+        if url.scheme() != "https" {
+            anyhow::bail!("HTTP/3 requires an https URL: {}", url);
+        }
+        if url.host_str().is_none() {
+            anyhow::bail!("Invalid URL: Host is missing in {}", url);
+        }
+        if options.proxy.is_some() {
+            anyhow::bail!("HTTP proxies are not supported by the H3 backend");
+        }
+        if options.connect_only {
+            anyhow::bail!("connect_only is only supported by the Hyper backend");
+        }
+        if options.compare_cold_warm {
+            anyhow::bail!("compare_cold_warm is only supported by the Hyper backend");
+        }
+
+        // TLS setup, advertising HTTP/3 through ALPN
+        let tls_config = tls::client_config(&[b"h3"]);
+        let client_config =
+            quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(tls_config)?));
+
+        Ok(H3Pinger {
+            port: url.port_or_known_default().unwrap(),
+            url,
+            template,
+            method,
+            timeout,
+            connect_timeout: options.connect_timeout,
+            client_config,
+            user_agent: options.user_agent,
+            read_body_bytes,
+            assertion,
+            capture_headers: options.capture_headers,
+            pin,
+            resolver,
+        })
+    }
+
+    fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    fn method(&self) -> &Method {
+        &self.method
+    }
+}
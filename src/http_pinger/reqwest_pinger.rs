@@ -1,46 +1,199 @@
 use crate::config::HttpPingerEntry;
-use crate::http_pinger::{AsyncHttpPinger, PingResponse, PingResult};
+use crate::http_pinger::{
+    AsyncHttpPinger, BodyAssertion, CertPin, HttpClientOptions, HttpProxy, PingResponse,
+    PingResult, STATUS_LINE_BYTES, UnixTarget, UrlTemplate, body_read_limit, capture_headers,
+    classify_error, effective_method, head_bytes, request_line_bytes, retry_after,
+};
+use crate::metric::{
+    ByteTally, FailureType, PingPhase, TCP_CONNECT_BYTES, TLS_HANDSHAKE_BYTES, TimeoutCause,
+    wall_clock,
+};
 use crate::resolver::Resolve;
 use async_trait::async_trait;
 use hyper::Method;
+use hyper::header::{HeaderMap, HeaderName};
 use reqwest::redirect::Policy;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tracing::instrument;
+use tracing::{debug, instrument};
 
 #[derive(Debug, Clone)]
-pub(crate) struct ReqwestPinger {
+pub struct ReqwestPinger {
+    /// Reported URL, with the tokens of a templated one removed
     url: url::Url,
+    template: Option<UrlTemplate>,
     method: Method,
     timeout: Duration,
-    reqwest_client: reqwest::Client,
+    reuse_connections: bool,
+    read_body_bytes: Option<usize>,
+    assertion: Option<BodyAssertion>,
+    capture_headers: Vec<HeaderName>,
+    /// Checked once the response head arrived, reqwest does not expose the handshake
+    pin: Option<CertPin>,
+    reqwest_client: Arc<Mutex<AgedClient>>,
+    /// Age after which the client and its pooled connections are replaced
+    max_age: Option<Duration>,
+    options: HttpClientOptions,
+    resolver: Arc<dyn Resolve>,
+}
+
+#[derive(Debug)]
+struct AgedClient {
+    client: reqwest::Client,
+    built: Instant,
 }
 
 impl ReqwestPinger {
-    #[instrument(fields(url = %self.url, method = %self.method), skip(self))]
-    async fn ping_inner(&self) -> anyhow::Result<PingResponse> {
-        let builder = self
-            .reqwest_client
-            .request(self.method.clone(), self.url.clone());
+    fn build_client(
+        timeout: Duration,
+        options: &HttpClientOptions,
+        resolver: Arc<dyn Resolve>,
+        tls_info: bool,
+    ) -> anyhow::Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(options.connect_timeout.unwrap_or(timeout))
+            .no_hickory_dns()
+            .dns_resolver2(resolver as Arc<dyn reqwest::dns::Resolve>)
+            .redirect(Policy::none())
+            .tls_info(tls_info);
+        if !options.reuse_connections {
+            builder = builder.pool_max_idle_per_host(0);
+        }
+        if !options.user_agent.is_empty() {
+            builder = builder.user_agent(&options.user_agent);
+        }
+        if let Some(HttpProxy { url, no_proxy }) = &options.proxy {
+            let no_proxy = reqwest::NoProxy::from_string(&no_proxy.join(","));
+            builder = builder.proxy(reqwest::Proxy::all(url.clone())?.no_proxy(no_proxy));
+        }
+        Ok(builder.build()?)
+    }
+
+    /// The client to ping with, rebuilt once older than `max_age` so that the next ping
+    /// opens a fresh connection; requests in flight keep the old pool until they finish
+    fn client(&self) -> anyhow::Result<reqwest::Client> {
+        let mut aged = self.reqwest_client.lock().unwrap();
+        if let Some(max_age) = self.max_age
+            && aged.built.elapsed() >= max_age
+        {
+            debug!("Replacing connection pool of {} after max age", self.url);
+            *aged = AgedClient {
+                client: Self::build_client(
+                    self.timeout,
+                    &self.options,
+                    self.resolver.clone(),
+                    self.pin.is_some(),
+                )?,
+                built: Instant::now(),
+            };
+        }
+        Ok(aged.client.clone())
+    }
+
+    #[instrument(fields(url = %self.url, method = %self.method), skip(self, phase, bytes))]
+    async fn ping_inner(
+        &self,
+        phase: &PingPhase,
+        bytes: &ByteTally,
+    ) -> anyhow::Result<PingResponse> {
+        let url = match &self.template {
+            Some(template) => template.expand()?,
+            None => self.url.clone(),
+        };
+        // Pooled connections are opened out of sight, only an unpooled one is known to connect
+        if !self.reuse_connections {
+            bytes.add(TCP_CONNECT_BYTES);
+            if url.scheme() == "https" {
+                bytes.add(TLS_HANDSHAKE_BYTES);
+            }
+        }
+        // The headers reqwest adds are not known here, only the request line is counted
+        bytes.add(head_bytes(
+            request_line_bytes(&self.method, url.as_str()),
+            &HeaderMap::new(),
+        ));
+        let builder = self.client()?.request(self.method.clone(), url);
         let begin = Instant::now();
         match builder.send().await {
-            Ok(response) => {
-                let response_time = begin.elapsed();
+            Ok(mut response) => {
+                let ttfb = begin.elapsed();
+                phase.enter(TimeoutCause::Response);
+                if let Some(pin) = &self.pin {
+                    let tls_info = response.extensions().get::<reqwest::tls::TlsInfo>();
+                    let leaf = tls_info.and_then(|info| info.peer_certificate());
+                    if let Err(e) = pin.check(leaf) {
+                        return Ok(self.wrap_soft_err(FailureType::PinMismatch, e, begin));
+                    }
+                }
                 let status = response.status();
+                let headers = capture_headers(&self.capture_headers, response.headers());
+                let retry_after = retry_after(status, response.headers());
+                bytes.add(head_bytes(STATUS_LINE_BYTES, response.headers()));
+                let ip = response.remote_addr().unwrap().to_string();
+                let version = response.version();
+                let body_read = match self.read_body_bytes {
+                    Some(limit) => {
+                        let mut read = Vec::new();
+                        while read.len() < limit {
+                            match response.chunk().await {
+                                Ok(Some(chunk)) => read.extend_from_slice(&chunk),
+                                Ok(None) => break,
+                                Err(e) => {
+                                    return Ok(self.wrap_soft_err(FailureType::Protocol, e, begin));
+                                }
+                            }
+                        }
+                        read.truncate(limit);
+                        Some(read)
+                    }
+                    None => None,
+                };
+                let total_time = begin.elapsed();
+                bytes.add(body_read.as_ref().map_or(0, |read| read.len() as u64));
+                if self.reuse_connections {
+                    // Only a fully read response returns its connection to the pool
+                    if let Ok(rest) = response.bytes().await {
+                        bytes.add(rest.len() as u64);
+                    }
+                }
+                let assertion = match (&self.assertion, &body_read) {
+                    (Some(assertion), Some(read)) => assertion.check(read).err(),
+                    _ => None,
+                };
+                let result = match assertion {
+                    Some(reason) => PingResult::AssertionFailure {
+                        http_status: status.as_u16(),
+                        reason,
+                    },
+                    None => PingResult::Success {
+                        http_status: status.as_u16(),
+                        ttfb,
+                        total_time,
+                        version,
+                        dns_time: None,
+                        connection_reused: None,
+                        body_bytes: body_read.map(|read| read.len()),
+                        local_addr: None,
+                    },
+                };
                 Ok(PingResponse {
                     url: self.url.to_string(),
-                    ip: Some(response.remote_addr().unwrap().to_string()),
+                    ip: Some(ip),
                     send_time: begin,
+                    sent_at: wall_clock(begin),
                     method: self.method.clone(),
-                    result: PingResult::Success {
-                        http_status: status.as_u16(),
-                        response_time,
-                        version: response.version(),
-                    },
+                    attempt: 1,
+                    result,
+                    headers,
+                    retry_after,
+                    bytes: bytes.total(),
                 })
             }
-            Err(e) => Ok(self.wrap_soft_err(e, begin)),
+            Err(e) if e.is_connect() && e.is_timeout() => {
+                Ok(self.wrap_soft_err(FailureType::ConnectTimeout, e, begin))
+            }
+            Err(e) => Ok(self.wrap_soft_err(classify_error(&e), e, begin)),
         }
     }
 }
@@ -51,28 +204,60 @@ impl AsyncHttpPinger for ReqwestPinger {
     async fn ping(&self) -> anyhow::Result<PingResponse> {
         use tokio::time::timeout;
         let task_submission_time = Instant::now();
-        let result = timeout(self.timeout, self.ping_inner()).await;
+        let phase = PingPhase::new(TimeoutCause::Request);
+        let bytes = ByteTally::default();
+        let result = timeout(self.timeout, self.ping_inner(&phase, &bytes)).await;
 
+        // Soft errors are built before knowing what was sent, so every outcome takes the tally
         match result {
-            Ok(res) => res,
+            Ok(res) => res.map(|response| PingResponse {
+                bytes: bytes.total(),
+                ..response
+            }),
             Err(_) => Ok(PingResponse {
                 url: self.url.to_string(),
                 ip: None,
                 method: self.method.clone(),
                 send_time: task_submission_time,
-                result: PingResult::Timeout,
+                sent_at: wall_clock(task_submission_time),
+                attempt: 1,
+                result: PingResult::Timeout {
+                    cause: phase.current(),
+                },
+                headers: Vec::new(),
+                retry_after: None,
+                bytes: bytes.total(),
             }),
         }
     }
     fn new(
-        HttpPingerEntry { url, method }: HttpPingerEntry,
+        entry: HttpPingerEntry,
         timeout: Duration,
+        options: HttpClientOptions,
         resolver: Arc<dyn Resolve>,
     ) -> anyhow::Result<Self> {
+        let assertion = BodyAssertion::from_entry(&entry)?;
+        let pin = CertPin::from_entry(&entry)?;
+        let HttpPingerEntry { url, method, .. } = entry;
         let method = Method::from_str(&method)
             .map_err(|e| anyhow::anyhow!("Invalid HTTP method: {}: {}", method, e))?;
-        let url = url.trim().to_string().parse::<url::Url>()?;
+        let read_body_bytes = body_read_limit(options.read_body_bytes, assertion.as_ref());
+        let method = effective_method(method, options.auto_head, read_body_bytes);
+        let (url, template) = UrlTemplate::parse(&url)?;
 
+        if url.scheme() == UnixTarget::SCHEME {
+            anyhow::bail!(
+                "{} URLs are only supported by the Hyper backend: {}",
+                UnixTarget::SCHEME,
+                url
+            );
+        }
+        if options.connect_only {
+            anyhow::bail!("connect_only is only supported by the Hyper backend");
+        }
+        if options.compare_cold_warm {
+            anyhow::bail!("compare_cold_warm is only supported by the Hyper backend");
+        }
         if url.host().is_none() {
             return Err(anyhow::anyhow!("Invalid URL: Host is missing in {}", url));
         }
@@ -80,18 +265,25 @@ impl AsyncHttpPinger for ReqwestPinger {
             return Err(anyhow::anyhow!("Unsupported URL scheme: {}", url));
         }
 
-        let builder = reqwest::Client::builder()
-            .connect_timeout(timeout)
-            .pool_max_idle_per_host(0)
-            .no_hickory_dns()
-            .dns_resolver2(resolver as Arc<dyn reqwest::dns::Resolve>)
-            .redirect(Policy::none());
+        let client = Self::build_client(timeout, &options, resolver.clone(), pin.is_some())?;
 
         Ok(ReqwestPinger {
             url,
+            template,
             method,
             timeout,
-            reqwest_client: builder.build()?,
+            reuse_connections: options.reuse_connections,
+            read_body_bytes,
+            assertion,
+            capture_headers: options.capture_headers.clone(),
+            pin,
+            reqwest_client: Arc::new(Mutex::new(AgedClient {
+                client,
+                built: Instant::now(),
+            })),
+            max_age: options.connection_max_age,
+            options,
+            resolver,
         })
     }
 
@@ -0,0 +1,3030 @@
+//! Probe engine of the pinger: HTTP, TCP, gRPC, WebSocket, DNS and NTP pingers reporting into
+//! [`PingMetrics`], and [`run`] to drive them from a [`PingerConfig`] as the binary does.
+
+use crate::config::{
+    CircuitBreaker, ConfigFormat, FailureBackoff, HttpPinger, HttpPingerEntry, PingerConfig,
+    Retries, RetryMode, ScheduleConfig, TcpPingerEntry,
+};
+use crate::dns_pinger::{DnsPingResponse, DnsPinger};
+use crate::grpc_pinger::{GrpcPingResponse, GrpcPinger};
+use crate::http_pinger::{CertPin, HttpClientOptions, HttpProxy, PingResponse, UnixTarget};
+use crate::metric::{FailureType, HttpEndpointLabel, TimeoutCause};
+use crate::ntp_pinger::{NtpPingResponse, NtpPinger};
+use crate::reload::{ReloadReceiver, ReloadSummary};
+use crate::summary::{Sample, Summary, SummarySender, grpc_target};
+use crate::tcp_pinger::{Socks5Proxy, TcpPingResult, TcpSocketOptions};
+use crate::ws_pinger::{WsPingResponse, WsPinger};
+use anyhow::Result;
+use futures::StreamExt;
+use futures::future::{OptionFuture, select_ok};
+use futures::stream::FuturesUnordered;
+use hyper::Method;
+use hyper::header::HeaderName;
+use regex::Regex;
+use resolver::Resolve;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::select;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, warn};
+
+pub mod config;
+pub mod dns_pinger;
+pub mod event;
+pub mod grpc_pinger;
+pub mod http_pinger;
+pub mod metric;
+pub mod metrics_server;
+pub mod ntp_pinger;
+pub mod reload;
+#[cfg(feature = "remote-write")]
+pub mod remote_write;
+pub mod resolver;
+pub mod summary;
+pub mod tcp_pinger;
+pub mod telemetry;
+pub mod tls;
+pub mod ws_pinger;
+
+pub use event::{EventSender, PingEvent};
+#[cfg(feature = "http3")]
+pub use http_pinger::h3_pinger::H3Pinger;
+pub use http_pinger::hyper_pinger::HyperPinger;
+pub use http_pinger::reqwest_pinger::ReqwestPinger;
+pub use http_pinger::{AsyncHttpPinger, HttpPingerBuilder};
+pub use metric::{PingMetrics, SharedMetrics};
+pub use resolver::build_resolver;
+pub use tcp_pinger::{TcpPinger, TcpPingerBuilder};
+
+/// Enum to hold different HTTP pinger types
+enum HttpPingerImpl {
+    Hyper(HyperPinger),
+    Reqwest(ReqwestPinger),
+    #[cfg(feature = "http3")]
+    H3(H3Pinger),
+}
+
+impl HttpPingerImpl {
+    #[inline]
+    async fn ping(&self) -> Result<PingResponse> {
+        match self {
+            HttpPingerImpl::Hyper(pinger) => pinger.ping().await,
+            HttpPingerImpl::Reqwest(pinger) => pinger.ping().await,
+            #[cfg(feature = "http3")]
+            HttpPingerImpl::H3(pinger) => pinger.ping().await,
+        }
+    }
+
+    fn url(&self) -> &url::Url {
+        match self {
+            HttpPingerImpl::Hyper(pinger) => pinger.url(),
+            HttpPingerImpl::Reqwest(pinger) => pinger.url(),
+            #[cfg(feature = "http3")]
+            HttpPingerImpl::H3(pinger) => pinger.url(),
+        }
+    }
+
+    fn endpoint_label(&self) -> HttpEndpointLabel {
+        let (url, method) = match self {
+            HttpPingerImpl::Hyper(pinger) => (pinger.url(), pinger.method()),
+            HttpPingerImpl::Reqwest(pinger) => (pinger.url(), pinger.method()),
+            #[cfg(feature = "http3")]
+            HttpPingerImpl::H3(pinger) => (pinger.url(), pinger.method()),
+        };
+        HttpEndpointLabel {
+            url: url.to_string(),
+            method: method.to_string(),
+        }
+    }
+
+    /// Failure response for a ping that was not sent
+    fn failure(&self, reason: &str) -> PingResponse {
+        match self {
+            HttpPingerImpl::Hyper(pinger) => {
+                pinger.wrap_soft_err(FailureType::Other, reason, Instant::now())
+            }
+            HttpPingerImpl::Reqwest(pinger) => {
+                pinger.wrap_soft_err(FailureType::Other, reason, Instant::now())
+            }
+            #[cfg(feature = "http3")]
+            HttpPingerImpl::H3(pinger) => {
+                pinger.wrap_soft_err(FailureType::Other, reason, Instant::now())
+            }
+        }
+    }
+
+    fn start_tick(&self) {
+        match self {
+            HttpPingerImpl::Hyper(pinger) => pinger.start_tick(),
+            HttpPingerImpl::Reqwest(pinger) => pinger.start_tick(),
+            #[cfg(feature = "http3")]
+            HttpPingerImpl::H3(pinger) => pinger.start_tick(),
+        }
+    }
+
+    /// Timeout response for a tick whose attempts ran past the total deadline
+    fn deadline_exceeded(&self, begin: Instant) -> PingResponse {
+        let cause = TimeoutCause::Deadline;
+        match self {
+            HttpPingerImpl::Hyper(pinger) => pinger.wrap_timeout(cause, begin),
+            HttpPingerImpl::Reqwest(pinger) => pinger.wrap_timeout(cause, begin),
+            #[cfg(feature = "http3")]
+            HttpPingerImpl::H3(pinger) => pinger.wrap_timeout(cause, begin),
+        }
+    }
+}
+
+/// Global limit on concurrently running pings, unlimited when no semaphore is set
+#[derive(Clone, Default)]
+struct PingLimiter(Option<Arc<Semaphore>>);
+
+impl PingLimiter {
+    fn new(max_concurrent: Option<usize>) -> Self {
+        Self(max_concurrent.map(|permits| Arc::new(Semaphore::new(permits))))
+    }
+
+    /// Wait up to `wait` for a permit, failing if none became available in time or `cancel`
+    /// was cancelled first
+    async fn acquire(
+        &self,
+        wait: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<Option<SemaphorePermit<'_>>> {
+        let Some(semaphore) = &self.0 else {
+            return Ok(None);
+        };
+        select! {
+            _ = cancel.cancelled() => anyhow::bail!("cancelled while waiting for a ping permit"),
+            permit = tokio::time::timeout(wait, semaphore.acquire()) => {
+                let permit = permit
+                    .map_err(|_| anyhow::anyhow!("no ping permit available within {:?}", wait))??;
+                Ok(Some(permit))
+            }
+        }
+    }
+}
+
+/// Longest spacing `host_rate_limit` may ask for, one ping per day
+const MAX_HOST_RATE_PERIOD: Duration = Duration::from_secs(86_400);
+
+/// Per-host pacing shared by every entry targeting the same host, see
+/// [`PingerConfig::host_rate_limit`]
+#[derive(Debug, Clone)]
+struct HostRateLimits {
+    /// Pings per second allowed to each host, unlimited when unset
+    rate: Option<f64>,
+    hosts: Arc<std::sync::Mutex<HashMap<String, Arc<RateLimiter>>>>,
+}
+
+impl HostRateLimits {
+    fn new(rate: Option<f64>) -> Self {
+        Self {
+            rate,
+            hosts: Arc::default(),
+        }
+    }
+
+    /// Limiter of `host`, shared with every other entry targeting it
+    fn for_host(&self, host: &str) -> HostRateLimit {
+        let Some(rate) = self.rate else {
+            return HostRateLimit(None);
+        };
+        let mut hosts = self.hosts.lock().unwrap();
+        let limiter = hosts.entry(host.to_ascii_lowercase()).or_insert_with(|| {
+            Arc::new(RateLimiter {
+                host: host.to_string(),
+                period: Duration::try_from_secs_f64(1.0 / rate)
+                    .unwrap_or(Duration::MAX)
+                    .min(MAX_HOST_RATE_PERIOD),
+                next: std::sync::Mutex::new(tokio::time::Instant::now()),
+            })
+        });
+        HostRateLimit(Some(Arc::clone(limiter)))
+    }
+}
+
+/// Spaces the pings to one host at least `period` apart
+#[derive(Debug)]
+struct RateLimiter {
+    host: String,
+    period: Duration,
+    /// Earliest start of the next ping
+    next: std::sync::Mutex<tokio::time::Instant>,
+}
+
+/// Rate limit applying to the pings of one entry
+struct HostRateLimit(Option<Arc<RateLimiter>>);
+
+impl HostRateLimit {
+    /// Wait for the next free slot of the host, failing without taking it if that is more than
+    /// `wait` away, or failing once `cancel` is cancelled
+    async fn acquire(&self, wait: Duration, cancel: &CancellationToken) -> Result<()> {
+        let Some(limiter) = &self.0 else {
+            return Ok(());
+        };
+        let slot = {
+            let mut next = limiter.next.lock().unwrap();
+            let now = tokio::time::Instant::now();
+            let slot = (*next).max(now);
+            if slot - now > wait {
+                anyhow::bail!(
+                    "rate limit of {} reached, next slot in {:?}",
+                    limiter.host,
+                    slot - now
+                );
+            }
+            let Some(after) = slot.checked_add(limiter.period) else {
+                anyhow::bail!("rate limit of {} has no slot left", limiter.host);
+            };
+            *next = after;
+            slot
+        };
+        select! {
+            _ = cancel.cancelled() => anyhow::bail!("cancelled while waiting for a rate limit slot"),
+            _ = tokio::time::sleep_until(slot) => Ok(()),
+        }
+    }
+}
+
+/// Consecutive failure tracker driving the interval multiplier of a ping task
+struct BackoffState {
+    config: FailureBackoff,
+    failures: u32,
+    multiplier: u32,
+}
+
+impl BackoffState {
+    fn new(config: FailureBackoff) -> Self {
+        Self {
+            config,
+            failures: 0,
+            multiplier: 1,
+        }
+    }
+
+    /// Record the outcome of a tick and return the interval multiplier to apply
+    fn record(&mut self, success: bool) -> u32 {
+        if success {
+            self.failures = 0;
+            self.multiplier = 1;
+        } else {
+            self.failures = self.failures.saturating_add(1);
+            if self.failures >= self.config.threshold {
+                self.multiplier = self
+                    .multiplier
+                    .saturating_mul(self.config.factor.max(1))
+                    .min(self.config.max_multiplier.max(1));
+            }
+        }
+        self.multiplier
+    }
+}
+
+/// Per-entry circuit breaker state.
+///
+/// The circuit opens after `failure_threshold` consecutive failures and stays open for the
+/// cooldown; the first tick after that is a half-open probe that either closes the circuit
+/// or opens it again.
+struct CircuitState {
+    config: CircuitBreaker,
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitState {
+    const OPEN_REASON: &str = "circuit open";
+
+    fn new(config: CircuitBreaker) -> Self {
+        Self {
+            config,
+            failures: 0,
+            open_until: None,
+        }
+    }
+
+    /// Whether a ping may be sent now; false while the circuit is open
+    fn allows(&self) -> bool {
+        self.open_until.is_none_or(|until| Instant::now() >= until)
+    }
+
+    /// Record the outcome of a ping and return whether the circuit is now open
+    fn record(&mut self, success: bool) -> bool {
+        if success {
+            self.failures = 0;
+            self.open_until = None;
+        } else {
+            self.failures = self.failures.saturating_add(1);
+            let half_open = self.open_until.is_some();
+            if half_open || self.failures >= self.config.failure_threshold {
+                self.open_until =
+                    Some(Instant::now() + Duration::from_millis(self.config.cooldown_millis));
+            }
+        }
+        self.open_until.is_some()
+    }
+}
+
+/// Read one configuration file, or stdin when `config_path` is `-`, as `T`
+async fn read_config<T: serde::de::DeserializeOwned>(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+) -> Result<T> {
+    let path = std::path::Path::new(config_path);
+
+    let config_content = if config_path == "-" {
+        let mut content = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read config from stdin: {}", e))?;
+        content
+    } else {
+        tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?
+    };
+    let format = match format {
+        Some(format) => format,
+        None if config_path == "-" => {
+            anyhow::bail!("--config-format is required when reading the config from stdin")
+        }
+        None => {
+            let ext = path
+                .file_name()
+                .ok_or(anyhow::anyhow!("Failed to get file name"))?
+                .to_str()
+                .ok_or(anyhow::anyhow!("Failed to decode file name"))?
+                .split(".")
+                .last()
+                .ok_or(anyhow::anyhow!("Failed to get file extension"))?;
+            match ext {
+                "json" => ConfigFormat::Json,
+                "yaml" => ConfigFormat::Yaml,
+                "toml" => ConfigFormat::Toml,
+                _ => anyhow::bail!("Unsupported file extension: {}", ext),
+            }
+        }
+    };
+    match format {
+        ConfigFormat::Json => serde_json::from_str(&config_content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", config_path, e)),
+        ConfigFormat::Yaml => serde_yaml::from_str(&config_content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", config_path, e)),
+        ConfigFormat::Toml => toml::from_str(&config_content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", config_path, e)),
+    }
+}
+
+/// Load configuration from one or more files, deep-merged in order.
+///
+/// Also returns a description of every scalar a later file overrode, to be logged once tracing
+/// is set up.
+pub async fn load_config(
+    config_paths: &[String],
+    format: Option<ConfigFormat>,
+) -> Result<(PingerConfig, Vec<String>)> {
+    if let [config_path] = config_paths {
+        return Ok((read_config(config_path, format).await?, Vec::new()));
+    }
+    let mut merged = serde_json::Value::Null;
+    let mut overrides = Vec::new();
+    for config_path in config_paths {
+        let value = read_config(config_path, format).await?;
+        merge_config(&mut merged, value, "", &mut overrides);
+    }
+    let config = serde_json::from_value(merged)
+        .map_err(|e| anyhow::anyhow!("Failed to parse merged config: {}", e))?;
+    Ok((config, overrides))
+}
+
+/// Deep-merge `value` into `merged`: objects merge key by key, `entries` lists concatenate and
+/// any other value, lists included, replaces the earlier one
+fn merge_config(
+    merged: &mut serde_json::Value,
+    value: serde_json::Value,
+    path: &str,
+    overrides: &mut Vec<String>,
+) {
+    use serde_json::Value;
+    match (merged, value) {
+        (Value::Object(merged), Value::Object(value)) => {
+            for (key, value) in value {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match merged.get_mut(&key) {
+                    Some(existing) => merge_config(existing, value, &path, overrides),
+                    None => {
+                        merged.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(merged), Value::Array(value)) if path.ends_with(".entries") => {
+            merged.extend(value);
+        }
+        (merged, value) => {
+            if !merged.is_null() && *merged != value {
+                overrides.push(format!("{} overridden: {} -> {}", path, merged, value));
+            }
+            *merged = value;
+        }
+    }
+}
+
+/// Check every entry and the global labels right after loading, so that a typo stops startup
+/// instead of silently dropping the entry; HTTP methods are normalized to upper case.
+///
+/// All problems are reported together in the returned error.
+pub fn validate_config(config: &mut PingerConfig) -> Result<()> {
+    let mut problems = Vec::new();
+
+    for entry in &mut config.http.entries {
+        let methods = std::iter::once(&mut entry.method).chain(entry.methods.iter_mut().flatten());
+        for method in methods {
+            let normalized = method.trim().to_ascii_uppercase();
+            // Extension methods such as PROPFIND or PURGE are valid as long as they are tokens
+            match Method::from_str(&normalized) {
+                Ok(_) => *method = normalized,
+                Err(_) => problems.push(format!("HTTP {}: invalid method {:?}", entry.url, method)),
+            }
+        }
+        if entry.methods.as_ref().is_some_and(Vec::is_empty) {
+            problems.push(format!("HTTP {}: methods must not be empty", entry.url));
+        }
+        match entry
+            .url
+            .trim()
+            .parse::<url::Url>()
+            .ok()
+            .as_ref()
+            .and_then(UnixTarget::parse)
+        {
+            Some(Err(e)) => problems.push(format!("HTTP {}: {}", entry.url, e)),
+            Some(Ok(_))
+                if !matches!(
+                    entry.pinger.unwrap_or(config.http.pinger),
+                    HttpPinger::Hyper
+                ) =>
+            {
+                problems.push(format!(
+                    "HTTP {}: {} URLs require the Hyper pinger",
+                    entry.url,
+                    UnixTarget::SCHEME
+                ))
+            }
+            Some(Ok(_)) => {}
+            None => {
+                if let Err(e) = validate_url(&entry.url, &["http", "https"]) {
+                    problems.push(format!("HTTP {}: {}", entry.url, e));
+                }
+            }
+        }
+        if config.http.connect_only
+            && !matches!(
+                entry.pinger.unwrap_or(config.http.pinger),
+                HttpPinger::Hyper
+            )
+        {
+            problems.push(format!(
+                "HTTP {}: connect_only requires the Hyper pinger",
+                entry.url
+            ));
+        }
+        if config.http.compare_cold_warm
+            && !matches!(
+                entry.pinger.unwrap_or(config.http.pinger),
+                HttpPinger::Hyper
+            )
+        {
+            problems.push(format!(
+                "HTTP {}: compare_cold_warm requires the Hyper pinger",
+                entry.url
+            ));
+        }
+        if let Some(fingerprint) = &entry.pinned_cert_sha256 {
+            if CertPin::parse(fingerprint).is_none() {
+                problems.push(format!(
+                    "HTTP {}: pinned_cert_sha256 {:?} is not a hex SHA-256 fingerprint",
+                    entry.url, fingerprint
+                ));
+            }
+            if !entry.url.trim().starts_with("https://") {
+                problems.push(format!(
+                    "HTTP {}: pinned_cert_sha256 requires an https URL",
+                    entry.url
+                ));
+            }
+        }
+        if entry.slo_millis == Some(0) {
+            problems.push(format!("HTTP {}: slo_millis must not be 0", entry.url));
+        }
+    }
+
+    if let Some(alpha) = config.metrics.ewma_alpha
+        && !(alpha > 0.0 && alpha <= 1.0)
+    {
+        problems.push(format!(
+            "metrics.ewma_alpha must be in (0, 1], got {}",
+            alpha
+        ));
+    }
+    if let Some(rate) = config.host_rate_limit
+        && !(rate.is_finite() && rate >= 1.0 / MAX_HOST_RATE_PERIOD.as_secs_f64())
+    {
+        problems.push(format!(
+            "host_rate_limit must be at least one ping per day, got {}",
+            rate
+        ));
+    }
+    if config.max_bytes_per_minute == Some(0) {
+        problems.push("max_bytes_per_minute must not be 0".to_string());
+    }
+    if config.max_concurrent_pings == Some(0) {
+        problems.push("max_concurrent_pings must not be 0".to_string());
+    }
+    if config
+        .remote_write
+        .as_ref()
+        .is_some_and(|remote_write| remote_write.interval_millis == 0)
+    {
+        problems.push("remote_write.interval_millis must not be 0".to_string());
+    }
+
+    let connect_timeouts = [
+        (
+            "http",
+            config.http.connect_timeout_millis,
+            config.http.timeout_millis,
+        ),
+        (
+            "tcp",
+            config.tcp.connect_timeout_millis,
+            config.tcp.timeout_millis,
+        ),
+    ];
+    let schedules = [
+        (
+            "http",
+            Some((
+                config.http.retries,
+                config.http.interval_millis,
+                config.http.schedule,
+            )),
+        ),
+        (
+            "tcp",
+            Some((
+                config.tcp.retries,
+                config.tcp.interval_millis,
+                config.tcp.schedule,
+            )),
+        ),
+        (
+            "grpc",
+            config
+                .grpc
+                .as_ref()
+                .map(|grpc| (grpc.retries, grpc.interval_millis, grpc.schedule)),
+        ),
+        (
+            "ws",
+            config
+                .ws
+                .as_ref()
+                .map(|ws| (ws.retries, ws.interval_millis, ws.schedule)),
+        ),
+        (
+            "dns",
+            config
+                .dns
+                .as_ref()
+                .map(|dns| (dns.retries, dns.interval_millis, dns.schedule)),
+        ),
+        (
+            "ntp",
+            config
+                .ntp
+                .as_ref()
+                .map(|ntp| (ntp.retries, ntp.interval_millis, ntp.schedule)),
+        ),
+    ];
+    for (section, settings) in schedules {
+        let Some((retries, interval_millis, schedule)) = settings else {
+            continue;
+        };
+        // Every tick waits its offset, so one past the interval would run into the next tick
+        if schedule.jitter_every_tick && schedule.jitter_millis >= interval_millis {
+            problems.push(format!(
+                "{}.jitter_millis ({}) must be below interval_millis ({}) with jitter_every_tick",
+                section, schedule.jitter_millis, interval_millis
+            ));
+        }
+        if let Retries::Backoff(backoff) = retries {
+            if backoff.max_attempts == 0 {
+                problems.push(format!("{}.retries.max_attempts must not be 0", section));
+            }
+            if !(backoff.multiplier.is_finite() && backoff.multiplier >= 1.0) {
+                problems.push(format!(
+                    "{}.retries.multiplier must be at least 1, got {}",
+                    section, backoff.multiplier
+                ));
+            }
+            // A first retry later than the next tick could never be sent
+            if backoff.initial_delay_millis > interval_millis {
+                problems.push(format!(
+                    "{}.retries.initial_delay_millis must not exceed interval_millis ({}), got {}",
+                    section, interval_millis, backoff.initial_delay_millis
+                ));
+            }
+        }
+    }
+
+    // A deadline shorter than one attempt would cut off every first attempt
+    let deadlines = [
+        (
+            "http",
+            config.http.schedule.total_deadline_millis,
+            config.http.timeout_millis,
+        ),
+        (
+            "tcp",
+            config.tcp.schedule.total_deadline_millis,
+            config.tcp.timeout_millis,
+        ),
+        (
+            "grpc",
+            config
+                .grpc
+                .as_ref()
+                .and_then(|grpc| grpc.schedule.total_deadline_millis),
+            config.grpc.as_ref().map_or(0, |grpc| grpc.timeout_millis),
+        ),
+        (
+            "ws",
+            config
+                .ws
+                .as_ref()
+                .and_then(|ws| ws.schedule.total_deadline_millis),
+            config.ws.as_ref().map_or(0, |ws| ws.timeout_millis),
+        ),
+        (
+            "dns",
+            config
+                .dns
+                .as_ref()
+                .and_then(|dns| dns.schedule.total_deadline_millis),
+            config.dns.as_ref().map_or(0, |dns| dns.timeout_millis),
+        ),
+        (
+            "ntp",
+            config
+                .ntp
+                .as_ref()
+                .and_then(|ntp| ntp.schedule.total_deadline_millis),
+            config.ntp.as_ref().map_or(0, |ntp| ntp.timeout_millis),
+        ),
+    ];
+    for (section, deadline, timeout) in deadlines {
+        if let Some(deadline) = deadline.filter(|&deadline| deadline == 0 || deadline < timeout) {
+            problems.push(format!(
+                "{}.total_deadline_millis ({}) must be positive and at least timeout_millis ({})",
+                section, deadline, timeout
+            ));
+        }
+    }
+
+    if config.http.connect_only && config.http.reuse_connections {
+        problems.push(
+            "http.connect_only opens a fresh connection for every ping and cannot be combined \
+             with reuse_connections"
+                .to_string(),
+        );
+    }
+    if config.http.connect_only && config.http.compare_cold_warm {
+        problems.push(
+            "http.connect_only sends no requests and cannot be combined with compare_cold_warm"
+                .to_string(),
+        );
+    }
+    for name in &config.http.capture_headers {
+        if HeaderName::from_bytes(name.trim().as_bytes()).is_err() {
+            problems.push(format!(
+                "http.capture_headers: {:?} is not a valid header name",
+                name
+            ));
+        }
+    }
+    if config.http.connection_max_age_millis == Some(0) {
+        problems.push("http.connection_max_age_millis must not be 0".to_string());
+    }
+
+    for (section, connect_timeout, timeout) in connect_timeouts {
+        match connect_timeout {
+            Some(0) => problems.push(format!("{}.connect_timeout_millis must not be 0", section)),
+            Some(connect_timeout) if connect_timeout > timeout => problems.push(format!(
+                "{}.connect_timeout_millis ({}) exceeds timeout_millis ({})",
+                section, connect_timeout, timeout
+            )),
+            _ => {}
+        }
+    }
+
+    match config.tcp.dscp {
+        Some(dscp) if dscp > 63 => {
+            problems.push(format!("tcp.dscp ({}) must be between 0 and 63", dscp))
+        }
+        Some(_) if !cfg!(unix) => {
+            problems.push("tcp.dscp is not supported on this platform".to_string())
+        }
+        _ => {}
+    }
+
+    for entry in &config.tcp.entries {
+        if entry.slo_millis == Some(0) {
+            problems.push(format!("TCP {}: slo_millis must not be 0", entry.target()));
+        }
+        if let Some(srv) = &entry.srv {
+            if ServerName::try_from(srv.trim()).is_err() {
+                problems.push(format!("TCP SRV {}: invalid record name", srv));
+            }
+            if entry.ports.is_some() {
+                problems.push(format!(
+                    "TCP SRV {}: ports cannot be combined with srv",
+                    srv
+                ));
+            }
+            continue;
+        }
+        if let Err(e) = tcp_pinger::parse_host(&entry.host) {
+            problems.push(format!("TCP {}: invalid host: {}", entry.host, e));
+        }
+        let ports = entry.ports.as_deref().unwrap_or_default();
+        if entry.port == 0 && ports.is_empty() {
+            problems.push(format!("TCP {}: port is missing", entry.host));
+        } else if ports.contains(&0) {
+            problems.push(format!("TCP {}: port 0 in ports", entry.host));
+        }
+    }
+
+    for entry in config.grpc.iter().flat_map(|grpc| &grpc.entries) {
+        if let Err(e) = validate_url(&entry.url, &["http", "https"]) {
+            problems.push(format!("gRPC {}: {}", entry.url, e));
+        }
+    }
+    for entry in config.ws.iter().flat_map(|ws| &ws.entries) {
+        if let Err(e) = validate_url(&entry.url, &["ws", "wss"]) {
+            problems.push(format!("WS {}: {}", entry.url, e));
+        }
+    }
+    for entry in config.dns.iter().flat_map(|dns| &dns.entries) {
+        if let Err(e) = hickory_resolver::proto::rr::Name::from_str(entry.query_name.trim()) {
+            problems.push(format!(
+                "DNS {}: invalid query name: {}",
+                entry.query_name, e
+            ));
+        }
+        let record_type = entry.record_type.to_uppercase();
+        if let Err(e) = hickory_resolver::proto::rr::RecordType::from_str(&record_type) {
+            problems.push(format!("DNS {}: {}", entry.query_name, e));
+        }
+    }
+    for entry in config.ntp.iter().flat_map(|ntp| &ntp.entries) {
+        let server = entry.server.trim();
+        if server.parse::<IpAddr>().is_err()
+            && let Err(e) = hickory_resolver::proto::rr::Name::from_str(server)
+        {
+            problems.push(format!("NTP {}: invalid server name: {}", entry.server, e));
+        }
+        if entry.port == 0 {
+            problems.push(format!("NTP {}: port must not be 0", entry.server));
+        }
+    }
+
+    for nameserver in &config.nameservers {
+        if let Err(e) = nameserver.socket_addr() {
+            problems.push(e);
+        }
+    }
+    if !config.nameservers.is_empty() && config.nameservers.iter().all(|ns| ns.weight == 0) {
+        problems.push("At least one name server needs a non-zero weight".to_string());
+    }
+
+    let valid_name = Regex::new("^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+    for name in config.global_labels.keys() {
+        if !valid_name.is_match(name) || name.starts_with("__") {
+            problems.push(format!("Invalid global label name: {:?}", name));
+        }
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("Invalid configuration:\n  {}", problems.join("\n  "));
+    }
+    Ok(())
+}
+
+/// Check that `url` parses, has a host and uses one of `schemes`
+fn validate_url(url: &str, schemes: &[&str]) -> Result<()> {
+    let url = url
+        .trim()
+        .parse::<url::Url>()
+        .map_err(|e| anyhow::anyhow!("invalid URL: {}", e))?;
+    if url.host_str().is_none() {
+        anyhow::bail!("host is missing");
+    }
+    if !schemes.contains(&url.scheme()) {
+        anyhow::bail!("unsupported scheme {}", url.scheme());
+    }
+    Ok(())
+}
+
+/// Check that a ping interval leaves room for a full timeout.
+///
+/// `interval == timeout` is accepted; an interval only marginally above the timeout is
+/// accepted with a warning, since slow pings may then overlap with the next tick.
+fn validate_interval(kind: &str, interval: Duration, timeout: Duration) -> Result<()> {
+    if interval < timeout {
+        error!(
+            "{} interval ({}ms) is less than timeout ({}ms)",
+            kind,
+            interval.as_millis(),
+            timeout.as_millis()
+        );
+        anyhow::bail!(
+            "{} interval ({}ms) is less than timeout ({}ms), use interval >= timeout",
+            kind,
+            interval.as_millis(),
+            timeout.as_millis()
+        );
+    }
+    if interval - timeout < timeout / 10 {
+        warn!(
+            "{} interval ({}ms) is close to timeout ({}ms), slow pings may overlap the next tick",
+            kind,
+            interval.as_millis(),
+            timeout.as_millis()
+        );
+    }
+    Ok(())
+}
+
+/// Warn when retrying could make a tick run past the next one
+fn warn_retry_budget(kind: &str, retry: RetryPolicy, interval: Duration, timeout: Duration) {
+    let worst_case = retry.worst_case(timeout);
+    if worst_case > interval {
+        warn!(
+            "{} retries may take up to {}ms, longer than the {}ms interval",
+            kind,
+            worst_case.as_millis(),
+            interval.as_millis()
+        );
+    }
+}
+
+/// Random delay in `[0, jitter]`
+fn random_jitter(jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        Duration::ZERO
+    } else {
+        Duration::from_millis(rand::random_range(0..=jitter.as_millis() as u64))
+    }
+}
+
+/// Create a ping ticker whose first tick is delayed by a random offset in `[0, jitter]`
+fn jittered_interval(interval: Duration, jitter: Duration) -> tokio::time::Interval {
+    tokio::time::interval_at(
+        tokio::time::Instant::now() + random_jitter(jitter),
+        interval,
+    )
+}
+
+/// Build the HTTP pinger backend selected by the entry, falling back to the group default
+fn build_http_pinger(
+    entry: HttpPingerEntry,
+    timeout: Duration,
+    mut options: HttpClientOptions,
+    resolver: &Arc<dyn Resolve>,
+    default_pinger: HttpPinger,
+) -> Result<HttpPingerImpl> {
+    options.proxy = HttpProxy::for_entry(&entry, options.proxy)?;
+    match entry.pinger.unwrap_or(default_pinger) {
+        HttpPinger::Hyper => HyperPinger::new(entry, timeout, options, Arc::clone(resolver))
+            .map(HttpPingerImpl::Hyper),
+        HttpPinger::Reqwest => ReqwestPinger::new(entry, timeout, options, Arc::clone(resolver))
+            .map(HttpPingerImpl::Reqwest),
+        #[cfg(feature = "http3")]
+        HttpPinger::H3 => {
+            H3Pinger::new(entry, timeout, options, Arc::clone(resolver)).map(HttpPingerImpl::H3)
+        }
+        #[cfg(not(feature = "http3"))]
+        HttpPinger::H3 => {
+            anyhow::bail!("The H3 pinger requires building with the `http3` feature")
+        }
+    }
+}
+
+/// How hard-failed ping attempts within one tick are retried
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    attempts: u8,
+    /// Delay before the second attempt
+    delay: Duration,
+    /// Factor applied to the delay before each further attempt
+    multiplier: f64,
+    max_delay: Option<Duration>,
+    mode: RetryMode,
+    /// Bound on all attempts of a tick, enforced by [`ping_tick`]
+    deadline: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// Retry policy of a section; [`run_once`] and [`run_with`] both build theirs here so
+    /// that they honour the same settings
+    fn new(retries: Retries, schedule: &ScheduleConfig) -> Self {
+        let mode = schedule.retry_mode;
+        let deadline = schedule.total_deadline_millis.map(Duration::from_millis);
+        match retries {
+            Retries::Count(attempts) => Self {
+                attempts,
+                delay: Duration::from_millis(schedule.retry_delay_millis),
+                multiplier: 1.0,
+                max_delay: None,
+                mode,
+                deadline,
+            },
+            Retries::Backoff(backoff) => Self {
+                attempts: backoff.max_attempts,
+                delay: Duration::from_millis(backoff.initial_delay_millis),
+                multiplier: backoff.multiplier,
+                max_delay: backoff.max_delay_millis.map(Duration::from_millis),
+                mode,
+                deadline,
+            },
+        }
+    }
+
+    /// Delay after the failed 0-based `attempt`, saturating at `Duration::MAX`
+    fn delay_after(&self, attempt: u8) -> Duration {
+        let factor = self.multiplier.powi(i32::from(attempt));
+        let delay =
+            Duration::try_from_secs_f64(self.delay.as_secs_f64() * factor).unwrap_or(Duration::MAX);
+        match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        }
+    }
+
+    fn is_parallel(&self) -> bool {
+        self.mode == RetryMode::Parallel && self.attempts > 1
+    }
+
+    /// Worst-case time spent on one tick when every attempt times out
+    fn worst_case(&self, timeout: Duration) -> Duration {
+        let worst_case = if self.is_parallel() {
+            timeout
+        } else {
+            (0..self.attempts.saturating_sub(1))
+                .map(|attempt| self.delay_after(attempt))
+                .fold(
+                    timeout.saturating_mul(u32::from(self.attempts)),
+                    |total, delay| total.saturating_add(delay),
+                )
+        };
+        match self.deadline {
+            Some(deadline) => worst_case.min(deadline),
+            None => worst_case,
+        }
+    }
+
+    /// Run the attempts of one tick, failing with their start time once the deadline passed
+    async fn within_deadline<T>(&self, attempts: impl Future<Output = T>) -> Result<T, Instant> {
+        let begin = Instant::now();
+        match self.deadline {
+            Some(deadline) => tokio::time::timeout_at((begin + deadline).into(), attempts)
+                .await
+                .map_err(|_| begin),
+            None => Ok(attempts.await),
+        }
+    }
+
+    /// Wait before the attempt following `attempt`, returning `false` when cancelled
+    async fn wait(&self, attempt: u8, cancel: &CancellationToken) -> bool {
+        let delay = self.delay_after(attempt);
+        if attempt + 1 >= self.attempts || delay.is_zero() {
+            return true;
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => false,
+            _ = tokio::time::sleep(delay) => true,
+        }
+    }
+}
+
+/// Reason a tick sent no ping
+enum Skip {
+    /// The byte budget of the current minute is used up
+    Budget,
+    /// No ping permit became available in time
+    Limited(anyhow::Error),
+    /// No slot of the host's rate limit became free in time
+    RateLimited(anyhow::Error),
+}
+
+impl Skip {
+    /// Log and count a skipped ping
+    fn report(&self, kind: &str, metrics: &PingMetrics) {
+        match self {
+            Skip::Budget => {
+                debug!(
+                    "Skipping {} ping: byte budget used up for this minute",
+                    kind
+                );
+                return;
+            }
+            Skip::Limited(_) => metrics.ping_skipped.inc(),
+            Skip::RateLimited(_) => metrics.ping_rate_limited.inc(),
+        };
+        warn!("Skipping {} ping: {}", kind, self);
+    }
+}
+
+impl std::fmt::Display for Skip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Skip::Budget => write!(f, "byte budget used up for this minute"),
+            Skip::Limited(e) | Skip::RateLimited(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Wait until a ping may be sent: the byte budget has room, a ping permit is held and the
+/// host's rate limit has a free slot. Both waits share one `interval`, and the host slot is
+/// only taken once the permit is held, so that skipped pings don't use up the host's rate
+async fn admit<'a>(
+    metrics: &PingMetrics,
+    limiter: &'a PingLimiter,
+    rate_limit: &HostRateLimit,
+    interval: Duration,
+    cancel: &CancellationToken,
+) -> Result<Option<SemaphorePermit<'a>>, Skip> {
+    if !metrics.budget_allows() {
+        return Err(Skip::Budget);
+    }
+    let waiting_since = Instant::now();
+    let permit = limiter
+        .acquire(interval, cancel)
+        .await
+        .map_err(Skip::Limited)?;
+    let wait = interval.saturating_sub(waiting_since.elapsed());
+    rate_limit
+        .acquire(wait, cancel)
+        .await
+        .map_err(Skip::RateLimited)?;
+    Ok(permit)
+}
+
+/// Ping once without recording anything but the bytes it used, so that cold DNS caches and
+/// connection setup at startup stay out of the metrics; lookups take the untimed resolver
+/// path. `remaining` warmup pings follow this one
+async fn warmup_ping<P: Probe>(
+    pinger: &P,
+    metrics: &PingMetrics,
+    remaining: u32,
+    admitted: Result<Option<SemaphorePermit<'_>>, Skip>,
+) {
+    let target = pinger.target();
+    match admitted {
+        Ok(_permit) => match resolver::untimed(pinger.probe()).await {
+            Ok(response) => {
+                metrics.record_bytes(P::bytes(&response));
+                debug!("{} warmup ping of {}: {:?}", P::KIND, target, response);
+            }
+            Err(e) => debug!("{} warmup ping of {} failed: {}", P::KIND, target, e),
+        },
+        Err(skip) => debug!("Skipping {} warmup ping of {}: {}", P::KIND, target, skip),
+    }
+    if remaining == 0 {
+        info!("{} warmup of {} complete", P::KIND, target);
+    }
+}
+
+/// Start `attempts` pings at once and return the first successful response, or the last
+/// failed one when none succeeds; the remaining attempts are dropped
+async fn race_attempts<T, F, Fut>(
+    kind: &str,
+    attempts: u8,
+    ping: F,
+    is_success: impl Fn(&T) -> bool,
+) -> Option<(u8, T)>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let (ping, is_success, max_attempts) = (&ping, &is_success, attempts);
+    let attempts = (1..=attempts).map(|attempt| {
+        Box::pin(async move {
+            match ping().await {
+                Ok(response) if is_success(&response) => Ok((attempt, response)),
+                Ok(response) => Err(Some((max_attempts, response))),
+                Err(e) => {
+                    error!("{} Ping error: {}", kind, e);
+                    Err(None)
+                }
+            }
+        })
+    });
+    match select_ok(attempts).await {
+        Ok((response, _)) => Some(response),
+        Err(last) => last,
+    }
+}
+
+/// One kind of pinger driven by [`spawn_ping_task`] and [`ping_with_retries`]
+trait Probe: Send + Sync + 'static {
+    type Response: std::fmt::Debug + Clone + Send + Sync + 'static;
+
+    /// Kind of probe named in logs and task states
+    const KIND: &'static str;
+
+    /// Target named in logs, SLOs and task states
+    fn target(&self) -> String;
+
+    /// Host the per-host rate limit of the entry is keyed by, unlimited when `None`
+    fn host(&self) -> Option<String>;
+
+    /// Prepare for a tick, called once before its first attempt
+    fn start_tick(&self) {}
+
+    /// Send one ping
+    fn probe(&self) -> impl Future<Output = Result<Self::Response>> + Send;
+
+    fn is_success(response: &Self::Response) -> bool;
+
+    /// Bytes `response` counts against the byte budget
+    fn bytes(response: &Self::Response) -> u64;
+
+    /// Set the 1-based attempt of the tick `response` came from
+    fn set_attempt(response: &mut Self::Response, attempt: u8);
+
+    /// Log `response` and record it into the metrics
+    fn record(&self, metrics: &PingMetrics, response: &Self::Response);
+
+    /// Record how many attempts a tick took
+    fn record_attempts(&self, _metrics: &PingMetrics, _attempts: u8) {}
+
+    /// Timeout response for a tick whose attempts ran past the total deadline
+    fn deadline_exceeded(&self, begin: Instant) -> Self::Response;
+
+    /// Failure response for a ping held back by an open circuit breaker, for kinds that
+    /// support one
+    fn failure(&self, _reason: &str) -> Option<Self::Response> {
+        None
+    }
+
+    fn sample(&self, response: Option<&Self::Response>) -> Sample;
+
+    fn event(response: Self::Response) -> PingEvent;
+
+    /// Log a tick that did not end in a successful response, `attempts` being the attempts it
+    /// was allowed
+    fn cycle_failed(&self, attempts: u8, response: Option<&Self::Response>);
+
+    fn record_circuit(&self, _metrics: &PingMetrics, _open: bool) {}
+
+    fn record_backoff(&self, _metrics: &PingMetrics, _multiplier: u32) {}
+
+    /// Delay the server asked for before the next ping
+    fn retry_after(_response: &Self::Response) -> Option<Duration> {
+        None
+    }
+}
+
+impl Probe for HttpPingerImpl {
+    type Response = PingResponse;
+    const KIND: &'static str = "HTTP";
+
+    fn target(&self) -> String {
+        self.endpoint_label().target()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url().host_str().map(String::from)
+    }
+
+    fn start_tick(&self) {
+        HttpPingerImpl::start_tick(self)
+    }
+
+    fn probe(&self) -> impl Future<Output = Result<PingResponse>> + Send {
+        self.ping()
+    }
+
+    fn is_success(response: &PingResponse) -> bool {
+        response.is_success()
+    }
+
+    fn bytes(response: &PingResponse) -> u64 {
+        response.bytes
+    }
+
+    fn set_attempt(response: &mut PingResponse, attempt: u8) {
+        response.attempt = attempt;
+    }
+
+    fn record(&self, metrics: &PingMetrics, response: &PingResponse) {
+        info!(name: "httping", "Response: {:?}", response);
+        metrics.record_http_ping(response);
+    }
+
+    fn record_attempts(&self, metrics: &PingMetrics, attempts: u8) {
+        metrics.record_http_attempts(&self.endpoint_label(), attempts);
+    }
+
+    fn deadline_exceeded(&self, begin: Instant) -> PingResponse {
+        self.deadline_exceeded(begin)
+    }
+
+    fn failure(&self, reason: &str) -> Option<PingResponse> {
+        Some(self.failure(reason))
+    }
+
+    fn sample(&self, response: Option<&PingResponse>) -> Sample {
+        Sample::http(&self.endpoint_label(), response)
+    }
+
+    fn event(response: PingResponse) -> PingEvent {
+        PingEvent::Http(response)
+    }
+
+    fn cycle_failed(&self, attempts: u8, response: Option<&PingResponse>) {
+        let endpoint = self.endpoint_label();
+        match response {
+            Some(response) => warn!(
+                "HTTP ping cycle failed for {:?} on attempt {}/{}: {:?}",
+                endpoint, response.attempt, attempts, response.result
+            ),
+            None => warn!(
+                "HTTP ping cycle failed for {:?}: all {} attempts errored",
+                endpoint, attempts
+            ),
+        }
+    }
+
+    fn record_circuit(&self, metrics: &PingMetrics, open: bool) {
+        metrics.record_http_circuit(&self.endpoint_label(), open);
+    }
+
+    fn record_backoff(&self, metrics: &PingMetrics, multiplier: u32) {
+        metrics.record_http_backoff(&self.endpoint_label(), multiplier);
+    }
+
+    fn retry_after(response: &PingResponse) -> Option<Duration> {
+        response.retry_after
+    }
+}
+
+impl Probe for TcpPinger {
+    type Response = TcpPingResult;
+    const KIND: &'static str = "TCP";
+
+    fn target(&self) -> String {
+        self.endpoint_label().target()
+    }
+
+    fn host(&self) -> Option<String> {
+        Some(self.endpoint_label().host)
+    }
+
+    fn probe(&self) -> impl Future<Output = Result<TcpPingResult>> + Send {
+        self.ping()
+    }
+
+    fn is_success(response: &TcpPingResult) -> bool {
+        response.is_success()
+    }
+
+    fn bytes(response: &TcpPingResult) -> u64 {
+        response.bytes
+    }
+
+    fn set_attempt(response: &mut TcpPingResult, attempt: u8) {
+        response.attempt = attempt;
+    }
+
+    fn record(&self, metrics: &PingMetrics, response: &TcpPingResult) {
+        info!(name: "tcping", "Response: {:?}", response);
+        metrics.record_tcp_ping(response);
+    }
+
+    fn record_attempts(&self, metrics: &PingMetrics, attempts: u8) {
+        metrics.record_tcp_attempts(&self.endpoint_label(), attempts);
+    }
+
+    fn deadline_exceeded(&self, begin: Instant) -> TcpPingResult {
+        self.deadline_exceeded(begin)
+    }
+
+    fn failure(&self, reason: &str) -> Option<TcpPingResult> {
+        Some(self.failure(reason))
+    }
+
+    fn sample(&self, response: Option<&TcpPingResult>) -> Sample {
+        Sample::tcp(&self.endpoint_label(), response)
+    }
+
+    fn event(response: TcpPingResult) -> PingEvent {
+        PingEvent::Tcp(response)
+    }
+
+    fn cycle_failed(&self, attempts: u8, response: Option<&TcpPingResult>) {
+        let endpoint = self.endpoint_label();
+        match response {
+            Some(response) => warn!(
+                "TCP ping cycle failed for {:?} on attempt {}/{}: {:?}",
+                endpoint, response.attempt, attempts, response.response
+            ),
+            None => warn!(
+                "TCP ping cycle failed for {:?}: all {} attempts errored",
+                endpoint, attempts
+            ),
+        }
+    }
+
+    fn record_circuit(&self, metrics: &PingMetrics, open: bool) {
+        metrics.record_tcp_circuit(&self.endpoint_label(), open);
+    }
+
+    fn record_backoff(&self, metrics: &PingMetrics, multiplier: u32) {
+        metrics.record_tcp_backoff(&self.endpoint_label(), multiplier);
+    }
+}
+
+impl Probe for GrpcPinger {
+    type Response = GrpcPingResponse;
+    const KIND: &'static str = "gRPC";
+
+    fn target(&self) -> String {
+        grpc_target(self.url().as_str(), self.service())
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url().host_str().map(String::from)
+    }
+
+    fn probe(&self) -> impl Future<Output = Result<GrpcPingResponse>> + Send {
+        self.ping()
+    }
+
+    fn is_success(response: &GrpcPingResponse) -> bool {
+        response.is_success()
+    }
+
+    fn bytes(response: &GrpcPingResponse) -> u64 {
+        response.bytes
+    }
+
+    fn set_attempt(response: &mut GrpcPingResponse, attempt: u8) {
+        response.attempt = attempt;
+    }
+
+    fn record(&self, metrics: &PingMetrics, response: &GrpcPingResponse) {
+        info!(name: "grpcping", "Response: {:?}", response);
+        metrics.record_grpc_ping(response);
+    }
+
+    fn deadline_exceeded(&self, begin: Instant) -> GrpcPingResponse {
+        self.deadline_exceeded(begin)
+    }
+
+    fn sample(&self, response: Option<&GrpcPingResponse>) -> Sample {
+        Sample::grpc(self.url(), self.service(), response)
+    }
+
+    fn event(response: GrpcPingResponse) -> PingEvent {
+        PingEvent::Grpc(response)
+    }
+
+    fn cycle_failed(&self, _attempts: u8, response: Option<&GrpcPingResponse>) {
+        warn!(
+            "gRPC health check failed for {} {:?}: {:?}",
+            self.url(),
+            self.service(),
+            response.map(|response| &response.result)
+        );
+    }
+}
+
+impl Probe for WsPinger {
+    type Response = WsPingResponse;
+    const KIND: &'static str = "WebSocket";
+
+    fn target(&self) -> String {
+        self.url().to_string()
+    }
+
+    fn host(&self) -> Option<String> {
+        self.url().host_str().map(String::from)
+    }
+
+    fn probe(&self) -> impl Future<Output = Result<WsPingResponse>> + Send {
+        self.ping()
+    }
+
+    fn is_success(response: &WsPingResponse) -> bool {
+        response.is_success()
+    }
+
+    fn bytes(response: &WsPingResponse) -> u64 {
+        response.bytes
+    }
+
+    fn set_attempt(response: &mut WsPingResponse, attempt: u8) {
+        response.attempt = attempt;
+    }
+
+    fn record(&self, metrics: &PingMetrics, response: &WsPingResponse) {
+        info!(name: "wsping", "Response: {:?}", response);
+        metrics.record_ws_ping(response);
+    }
+
+    fn deadline_exceeded(&self, begin: Instant) -> WsPingResponse {
+        self.deadline_exceeded(begin)
+    }
+
+    fn sample(&self, response: Option<&WsPingResponse>) -> Sample {
+        Sample::ws(self.url(), response)
+    }
+
+    fn event(response: WsPingResponse) -> PingEvent {
+        PingEvent::Ws(response)
+    }
+
+    fn cycle_failed(&self, _attempts: u8, response: Option<&WsPingResponse>) {
+        warn!(
+            "WebSocket ping failed for {}: {:?}",
+            self.url(),
+            response.map(|response| &response.result)
+        );
+    }
+}
+
+impl Probe for DnsPinger {
+    type Response = DnsPingResponse;
+    const KIND: &'static str = "DNS";
+
+    fn target(&self) -> String {
+        format!("{} {}", self.query_name(), self.record_type())
+    }
+
+    /// Queries go to the system name servers, which no entry names
+    fn host(&self) -> Option<String> {
+        None
+    }
+
+    fn probe(&self) -> impl Future<Output = Result<DnsPingResponse>> + Send {
+        self.ping()
+    }
+
+    fn is_success(response: &DnsPingResponse) -> bool {
+        response.is_success()
+    }
+
+    fn bytes(response: &DnsPingResponse) -> u64 {
+        response.bytes
+    }
+
+    fn set_attempt(response: &mut DnsPingResponse, attempt: u8) {
+        response.attempt = attempt;
+    }
+
+    fn record(&self, metrics: &PingMetrics, response: &DnsPingResponse) {
+        info!(name: "dnsping", "Response: {:?}", response);
+        metrics.record_dns_query(response);
+    }
+
+    fn deadline_exceeded(&self, begin: Instant) -> DnsPingResponse {
+        self.deadline_exceeded(begin)
+    }
+
+    fn sample(&self, response: Option<&DnsPingResponse>) -> Sample {
+        Sample::dns(self.query_name(), self.record_type(), response)
+    }
+
+    fn event(response: DnsPingResponse) -> PingEvent {
+        PingEvent::Dns(response)
+    }
+
+    fn cycle_failed(&self, _attempts: u8, response: Option<&DnsPingResponse>) {
+        warn!(
+            "DNS ping failed for {} {}: {:?}",
+            self.query_name(),
+            self.record_type(),
+            response.map(|response| &response.result)
+        );
+    }
+}
+
+impl Probe for NtpPinger {
+    type Response = NtpPingResponse;
+    const KIND: &'static str = "NTP";
+
+    fn target(&self) -> String {
+        self.target()
+    }
+
+    fn host(&self) -> Option<String> {
+        Some(self.server().to_string())
+    }
+
+    fn probe(&self) -> impl Future<Output = Result<NtpPingResponse>> + Send {
+        self.ping()
+    }
+
+    fn is_success(response: &NtpPingResponse) -> bool {
+        response.is_success()
+    }
+
+    fn bytes(response: &NtpPingResponse) -> u64 {
+        response.bytes
+    }
+
+    fn set_attempt(response: &mut NtpPingResponse, attempt: u8) {
+        response.attempt = attempt;
+    }
+
+    fn record(&self, metrics: &PingMetrics, response: &NtpPingResponse) {
+        info!(name: "ntpping", "Response: {:?}", response);
+        metrics.record_ntp_ping(response);
+    }
+
+    fn deadline_exceeded(&self, begin: Instant) -> NtpPingResponse {
+        self.deadline_exceeded(begin)
+    }
+
+    fn sample(&self, response: Option<&NtpPingResponse>) -> Sample {
+        Sample::ntp(&self.target(), response)
+    }
+
+    fn event(response: NtpPingResponse) -> PingEvent {
+        PingEvent::Ntp(response)
+    }
+
+    fn cycle_failed(&self, _attempts: u8, response: Option<&NtpPingResponse>) {
+        warn!(
+            "NTP ping failed for {}: {:?}",
+            self.target(),
+            response.map(|response| &response.result)
+        );
+    }
+}
+
+/// Ping `pinger`, retrying on hard errors according to `retry`
+#[instrument(name = "probe", skip_all, fields(kind = P::KIND))]
+async fn ping_with_retries<P: Probe>(
+    pinger: &P,
+    retry: RetryPolicy,
+    metrics: &PingMetrics,
+    cancel: &CancellationToken,
+) -> Option<P::Response> {
+    pinger.start_tick();
+    if retry.is_parallel() {
+        let Some((attempt, mut response)) =
+            race_attempts(P::KIND, retry.attempts, || pinger.probe(), P::is_success).await
+        else {
+            pinger.record_attempts(metrics, retry.attempts);
+            return None;
+        };
+        P::set_attempt(&mut response, attempt);
+        pinger.record(metrics, &response);
+        pinger.record_attempts(metrics, attempt);
+        return Some(response);
+    }
+
+    for attempt in 0..retry.attempts {
+        match pinger.probe().await {
+            Ok(mut response) => {
+                P::set_attempt(&mut response, attempt + 1);
+                pinger.record(metrics, &response);
+                pinger.record_attempts(metrics, attempt + 1);
+                return Some(response);
+            }
+            Err(e) => {
+                error!("{} Ping error: {}", P::KIND, e);
+            }
+        }
+        if !retry.wait(attempt, cancel).await {
+            break;
+        }
+    }
+    pinger.record_attempts(metrics, retry.attempts);
+    None
+}
+
+/// Ping one tick with retries, recording a timeout once the policy's deadline passes
+async fn ping_tick<P: Probe>(
+    pinger: &P,
+    retry: RetryPolicy,
+    metrics: &PingMetrics,
+    cancel: &CancellationToken,
+) -> Option<P::Response> {
+    let attempts = ping_with_retries(pinger, retry, metrics, cancel);
+    match retry.within_deadline(attempts).await {
+        Ok(response) => response,
+        Err(begin) => {
+            let response = pinger.deadline_exceeded(begin);
+            pinger.record(metrics, &response);
+            Some(response)
+        }
+    }
+}
+
+/// Timing and failure handling of the ping tasks of one kind
+#[derive(Debug, Clone, Copy)]
+struct TaskSchedule {
+    interval: Duration,
+    jitter: Duration,
+    /// Delay every tick by a fresh random offset instead of shifting only the first one
+    jitter_every_tick: bool,
+    retry: RetryPolicy,
+    backoff: Option<FailureBackoff>,
+    circuit: Option<CircuitBreaker>,
+    /// Longest a Retry-After response may defer the next ping by
+    max_retry_after: Duration,
+}
+
+impl TaskSchedule {
+    fn new(interval: Duration, schedule: &ScheduleConfig, retry: RetryPolicy) -> Self {
+        Self {
+            interval,
+            jitter: Duration::from_millis(schedule.jitter_millis),
+            jitter_every_tick: schedule.jitter_every_tick,
+            retry,
+            backoff: None,
+            circuit: None,
+            max_retry_after: Duration::ZERO,
+        }
+    }
+}
+
+/// State shared by every ping task of a run
+#[derive(Clone)]
+struct TaskContext {
+    count: Option<u64>,
+    warmup: u32,
+    limiter: PingLimiter,
+    rate_limits: HostRateLimits,
+    metrics: SharedMetrics,
+    summary: Option<SummarySender>,
+    events: Option<EventSender>,
+    cancel: CancellationToken,
+}
+
+/// Spawn the ping task of `pinger`, which resolves to whether it finished a ping after
+/// cancellation
+fn spawn_ping_task<P: Probe>(
+    pinger: P,
+    schedule: TaskSchedule,
+    context: &TaskContext,
+) -> JoinHandle<bool> {
+    let TaskContext {
+        count,
+        mut warmup,
+        limiter,
+        rate_limits,
+        metrics,
+        summary,
+        events,
+        cancel,
+    } = context.clone();
+    let TaskSchedule {
+        interval,
+        jitter,
+        jitter_every_tick,
+        retry,
+        max_retry_after,
+        ..
+    } = schedule;
+    let target = pinger.target();
+    let heartbeat = metrics.register_task(P::KIND, target.clone());
+    let rate_limit = match pinger.host() {
+        Some(host) => rate_limits.for_host(&host),
+        None => HostRateLimit(None),
+    };
+    let mut backoff = schedule.backoff.map(BackoffState::new);
+    let mut circuit = schedule.circuit.map(CircuitState::new);
+    tokio::spawn(async move {
+        // Per-tick jitter keeps the grid and offsets each tick on it instead
+        let first_tick_jitter = if jitter_every_tick {
+            Duration::ZERO
+        } else {
+            jitter
+        };
+        let mut tick = jittered_interval(interval, first_tick_jitter);
+        let mut drained = false;
+        let mut ticks = 0;
+        loop {
+            if count.is_some_and(|count| ticks >= count) {
+                break;
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tick.tick() => {
+                    if jitter_every_tick {
+                        tokio::select! {
+                            _ = cancel.cancelled() => break,
+                            _ = tokio::time::sleep(random_jitter(jitter)) => {}
+                        }
+                    }
+                    let _tick = heartbeat.tick();
+                    let warming_up = warmup > 0;
+                    if warming_up {
+                        warmup -= 1;
+                    } else {
+                        ticks += 1;
+                    }
+                    if !warming_up && circuit.as_ref().is_some_and(|circuit| !circuit.allows()) {
+                        heartbeat.record(false);
+                        if let Some(response) = pinger.failure(CircuitState::OPEN_REASON) {
+                            pinger.record(&metrics, &response);
+                            if let Some(summary) = &summary {
+                                let _ = summary.send(pinger.sample(Some(&response)));
+                            }
+                            if let Some(events) = &events {
+                                event::forward(events, P::event(response));
+                            }
+                        }
+                        continue;
+                    }
+                    let admitted = admit(&metrics, &limiter, &rate_limit, interval, &cancel).await;
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+                    if warming_up {
+                        warmup_ping(&pinger, &metrics, warmup, admitted).await;
+                        continue;
+                    }
+                    let _permit = match admitted {
+                        Ok(permit) => permit,
+                        Err(skip) => {
+                            skip.report(P::KIND, &metrics);
+                            continue;
+                        }
+                    };
+                    let response = ping_tick(&pinger, retry, &metrics, &cancel).await;
+                    drained = cancel.is_cancelled();
+                    if let Some(summary) = &summary {
+                        let _ = summary.send(pinger.sample(response.as_ref()));
+                    }
+                    if let (Some(events), Some(response)) = (&events, &response) {
+                        event::forward(events, P::event(response.clone()));
+                    }
+                    let success = response.as_ref().is_some_and(P::is_success);
+                    heartbeat.record(success);
+                    // A tick cut short by shutdown did not fail, it just did not finish
+                    if !success && !drained {
+                        metrics.record_cycle_failed(P::KIND, &target);
+                        pinger.cycle_failed(retry.attempts, response.as_ref());
+                    }
+                    if let Some(circuit) = circuit.as_mut() {
+                        let open = circuit.record(success);
+                        pinger.record_circuit(&metrics, open);
+                        if open {
+                            warn!(
+                                "{} circuit open for {} after {} failures",
+                                P::KIND, target, circuit.failures
+                            );
+                        }
+                    }
+                    let mut next_tick = interval;
+                    if let Some(backoff) = backoff.as_mut() {
+                        let multiplier = backoff.record(success);
+                        pinger.record_backoff(&metrics, multiplier);
+                        heartbeat.set_backoff(multiplier);
+                        next_tick = interval * multiplier;
+                    }
+                    if let Some(retry_after) = response.as_ref().and_then(P::retry_after) {
+                        metrics.ping_retry_after.inc();
+                        let retry_after = retry_after.min(max_retry_after);
+                        info!(
+                            "Deferring next {} ping of {} by {:?} for Retry-After",
+                            P::KIND, target, retry_after
+                        );
+                        next_tick = next_tick.max(retry_after);
+                    }
+                    if next_tick > interval {
+                        tick.reset_after(next_tick);
+                    }
+                }
+            }
+        }
+        drained
+    })
+}
+
+/// Ping `pinger` for `count` ticks, `interval` apart, sending each into `summary`. The outcome
+/// is the first failed tick, or the last tick when all succeeded, described by `describe`
+async fn ping_cycles<P: Probe>(
+    pinger: &P,
+    count: u64,
+    interval: Duration,
+    retry: RetryPolicy,
+    metrics: &PingMetrics,
+    summary: &SummarySender,
+    describe: impl Fn(&P::Response) -> String,
+) -> Result<String, String> {
+    // Nothing cancels a one-shot run, the token only satisfies the retry loop
+    let cancel = CancellationToken::new();
+    let (mut first_failure, mut last) = (None, Err("no ping sent".to_string()));
+    for tick in 0..count {
+        if tick > 0 {
+            tokio::time::sleep(interval).await;
+        }
+        let response = ping_tick(pinger, retry, metrics, &cancel).await;
+        let _ = summary.send(pinger.sample(response.as_ref()));
+        last = match response {
+            Some(response) if P::is_success(&response) => Ok(describe(&response)),
+            Some(response) => Err(describe(&response)),
+            None => Err("all attempts failed".to_string()),
+        };
+        if last.is_err() && first_failure.is_none() {
+            first_failure = Some(last.clone());
+        }
+    }
+    first_failure.unwrap_or(last)
+}
+
+/// Ping every configured entry `count` times, `--once` being a single time, and print a
+/// summary.
+///
+/// Returns whether all entries were constructed and pinged successfully.
+pub async fn run_once(
+    config: PingerConfig,
+    resolver: Arc<dyn Resolve>,
+    metrics: SharedMetrics,
+    count: u64,
+) -> bool {
+    let mut probes: Vec<JoinHandle<(String, Result<String, String>)>> = Vec::new();
+    let (summary, summary_task) = Summary::collect();
+
+    let http_timeout = Duration::from_millis(config.http.timeout_millis);
+    let http_interval = Duration::from_millis(config.http.interval_millis);
+    let http_options = match HttpClientOptions::from_config(&config.http) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("FAIL  HTTP proxy  {}", e);
+            return false;
+        }
+    };
+    for entry in config
+        .http
+        .entries
+        .into_iter()
+        .flat_map(HttpPingerEntry::expand)
+    {
+        let target = format!("HTTP {} {}", entry.method, entry.url);
+        let resolver = Arc::clone(&resolver);
+        let metrics = Arc::clone(&metrics);
+        let default_pinger = config.http.pinger;
+        let retry = RetryPolicy::new(config.http.retries, &config.http.schedule);
+        let options = http_options.clone();
+        let summary = summary.clone();
+        probes.push(tokio::spawn(async move {
+            let pinger = build_http_pinger(entry, http_timeout, options, &resolver, default_pinger);
+            let outcome = match pinger {
+                Ok(pinger) => {
+                    ping_cycles(
+                        &pinger,
+                        count,
+                        http_interval,
+                        retry,
+                        &metrics,
+                        &summary,
+                        |response| format!("{:?}", response.result),
+                    )
+                    .await
+                }
+                Err(e) => Err(format!("pinger creation failed: {}", e)),
+            };
+            (target, outcome)
+        }));
+    }
+
+    let tcp_timeout = Duration::from_millis(config.tcp.timeout_millis);
+    let tcp_interval = Duration::from_millis(config.tcp.interval_millis);
+    let tcp_socks5 = Socks5Proxy::from_config(&config.tcp);
+    let tcp_socket = TcpSocketOptions::from_config(&config.tcp);
+    for entry in config
+        .tcp
+        .entries
+        .into_iter()
+        .flat_map(TcpPingerEntry::expand)
+    {
+        let target = format!("TCP {}", entry.target());
+        let resolver = Arc::clone(&resolver);
+        let metrics = Arc::clone(&metrics);
+        let measure_dns_stats = config.measure_dns_stats;
+        let retry = RetryPolicy::new(config.tcp.retries, &config.tcp.schedule);
+        let summary = summary.clone();
+        probes.push(tokio::spawn(async move {
+            let outcome = match TcpPinger::new(
+                entry,
+                tcp_timeout,
+                measure_dns_stats,
+                tcp_socks5,
+                tcp_socket,
+                resolver,
+            )
+            .await
+            {
+                Ok(pinger) => {
+                    ping_cycles(
+                        &pinger,
+                        count,
+                        tcp_interval,
+                        retry,
+                        &metrics,
+                        &summary,
+                        |response| format!("{:?}", response.response),
+                    )
+                    .await
+                }
+                Err(e) => Err(format!("pinger creation failed: {}", e)),
+            };
+            (target, outcome)
+        }));
+    }
+
+    if let Some(grpc) = config.grpc {
+        let grpc_timeout = Duration::from_millis(grpc.timeout_millis);
+        let interval = Duration::from_millis(grpc.interval_millis);
+        let retry = RetryPolicy::new(grpc.retries, &grpc.schedule);
+        for entry in grpc.entries {
+            let target = grpc_target(&entry.url, &entry.service);
+            let resolver = Arc::clone(&resolver);
+            let metrics = Arc::clone(&metrics);
+            let summary = summary.clone();
+            probes.push(tokio::spawn(async move {
+                let outcome = match GrpcPinger::new(entry, grpc_timeout, resolver) {
+                    Ok(pinger) => {
+                        ping_cycles(
+                            &pinger,
+                            count,
+                            interval,
+                            retry,
+                            &metrics,
+                            &summary,
+                            |response| format!("{:?}", response.result),
+                        )
+                        .await
+                    }
+                    Err(e) => Err(format!("pinger creation failed: {}", e)),
+                };
+                (target, outcome)
+            }));
+        }
+    }
+
+    if let Some(ws) = config.ws {
+        let ws_timeout = Duration::from_millis(ws.timeout_millis);
+        let interval = Duration::from_millis(ws.interval_millis);
+        let retry = RetryPolicy::new(ws.retries, &ws.schedule);
+        for entry in ws.entries {
+            let target = format!("WS {}", entry.url);
+            let resolver = Arc::clone(&resolver);
+            let metrics = Arc::clone(&metrics);
+            let summary = summary.clone();
+            probes.push(tokio::spawn(async move {
+                let outcome = match WsPinger::new(entry, ws_timeout, resolver) {
+                    Ok(pinger) => {
+                        ping_cycles(
+                            &pinger,
+                            count,
+                            interval,
+                            retry,
+                            &metrics,
+                            &summary,
+                            |response| format!("{:?}", response.result),
+                        )
+                        .await
+                    }
+                    Err(e) => Err(format!("pinger creation failed: {}", e)),
+                };
+                (target, outcome)
+            }));
+        }
+    }
+
+    if let Some(dns) = config.dns {
+        let dns_timeout = Duration::from_millis(dns.timeout_millis);
+        let interval = Duration::from_millis(dns.interval_millis);
+        let retry = RetryPolicy::new(dns.retries, &dns.schedule);
+        for entry in dns.entries {
+            let target = format!(
+                "DNS {} {}",
+                entry.query_name,
+                entry.record_type.to_uppercase()
+            );
+            let metrics = Arc::clone(&metrics);
+            let summary = summary.clone();
+            probes.push(tokio::spawn(async move {
+                let outcome = match DnsPinger::new(entry, dns_timeout) {
+                    Ok(pinger) => {
+                        ping_cycles(
+                            &pinger,
+                            count,
+                            interval,
+                            retry,
+                            &metrics,
+                            &summary,
+                            |response| format!("{:?}", response.result),
+                        )
+                        .await
+                    }
+                    Err(e) => Err(format!("pinger creation failed: {}", e)),
+                };
+                (target, outcome)
+            }));
+        }
+    }
+
+    if let Some(ntp) = config.ntp {
+        let ntp_timeout = Duration::from_millis(ntp.timeout_millis);
+        let interval = Duration::from_millis(ntp.interval_millis);
+        let retry = RetryPolicy::new(ntp.retries, &ntp.schedule);
+        for entry in ntp.entries {
+            let pinger = NtpPinger::new(entry, ntp_timeout, Arc::clone(&resolver));
+            let target = format!("NTP {}", pinger.target());
+            let metrics = Arc::clone(&metrics);
+            let summary = summary.clone();
+            probes.push(tokio::spawn(async move {
+                let outcome = ping_cycles(
+                    &pinger,
+                    count,
+                    interval,
+                    retry,
+                    &metrics,
+                    &summary,
+                    |response| format!("{:?}", response.result),
+                )
+                .await;
+                (target, outcome)
+            }));
+        }
+    }
+    let mut all_ok = true;
+    for probe in probes {
+        match probe.await {
+            Ok((target, Ok(detail))) => println!("OK    {}  {}", target, detail),
+            Ok((target, Err(detail))) => {
+                all_ok = false;
+                println!("FAIL  {}  {}", target, detail);
+            }
+            Err(e) => {
+                all_ok = false;
+                println!("FAIL  probe task panicked: {}", e);
+            }
+        }
+    }
+    drop(summary);
+    if let Ok(summary) = summary_task.await {
+        println!();
+        print!("{}", summary.render());
+    }
+    all_ok
+}
+
+/// Construct every configured pinger without sending any probe and print a summary.
+///
+/// Returns whether the configuration and all entries are valid.
+pub async fn check_config(config: PingerConfig, resolver: Arc<dyn Resolve>) -> bool {
+    let mut all_ok = true;
+    let mut report = |target: String, result: Result<()>| match result {
+        Ok(()) => println!("OK    {}", target),
+        Err(e) => {
+            all_ok = false;
+            println!("FAIL  {}  {}", target, e);
+        }
+    };
+
+    if !config.http.entries.is_empty() {
+        report(
+            "HTTP interval".to_string(),
+            validate_interval(
+                "HTTP",
+                Duration::from_millis(config.http.interval_millis),
+                Duration::from_millis(config.http.timeout_millis),
+            ),
+        );
+    }
+    let http_timeout = Duration::from_millis(config.http.timeout_millis);
+    // Entries can't be constructed without valid client options, report those instead
+    match HttpClientOptions::from_config(&config.http) {
+        Ok(http_options) => {
+            for entry in config
+                .http
+                .entries
+                .into_iter()
+                .flat_map(HttpPingerEntry::expand)
+            {
+                let target = format!("HTTP {} {}", entry.method, entry.url);
+                let result = build_http_pinger(
+                    entry,
+                    http_timeout,
+                    http_options.clone(),
+                    &resolver,
+                    config.http.pinger,
+                );
+                report(target, result.map(|_| ()));
+            }
+        }
+        Err(e) => report("HTTP proxy".to_string(), Err(e)),
+    }
+
+    if !config.tcp.entries.is_empty() {
+        report(
+            "TCP interval".to_string(),
+            validate_interval(
+                "TCP",
+                Duration::from_millis(config.tcp.interval_millis),
+                Duration::from_millis(config.tcp.timeout_millis),
+            ),
+        );
+    }
+    let tcp_timeout = Duration::from_millis(config.tcp.timeout_millis);
+    let tcp_socks5 = Socks5Proxy::from_config(&config.tcp);
+    let tcp_socket = TcpSocketOptions::from_config(&config.tcp);
+    for entry in config
+        .tcp
+        .entries
+        .into_iter()
+        .flat_map(TcpPingerEntry::expand)
+    {
+        let target = format!("TCP {}", entry.target());
+        // Resolve lazily so that checking does not send DNS queries
+        let result = TcpPinger::new(
+            entry,
+            tcp_timeout,
+            true,
+            tcp_socks5,
+            tcp_socket,
+            Arc::clone(&resolver),
+        )
+        .await;
+        report(target, result.map(|_| ()));
+    }
+
+    if let Some(grpc) = config.grpc {
+        let grpc_timeout = Duration::from_millis(grpc.timeout_millis);
+        report(
+            "gRPC interval".to_string(),
+            validate_interval(
+                "gRPC",
+                Duration::from_millis(grpc.interval_millis),
+                grpc_timeout,
+            ),
+        );
+        for entry in grpc.entries {
+            let target = grpc_target(&entry.url, &entry.service);
+            let result = GrpcPinger::new(entry, grpc_timeout, Arc::clone(&resolver));
+            report(target, result.map(|_| ()));
+        }
+    }
+
+    if let Some(ws) = config.ws {
+        let ws_timeout = Duration::from_millis(ws.timeout_millis);
+        report(
+            "WebSocket interval".to_string(),
+            validate_interval(
+                "WebSocket",
+                Duration::from_millis(ws.interval_millis),
+                ws_timeout,
+            ),
+        );
+        for entry in ws.entries {
+            let target = format!("WS {}", entry.url);
+            let result = WsPinger::new(entry, ws_timeout, Arc::clone(&resolver));
+            report(target, result.map(|_| ()));
+        }
+    }
+
+    if let Some(dns) = config.dns {
+        let dns_timeout = Duration::from_millis(dns.timeout_millis);
+        report(
+            "DNS interval".to_string(),
+            validate_interval(
+                "DNS",
+                Duration::from_millis(dns.interval_millis),
+                dns_timeout,
+            ),
+        );
+        for entry in dns.entries {
+            let target = format!(
+                "DNS {} {}",
+                entry.query_name,
+                entry.record_type.to_uppercase()
+            );
+            let result = DnsPinger::new(entry, dns_timeout);
+            report(target, result.map(|_| ()));
+        }
+    }
+
+    if let Some(ntp) = config.ntp {
+        report(
+            "NTP interval".to_string(),
+            validate_interval(
+                "NTP",
+                Duration::from_millis(ntp.interval_millis),
+                Duration::from_millis(ntp.timeout_millis),
+            ),
+        );
+    }
+
+    all_ok
+}
+
+/// Ping task started for a config entry, see [`start_entries`]
+struct RunningTask {
+    /// `<kind> <target>`, as reported by reloads
+    name: String,
+    /// Child of the run's token, stopping only this task
+    cancel: CancellationToken,
+}
+
+/// Ping tasks of a run, keyed by the entry they were started for
+#[derive(Default)]
+struct PingTasks {
+    // Finished tasks leave the set, so it can be polled again once shutdown interrupts the wait
+    handles: FuturesUnordered<JoinHandle<bool>>,
+    running: HashMap<String, RunningTask>,
+}
+
+impl PingTasks {
+    /// Spawn the ping task of `pinger` under `key`, returning its name
+    fn spawn<P: Probe>(
+        &mut self,
+        key: String,
+        pinger: P,
+        schedule: TaskSchedule,
+        context: &TaskContext,
+    ) -> String {
+        let name = format!("{} {}", P::KIND, pinger.target());
+        let cancel = context.cancel.child_token();
+        let context = TaskContext {
+            cancel: cancel.clone(),
+            ..context.clone()
+        };
+        self.handles
+            .push(spawn_ping_task(pinger, schedule, &context));
+        self.running.insert(
+            key,
+            RunningTask {
+                name: name.clone(),
+                cancel,
+            },
+        );
+        name
+    }
+
+    /// Stop every task whose key is not in `keys`, returning their names
+    fn retain(&mut self, keys: &HashSet<String>) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.running.retain(|key, task| {
+            let keep = keys.contains(key);
+            if !keep {
+                task.cancel.cancel();
+                removed.push(task.name.clone());
+            }
+            keep
+        });
+        removed.sort();
+        removed
+    }
+}
+
+/// Entries of a config passed to [`start_entries`]
+#[derive(Default)]
+struct StartedEntries {
+    /// Key of every entry, whether started now or already running
+    keys: HashSet<String>,
+    /// Names of the tasks started
+    added: Vec<String>,
+    /// Entries whose pinger could not be created
+    failed: usize,
+}
+
+impl StartedEntries {
+    /// Claim the key of `entry` in `section`, `None` when its task is already running;
+    /// repeats of an entry are numbered so that each keeps its own task
+    fn claim(
+        &mut self,
+        section: &str,
+        entry: &impl Serialize,
+        tasks: &PingTasks,
+    ) -> Option<String> {
+        let base = format!(
+            "{} {}",
+            section,
+            serde_json::to_value(entry).unwrap_or_default()
+        );
+        let mut key = base.clone();
+        let mut repeat = 1;
+        while !self.keys.insert(key.clone()) {
+            repeat += 1;
+            key = format!("{} #{}", base, repeat);
+        }
+        (!tasks.running.contains_key(&key)).then_some(key)
+    }
+}
+
+/// Settings of `section` its entries are keyed by, everything but the entries themselves
+fn section_key(kind: &str, section: &impl Serialize) -> String {
+    let mut settings = serde_json::to_value(section).unwrap_or_default();
+    if let Some(settings) = settings.as_object_mut() {
+        settings.remove("entries");
+    }
+    format!("{} {}", kind, settings)
+}
+
+/// Spawn a ping task for every entry of `config` that has none in `tasks` yet.
+///
+/// Entries are keyed by their kind, their own settings and those of their section, so
+/// changing either starts a new task. Every section is checked before the first task is
+/// spawned, so a rejected config starts nothing.
+async fn start_entries(
+    config: &PingerConfig,
+    resolver: &Arc<dyn Resolve>,
+    context: &TaskContext,
+    tasks: &mut PingTasks,
+) -> Result<StartedEntries> {
+    let metrics = &context.metrics;
+    let timings = [
+        (
+            "HTTP",
+            (!config.http.entries.is_empty())
+                .then_some((config.http.interval_millis, config.http.timeout_millis)),
+        ),
+        (
+            "TCP",
+            (!config.tcp.entries.is_empty())
+                .then_some((config.tcp.interval_millis, config.tcp.timeout_millis)),
+        ),
+        (
+            "gRPC",
+            config
+                .grpc
+                .as_ref()
+                .map(|grpc| (grpc.interval_millis, grpc.timeout_millis)),
+        ),
+        (
+            "WebSocket",
+            config
+                .ws
+                .as_ref()
+                .map(|ws| (ws.interval_millis, ws.timeout_millis)),
+        ),
+        (
+            "DNS",
+            config
+                .dns
+                .as_ref()
+                .map(|dns| (dns.interval_millis, dns.timeout_millis)),
+        ),
+        (
+            "NTP",
+            config
+                .ntp
+                .as_ref()
+                .map(|ntp| (ntp.interval_millis, ntp.timeout_millis)),
+        ),
+    ];
+    for (kind, timing) in timings {
+        if let Some((interval, timeout)) = timing {
+            validate_interval(
+                kind,
+                Duration::from_millis(interval),
+                Duration::from_millis(timeout),
+            )?;
+        }
+    }
+    let http_options = match config.http.entries.is_empty() {
+        true => None,
+        false => Some(HttpClientOptions::from_config(&config.http)?),
+    };
+    let mut started = StartedEntries::default();
+
+    // Create HTTP ping tasks
+    if let Some(http_options) = http_options {
+        let http_timeout = Duration::from_millis(config.http.timeout_millis);
+        let http_interval = Duration::from_millis(config.http.interval_millis);
+
+        let http_retry = RetryPolicy::new(config.http.retries, &config.http.schedule);
+        warn_retry_budget("HTTP", http_retry, http_interval, http_timeout);
+        let http_schedule = TaskSchedule {
+            backoff: config.http.failure_backoff,
+            circuit: config.http.circuit_breaker,
+            max_retry_after: Duration::from_millis(config.http.max_retry_after_millis),
+            ..TaskSchedule::new(http_interval, &config.http.schedule, http_retry)
+        };
+        let http_section = section_key("HTTP", &config.http);
+
+        for entry in config
+            .http
+            .entries
+            .iter()
+            .cloned()
+            .flat_map(HttpPingerEntry::expand)
+        {
+            let Some(key) = started.claim(&http_section, &entry, tasks) else {
+                continue;
+            };
+            let slo = entry.slo_millis;
+            let pinger = build_http_pinger(
+                entry,
+                http_timeout,
+                http_options.clone(),
+                resolver,
+                config.http.pinger,
+            );
+            match pinger {
+                Ok(pinger) => {
+                    if let Some(slo) = slo {
+                        metrics.set_slo("HTTP", pinger.target(), Duration::from_millis(slo));
+                    }
+                    let name = tasks.spawn(key, pinger, http_schedule, context);
+                    started.added.push(name);
+                }
+                Err(e) => {
+                    error!("Failed to create HTTP pinger: {}", e);
+                    started.failed += 1;
+                }
+            }
+        }
+    }
+
+    // Create TCP ping tasks
+    if !config.tcp.entries.is_empty() {
+        let tcp_timeout = Duration::from_millis(config.tcp.timeout_millis);
+        let tcp_interval = Duration::from_millis(config.tcp.interval_millis);
+
+        let tcp_retry = RetryPolicy::new(config.tcp.retries, &config.tcp.schedule);
+        warn_retry_budget("TCP", tcp_retry, tcp_interval, tcp_timeout);
+        let tcp_socks5 = Socks5Proxy::from_config(&config.tcp);
+        let tcp_socket = TcpSocketOptions::from_config(&config.tcp);
+        let tcp_schedule = TaskSchedule {
+            backoff: config.tcp.failure_backoff,
+            circuit: config.tcp.circuit_breaker,
+            ..TaskSchedule::new(tcp_interval, &config.tcp.schedule, tcp_retry)
+        };
+        let tcp_section = section_key("TCP", &config.tcp);
+
+        for entry in config
+            .tcp
+            .entries
+            .iter()
+            .cloned()
+            .flat_map(TcpPingerEntry::expand)
+        {
+            let Some(key) = started.claim(&tcp_section, &entry, tasks) else {
+                continue;
+            };
+            let slo = entry.slo_millis;
+            let pinger = TcpPinger::new(
+                entry,
+                tcp_timeout,
+                config.measure_dns_stats,
+                tcp_socks5,
+                tcp_socket,
+                Arc::clone(resolver),
+            )
+            .await;
+            match pinger {
+                Ok(pinger) => {
+                    if let Some(slo) = slo {
+                        metrics.set_slo("TCP", pinger.target(), Duration::from_millis(slo));
+                    }
+                    let name = tasks.spawn(key, pinger, tcp_schedule, context);
+                    started.added.push(name);
+                }
+                Err(e) => {
+                    error!("Failed to create TCP pinger: {}", e);
+                    started.failed += 1;
+                }
+            }
+        }
+    }
+
+    // Create gRPC health check tasks
+    if let Some(grpc) = &config.grpc {
+        let grpc_timeout = Duration::from_millis(grpc.timeout_millis);
+        let grpc_interval = Duration::from_millis(grpc.interval_millis);
+
+        let grpc_retry = RetryPolicy::new(grpc.retries, &grpc.schedule);
+        warn_retry_budget("gRPC", grpc_retry, grpc_interval, grpc_timeout);
+        let grpc_schedule = TaskSchedule::new(grpc_interval, &grpc.schedule, grpc_retry);
+        let grpc_section = section_key("gRPC", grpc);
+
+        for entry in &grpc.entries {
+            let Some(key) = started.claim(&grpc_section, entry, tasks) else {
+                continue;
+            };
+            match GrpcPinger::new(entry.clone(), grpc_timeout, Arc::clone(resolver)) {
+                Ok(pinger) => started
+                    .added
+                    .push(tasks.spawn(key, pinger, grpc_schedule, context)),
+                Err(e) => {
+                    error!("Failed to create gRPC pinger: {}", e);
+                    started.failed += 1;
+                }
+            }
+        }
+    }
+
+    // Create WebSocket ping tasks
+    if let Some(ws) = &config.ws {
+        let ws_timeout = Duration::from_millis(ws.timeout_millis);
+        let ws_interval = Duration::from_millis(ws.interval_millis);
+
+        let ws_retry = RetryPolicy::new(ws.retries, &ws.schedule);
+        warn_retry_budget("WebSocket", ws_retry, ws_interval, ws_timeout);
+        let ws_schedule = TaskSchedule::new(ws_interval, &ws.schedule, ws_retry);
+        let ws_section = section_key("WebSocket", ws);
+
+        for entry in &ws.entries {
+            let Some(key) = started.claim(&ws_section, entry, tasks) else {
+                continue;
+            };
+            match WsPinger::new(entry.clone(), ws_timeout, Arc::clone(resolver)) {
+                Ok(pinger) => started
+                    .added
+                    .push(tasks.spawn(key, pinger, ws_schedule, context)),
+                Err(e) => {
+                    error!("Failed to create WebSocket pinger: {}", e);
+                    started.failed += 1;
+                }
+            }
+        }
+    }
+
+    // Create DNS query probe tasks
+    if let Some(dns) = &config.dns {
+        let dns_timeout = Duration::from_millis(dns.timeout_millis);
+        let dns_interval = Duration::from_millis(dns.interval_millis);
+
+        let dns_retry = RetryPolicy::new(dns.retries, &dns.schedule);
+        warn_retry_budget("DNS", dns_retry, dns_interval, dns_timeout);
+        let dns_schedule = TaskSchedule::new(dns_interval, &dns.schedule, dns_retry);
+        let dns_section = section_key("DNS", dns);
+
+        for entry in &dns.entries {
+            let Some(key) = started.claim(&dns_section, entry, tasks) else {
+                continue;
+            };
+            match DnsPinger::new(entry.clone(), dns_timeout) {
+                Ok(pinger) => started
+                    .added
+                    .push(tasks.spawn(key, pinger, dns_schedule, context)),
+                Err(e) => {
+                    error!("Failed to create DNS pinger: {}", e);
+                    started.failed += 1;
+                }
+            }
+        }
+    }
+
+    // Create NTP ping tasks
+    if let Some(ntp) = &config.ntp {
+        let ntp_timeout = Duration::from_millis(ntp.timeout_millis);
+        let ntp_interval = Duration::from_millis(ntp.interval_millis);
+
+        let ntp_retry = RetryPolicy::new(ntp.retries, &ntp.schedule);
+        warn_retry_budget("NTP", ntp_retry, ntp_interval, ntp_timeout);
+        let ntp_schedule = TaskSchedule::new(ntp_interval, &ntp.schedule, ntp_retry);
+        let ntp_section = section_key("NTP", ntp);
+
+        for entry in &ntp.entries {
+            let Some(key) = started.claim(&ntp_section, entry, tasks) else {
+                continue;
+            };
+            let pinger = NtpPinger::new(entry.clone(), ntp_timeout, Arc::clone(resolver));
+            started
+                .added
+                .push(tasks.spawn(key, pinger, ntp_schedule, context));
+        }
+    }
+
+    Ok(started)
+}
+
+/// Bring the running ping tasks in line with a reloaded `config`, starting those of new or
+/// changed entries and stopping those of entries it no longer has
+async fn reload_entries(
+    config: PingerConfig,
+    resolver: &Arc<dyn Resolve>,
+    context: &TaskContext,
+    tasks: &mut PingTasks,
+) -> Result<ReloadSummary> {
+    let started = start_entries(&config, resolver, context, tasks).await?;
+    let removed = tasks.retain(&started.keys);
+    info!(
+        "Reloaded config: {} entries added, {} removed, {} failed",
+        started.added.len(),
+        removed.len(),
+        started.failed
+    );
+    Ok(ReloadSummary {
+        added: started.added,
+        removed,
+        failed: started.failed,
+    })
+}
+
+/// Options of [`run_with`], beyond the events mostly taken from the command line by the binary
+#[derive(Debug)]
+pub struct RunOptions {
+    /// Stop each entry after this many ping cycles and print a per-entry summary table; run
+    /// until cancelled when unset
+    pub count: Option<u64>,
+    /// Time in-flight pings may take to finish once cancelled before they are abandoned
+    pub shutdown_grace: Duration,
+    /// Channel every ping cycle's result is forwarded to, in addition to the metrics
+    pub events: Option<EventSender>,
+    /// Reloaded configs whose entries are applied to the running tasks, see
+    /// [`reload::Reloader`]
+    pub reload: Option<ReloadReceiver>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            count: None,
+            shutdown_grace: Duration::from_secs(5),
+            events: None,
+            reload: None,
+        }
+    }
+}
+
+/// Ping every configured entry into `metrics` until `cancel` is cancelled; serving the
+/// metrics is left to the caller, e.g. through [`metrics_server::start_metrics_server`]
+pub async fn run(
+    config: PingerConfig,
+    metrics: SharedMetrics,
+    cancel: CancellationToken,
+) -> Result<()> {
+    run_with(config, metrics, cancel, RunOptions::default()).await
+}
+
+/// [`run`] with explicit [`RunOptions`]; with a `count`, `cancel` is cancelled once every
+/// entry has finished its cycles
+pub async fn run_with(
+    mut config: PingerConfig,
+    metrics: SharedMetrics,
+    cancel: CancellationToken,
+    options: RunOptions,
+) -> Result<()> {
+    validate_config(&mut config)?;
+    let resolver = resolver::build_resolver(&config, Arc::clone(&metrics))?;
+
+    // Push the same metrics to a remote-write receiver when configured
+    #[cfg(feature = "remote-write")]
+    let remote_write_handle = config
+        .remote_write
+        .clone()
+        .map(|remote_write| remote_write::spawn(remote_write, Arc::clone(&metrics), cancel.clone()))
+        .transpose()?;
+    #[cfg(not(feature = "remote-write"))]
+    if config.remote_write.is_some() {
+        return Err(anyhow::anyhow!(
+            "remote_write requires building with the `remote-write` feature"
+        ));
+    }
+
+    // Bounded runs print per-entry statistics on exit
+    let (summary, summary_task) = match options.count {
+        Some(_) => {
+            let (summary, task) = Summary::collect();
+            (Some(summary), Some(task))
+        }
+        None => (None, None),
+    };
+    let context = TaskContext {
+        count: options.count,
+        warmup: config.warmup_cycles,
+        limiter: PingLimiter::new(config.max_concurrent_pings),
+        rate_limits: HostRateLimits::new(config.host_rate_limit),
+        metrics: Arc::clone(&metrics),
+        summary: summary.clone(),
+        events: options.events.clone(),
+        cancel: cancel.clone(),
+    };
+
+    let mut tasks = PingTasks::default();
+    let started = start_entries(&config, &resolver, &context, &mut tasks).await?;
+    if config.strict_startup && started.failed > 0 {
+        cancel.cancel();
+        return Err(anyhow::anyhow!(
+            "{} ping task(s) failed to start with strict_startup set",
+            started.failed
+        ));
+    }
+
+    // Runs until cancelled or, with --count, until every task has finished its ticks
+    let mut reloads = options.reload;
+    let finished = loop {
+        if options.count.is_some() && tasks.handles.is_empty() {
+            break true;
+        }
+        select! {
+            _ = cancel.cancelled() => break false,
+            Some(_) = tasks.handles.next(), if !tasks.handles.is_empty() => {}
+            request = async { reloads.as_mut()?.recv().await }, if reloads.is_some() => {
+                match request {
+                    Some(request) => {
+                        let result = reload_entries(request.config, &resolver, &context, &mut tasks)
+                            .await
+                            .map_err(|e| format!("{:#}", e));
+                        let _ = request.reply.send(result);
+                    }
+                    // Nothing can ask for a reload anymore
+                    None => reloads = None,
+                }
+            }
+        }
+    };
+    drop(context);
+    let mut ping_tasks = tasks.handles;
+    if finished {
+        // Stops whatever else shares the token, e.g. the metrics server and the signal handler
+        cancel.cancel();
+    } else {
+        // Lets in-flight pings finish within the grace period
+        let deadline = Instant::now() + options.shutdown_grace;
+        let mut drained = 0;
+        let drain = async {
+            while let Some(result) = ping_tasks.next().await {
+                if matches!(result, Ok(true)) {
+                    drained += 1;
+                }
+            }
+        };
+        let _ = tokio::time::timeout_at(deadline.into(), drain).await;
+        let abandoned = ping_tasks.len();
+        for task in ping_tasks.iter() {
+            task.abort();
+        }
+        info!(
+            "Shutdown: {} in-flight pings drained, {} abandoned",
+            drained, abandoned
+        );
+    }
+
+    drop(summary);
+    if let Some(Ok(summary)) = OptionFuture::from(summary_task).await {
+        print!("{}", summary.render());
+    }
+
+    #[cfg(feature = "remote-write")]
+    if let Some(handle) = remote_write_handle {
+        let _ = handle.await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_equal_to_timeout_is_accepted() {
+        let timeout = Duration::from_secs(5);
+        assert!(validate_interval("HTTP", timeout, timeout).is_ok());
+    }
+
+    #[test]
+    fn interval_slightly_above_timeout_is_accepted() {
+        let interval = Duration::from_millis(5001);
+        assert!(validate_interval("HTTP", interval, Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn interval_below_timeout_is_rejected() {
+        let interval = Duration::from_millis(4999);
+        let error = validate_interval("HTTP", interval, Duration::from_secs(5)).unwrap_err();
+        assert!(error.to_string().contains("use interval >= timeout"));
+    }
+
+    fn config(json: &str) -> PingerConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn zero_max_concurrent_pings_is_rejected() {
+        let mut config = config(r#"{"max_concurrent_pings": 0}"#);
+        let error = validate_config(&mut config).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("max_concurrent_pings must not be 0")
+        );
+    }
+
+    #[test]
+    fn extension_methods_are_accepted() {
+        let mut valid = config(
+            r#"{"http": {"entries": [
+                {"url": "http://example.com/", "methods": ["propfind", "PURGE"]}
+            ]}}"#,
+        );
+        validate_config(&mut valid).unwrap();
+        let methods = valid.http.entries[0].methods.as_ref().unwrap();
+        assert_eq!(methods, &["PROPFIND", "PURGE"]);
+
+        let mut invalid =
+            config(r#"{"http": {"entries": [{"url": "http://x/", "method": "G T"}]}}"#);
+        let error = validate_config(&mut invalid).unwrap_err();
+        assert!(error.to_string().contains("invalid method"));
+    }
+
+    #[test]
+    fn per_tick_jitter_must_stay_below_the_interval() {
+        let mut valid = config(
+            r#"{"tcp": {"interval_millis": 1000, "jitter_millis": 999, "jitter_every_tick": true}}"#,
+        );
+        validate_config(&mut valid).unwrap();
+        assert_eq!(valid.tcp.schedule.jitter_millis, 999);
+        assert!(valid.tcp.schedule.jitter_every_tick);
+
+        let mut invalid = config(
+            r#"{"tcp": {"interval_millis": 1000, "jitter_millis": 1000, "jitter_every_tick": true}}"#,
+        );
+        let error = validate_config(&mut invalid).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("tcp.jitter_millis (1000) must be below interval_millis (1000)")
+        );
+    }
+
+    #[test]
+    fn zero_remote_write_interval_is_rejected() {
+        let mut config = config(
+            r#"{"remote_write": {"url": "http://localhost:9090/api/v1/write", "interval_millis": 0}}"#,
+        );
+        let error = validate_config(&mut config).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("remote_write.interval_millis must not be 0")
+        );
+    }
+
+    #[test]
+    fn retry_delays_saturate_instead_of_overflowing() {
+        let retries = Retries::Backoff(crate::config::RetryBackoff {
+            max_attempts: u8::MAX,
+            initial_delay_millis: u64::MAX,
+            multiplier: 10.0,
+            max_delay_millis: None,
+        });
+        let retry = RetryPolicy::new(retries, &ScheduleConfig::default());
+        assert_eq!(retry.delay_after(200), Duration::MAX);
+        assert_eq!(retry.worst_case(Duration::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn initial_retry_delay_beyond_the_interval_is_rejected() {
+        let mut config = config(
+            r#"{"dns": {"timeout_millis": 500, "interval_millis": 1000, "entries": [],
+                "retries": {"max_attempts": 3, "initial_delay_millis": 1001}}}"#,
+        );
+        let error = validate_config(&mut config).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("dns.retries.initial_delay_millis must not exceed interval_millis")
+        );
+    }
+
+    #[tokio::test]
+    async fn ping_permit_wait_ends_on_cancel() {
+        let limiter = PingLimiter::new(Some(1));
+        let cancel = CancellationToken::new();
+        let _held = limiter.acquire(Duration::ZERO, &cancel).await.unwrap();
+        let waiting = limiter.acquire(Duration::from_secs(60), &cancel);
+        cancel.cancel();
+        let result = tokio::time::timeout(Duration::from_secs(1), waiting).await;
+        assert!(result.expect("acquire ignored the cancellation").is_err());
+    }
+
+    #[tokio::test]
+    async fn host_slot_wait_ends_on_cancel() {
+        let rate_limit = HostRateLimits::new(Some(0.01)).for_host("example.com");
+        let cancel = CancellationToken::new();
+        rate_limit.acquire(Duration::ZERO, &cancel).await.unwrap();
+        assert!(rate_limit.acquire(Duration::ZERO, &cancel).await.is_err());
+        let waiting = rate_limit.acquire(Duration::from_secs(600), &cancel);
+        cancel.cancel();
+        let result = tokio::time::timeout(Duration::from_secs(1), waiting).await;
+        assert!(result.expect("acquire ignored the cancellation").is_err());
+    }
+
+    #[tokio::test]
+    async fn tiny_host_rates_are_rejected_and_never_overflow() {
+        let mut config = config(r#"{"host_rate_limit": 1e-20}"#);
+        let error = validate_config(&mut config).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("host_rate_limit must be at least one ping per day")
+        );
+
+        let rate_limit = HostRateLimits::new(Some(1e-20)).for_host("example.com");
+        let limiter = rate_limit.0.as_ref().unwrap();
+        assert_eq!(limiter.period, MAX_HOST_RATE_PERIOD);
+        let cancel = CancellationToken::new();
+        rate_limit.acquire(Duration::ZERO, &cancel).await.unwrap();
+        assert!(rate_limit.acquire(Duration::ZERO, &cancel).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reloads_start_added_and_stop_removed_entries() {
+        let tcp = |ports: &[u16], interval: u64| {
+            let entries: Vec<String> = ports
+                .iter()
+                .map(|port| format!(r#"{{"host": "127.0.0.1", "port": {}}}"#, port))
+                .collect();
+            config(&format!(
+                r#"{{"tcp": {{"interval_millis": {}, "entries": [{}]}}}}"#,
+                interval,
+                entries.join(", ")
+            ))
+        };
+        let initial = tcp(&[1, 2, 2], 5000);
+        let metrics: SharedMetrics = Arc::new(PingMetrics::new(&initial));
+        let resolver = resolver::build_resolver(&initial, Arc::clone(&metrics)).unwrap();
+        let cancel = CancellationToken::new();
+        let context = TaskContext {
+            count: None,
+            warmup: 0,
+            limiter: PingLimiter::new(None),
+            rate_limits: HostRateLimits::new(None),
+            metrics,
+            summary: None,
+            events: None,
+            cancel: cancel.clone(),
+        };
+        let mut tasks = PingTasks::default();
+
+        // Repeated entries keep a task each
+        let started = start_entries(&initial, &resolver, &context, &mut tasks)
+            .await
+            .unwrap();
+        assert_eq!(started.added.len(), 3);
+        assert_eq!(tasks.running.len(), 3);
+
+        let summary = reload_entries(tcp(&[2, 3], 5000), &resolver, &context, &mut tasks)
+            .await
+            .unwrap();
+        assert_eq!(summary.added, ["TCP 127.0.0.1:3"]);
+        assert_eq!(summary.removed, ["TCP 127.0.0.1:1", "TCP 127.0.0.1:2"]);
+        assert_eq!(tasks.running.len(), 2);
+
+        // Section settings are part of every entry
+        let summary = reload_entries(tcp(&[2, 3], 6000), &resolver, &context, &mut tasks)
+            .await
+            .unwrap();
+        assert_eq!(summary.added.len(), 2);
+        assert_eq!(summary.removed.len(), 2);
+
+        // A rejected section starts nothing and keeps the running tasks
+        let mut rejected = tcp(&[4], 6000);
+        rejected.tcp.timeout_millis = 10_000;
+        assert!(
+            reload_entries(rejected, &resolver, &context, &mut tasks)
+                .await
+                .is_err()
+        );
+        assert_eq!(tasks.running.len(), 2);
+
+        // Stopped tasks end without the run being cancelled
+        let stopped = tokio::time::timeout(Duration::from_secs(5), async {
+            while tasks.handles.len() > 2 {
+                tasks.handles.next().await;
+            }
+        });
+        stopped.await.expect("removed tasks kept running");
+        assert!(!cancel.is_cancelled());
+        cancel.cancel();
+    }
+}
@@ -1,169 +1,15 @@
-use crate::config::{Args, HttpPinger, PingerConfig};
-use crate::http_pinger::AsyncHttpPinger;
-use crate::http_pinger::hyper_pinger::HyperPinger;
-use crate::http_pinger::reqwest_pinger::ReqwestPinger;
-use crate::metric::{PingMetrics, SharedMetrics};
-use crate::metrics_server::start_metrics_server;
-use crate::tcp_pinger::TcpPinger;
-use anyhow::Result;
 use clap::Parser;
-use resolver::Resolve;
+use pinger::config::{Args, PingerConfig};
+use pinger::metrics_server::{MetricsBind, start_metrics_server};
+use pinger::reload::Reloader;
+use pinger::{PingMetrics, RunOptions, SharedMetrics, telemetry};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::signal::unix::SignalKind;
 use tokio::task::JoinHandle;
 use tokio::{select, signal};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
-
-mod config;
-mod http_pinger;
-mod metric;
-mod metrics_server;
-mod resolver;
-mod tcp_pinger;
-
-/// Enum to hold different HTTP pinger types
-enum HttpPingerImpl {
-    Hyper(HyperPinger),
-    Reqwest(ReqwestPinger),
-}
-
-impl HttpPingerImpl {
-    #[inline]
-    async fn ping(&self) -> Result<crate::http_pinger::PingResponse> {
-        match self {
-            HttpPingerImpl::Hyper(pinger) => pinger.ping().await,
-            HttpPingerImpl::Reqwest(pinger) => pinger.ping().await,
-        }
-    }
-}
-
-/// Load configuration from file
-async fn load_config(config_path: &str) -> Result<PingerConfig> {
-    let path = std::path::Path::new(config_path);
-
-    let config_content = tokio::fs::read_to_string(path)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
-    let ext = path
-        .file_name()
-        .ok_or(anyhow::anyhow!("Failed to get file name"))?
-        .to_str()
-        .ok_or(anyhow::anyhow!("Failed to decode file name"))?
-        .split(".")
-        .last()
-        .ok_or(anyhow::anyhow!("Failed to get file extension"))?;
-    match ext {
-        "json" => serde_json::from_str(&config_content)
-            .map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e)),
-        "yaml" => serde_yaml::from_str(&config_content)
-            .map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e)),
-        "toml" => toml::from_str(&config_content)
-            .map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e)),
-        _ => anyhow::bail!("Unsupported file extension: {}", ext),
-    }
-}
-
-/// Create HTTP ping task
-#[allow(clippy::too_many_arguments)]
-fn create_http_ping_task(
-    entry: crate::config::HttpPingerEntry,
-    timeout: Duration,
-    interval: Duration,
-    retries: u8,
-    resolver: Arc<dyn Resolve>,
-    metrics: SharedMetrics,
-    pinger_type: HttpPinger,
-    cancel: CancellationToken,
-) -> Result<JoinHandle<()>> {
-    let pinger_result = match pinger_type {
-        HttpPinger::Hyper => {
-            HyperPinger::new(entry, timeout, Arc::clone(&resolver) as _).map(HttpPingerImpl::Hyper)
-        }
-        HttpPinger::Reqwest => ReqwestPinger::new(entry, timeout, Arc::clone(&resolver) as _)
-            .map(HttpPingerImpl::Reqwest),
-    };
-
-    match pinger_result {
-        Ok(pinger) => {
-            let task = tokio::spawn(async move {
-                let mut tick = tokio::time::interval(interval);
-                loop {
-                    tokio::select! {
-                        _ = cancel.cancelled() => {
-                            break;
-                        }
-                        _ = tick.tick() => {
-                            for _ in 0..retries {
-                                match pinger.ping().await {
-                                    Ok(response) => {
-                                        info!(name: "httping", "Response: {:?}", response);
-                                        metrics.record_http_ping(&response);
-                                        break;
-                                    }
-                                    Err(e) => {
-                                        error!("HTTP Ping error: {}", e);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            });
-            Ok(task)
-        }
-        Err(e) => {
-            error!("Failed to create HTTP pinger: {}", e);
-            Err(anyhow::anyhow!("HTTP pinger creation failed: {}", e))
-        }
-    }
-}
-
-/// Create TCP ping task
-#[allow(clippy::too_many_arguments)]
-async fn create_tcp_ping_task(
-    entry: crate::config::TcpPingerEntry,
-    timeout: Duration,
-    interval: Duration,
-    measure_dns_stats: bool,
-    retries: u8,
-    resolver: Arc<dyn Resolve>,
-    metrics: SharedMetrics,
-    cancel: CancellationToken,
-) -> Result<JoinHandle<()>> {
-    match TcpPinger::new(entry, timeout, measure_dns_stats, resolver).await {
-        Ok(pinger) => {
-            let mut tick = tokio::time::interval(interval);
-            let task = tokio::spawn(async move {
-                loop {
-                    tokio::select! {
-                        _ = cancel.cancelled() => { break; }
-                        _ = tick.tick() => {
-                            for _ in 0..retries {
-                                match pinger.ping().await {
-                                    Ok(response) => {
-                                        info!(name: "tcping", "Response: {:?}", response);
-                                        metrics.record_tcp_ping(&response);
-                                        break;
-                                    }
-                                    Err(e) => {
-                                        error!("TCP Ping error: {}", e);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            });
-            Ok(task)
-        }
-        Err(e) => {
-            error!("Failed to create TCP pinger: {}", e);
-            Err(anyhow::anyhow!("TCP pinger creation failed: {}", e))
-        }
-    }
-}
+use tracing::{debug, info};
 
 fn cancel_handler() -> (CancellationToken, JoinHandle<()>) {
     let cancel = CancellationToken::new();
@@ -181,6 +27,7 @@ fn cancel_handler() -> (CancellationToken, JoinHandle<()>) {
             _ = sigint.recv() => {
                 info!("Received SIGINT signal, cancelling tasks");
             }
+            _ = cancel_clone.cancelled() => {}
         }
         cancel_clone.cancel();
     });
@@ -191,94 +38,77 @@ fn cancel_handler() -> (CancellationToken, JoinHandle<()>) {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    if args.debug {
-        tracing_subscriber::fmt::init();
+    if args.print_schema {
+        let schema = schemars::schema_for!(PingerConfig);
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
     }
 
     // Load configuration
-    let config = load_config(&args.config).await?;
+    let (mut config, overrides) = pinger::load_config(&args.config, args.config_format).await?;
+    pinger::validate_config(&mut config)?;
+    let tracer_provider = telemetry::init(&args, config.otel.as_ref())?;
+    for config_override in overrides {
+        debug!("{}", config_override);
+    }
 
     // Initialize metrics
-    let metrics: SharedMetrics = Arc::new(PingMetrics::default());
+    let metrics: SharedMetrics = Arc::new(PingMetrics::new(&config));
+
+    // Check mode: validate the configuration without sending traffic
+    if args.check {
+        let resolver = pinger::build_resolver(&config, Arc::clone(&metrics))?;
+        let all_ok = pinger::check_config(config, resolver).await;
+        telemetry::shutdown(tracer_provider);
+        std::process::exit(if all_ok { 0 } else { 1 });
+    }
+
+    // One-shot mode: ping every entry once, or --count times, and exit without serving metrics
+    if args.once {
+        let resolver = pinger::build_resolver(&config, Arc::clone(&metrics))?;
+        let all_ok = pinger::run_once(config, resolver, metrics, args.count.unwrap_or(1)).await;
+        telemetry::shutdown(tracer_provider);
+        std::process::exit(if all_ok { 0 } else { 1 });
+    }
 
     // Ctrl+C to cancel all tasks
     let (cancel, cancel_task) = cancel_handler();
 
-    // Start metrics server in background with CLI configurable host and port
+    // Start metrics server in background with CLI configurable host and port or Unix socket
+    let metrics_binds = MetricsBind::parse_list(&args.bind, args.port);
+    if metrics_binds.is_empty() {
+        return Err(anyhow::anyhow!("--bind names no address").into());
+    }
+    let (reloader, reload) = match args.allow_reload {
+        true => {
+            let (reloader, reload) = Reloader::new(args.config.clone(), args.config_format);
+            (Some(reloader), Some(reload))
+        }
+        false => (None, None),
+    };
     let metrics_server_handle = tokio::spawn(start_metrics_server(
         Arc::clone(&metrics),
-        args.bind.clone(),
-        args.port,
+        metrics_binds.clone(),
+        args.cors.clone(),
+        args.allow_reset,
+        reloader,
+        args.debug,
         cancel.clone(),
     ));
-
-    let resolver = resolver::build_resolver(&config, Arc::clone(&metrics))?;
-    let mut ping_tasks: Vec<JoinHandle<()>> = Vec::new();
-
-    // Create HTTP ping tasks
-    if !config.http.entries.is_empty() {
-        let http_timeout = Duration::from_millis(config.http.timeout_millis);
-        let http_interval = Duration::from_millis(config.http.interval_millis);
-
-        if http_interval < http_timeout {
-            error!("HTTP interval is less than timeout, which is not allowed");
-            return Err("HTTP interval is less than timeout, which is not allowed".into());
-        }
-
-        for entry in config.http.entries {
-            match create_http_ping_task(
-                entry,
-                http_timeout,
-                http_interval,
-                config.http.retries,
-                Arc::clone(&resolver),
-                Arc::clone(&metrics),
-                config.http.pinger,
-                cancel.clone(),
-            ) {
-                Ok(task) => ping_tasks.push(task),
-                Err(e) => error!("Failed to create HTTP ping task: {}", e),
-            }
-        }
-    }
-
-    // Create TCP ping tasks
-    if !config.tcp.entries.is_empty() {
-        let tcp_timeout = Duration::from_millis(config.tcp.timeout_millis);
-        let tcp_interval = Duration::from_millis(config.tcp.interval_millis);
-
-        if tcp_interval < tcp_timeout {
-            error!("TCP interval is less than timeout, which is not allowed");
-            return Err("TCP interval is less than timeout, which is not allowed".into());
-        }
-
-        for entry in config.tcp.entries {
-            match create_tcp_ping_task(
-                entry,
-                tcp_timeout,
-                tcp_interval,
-                config.measure_dns_stats,
-                config.tcp.retries,
-                Arc::clone(&resolver),
-                Arc::clone(&metrics),
-                cancel.clone(),
-            )
-            .await
-            {
-                Ok(task) => ping_tasks.push(task),
-                Err(e) => error!("Failed to create TCP ping task: {}", e),
-            }
-        }
+    for bind in &metrics_binds {
+        println!("Metrics server running on {}/metrics", bind);
     }
 
-    println!(
-        "Metrics server running on http://{}:{}/metrics",
-        args.bind, args.port
-    );
-
-    // Wait for all tasks (runs indefinitely)
-    for task in ping_tasks {
-        let _ = task.await;
+    let options = RunOptions {
+        count: args.count,
+        shutdown_grace: Duration::from_millis(args.shutdown_grace_millis),
+        events: None,
+        reload,
+    };
+    if let Err(e) = pinger::run_with(config, metrics, cancel.clone(), options).await {
+        cancel.cancel();
+        telemetry::shutdown(tracer_provider);
+        return Err(e.into());
     }
 
     // Wait for metrics server
@@ -287,5 +117,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Wait for cancel task
     let _ = cancel_task.await;
 
+    telemetry::shutdown(tracer_provider);
+
     Ok(())
 }
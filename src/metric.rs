@@ -1,31 +1,192 @@
-use crate::{http_pinger, tcp_pinger};
+use crate::config::PingerConfig;
+use crate::resolver::ResolveDeadline;
+use crate::summary::grpc_target;
+use crate::{dns_pinger, grpc_pinger, http_pinger, ntp_pinger, tcp_pinger, ws_pinger};
+use hickory_resolver::lookup::Lookup;
 use hickory_resolver::proto::ProtoErrorKind;
 use hickory_resolver::{ResolveError, ResolveErrorKind};
-use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use opentelemetry::trace::TraceContextExt;
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue, EncodeMetric, MetricEncoder};
+use prometheus_client::metrics::MetricType;
 use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::exemplar::HistogramWithExemplars;
 use prometheus_client::metrics::family::Family;
 use prometheus_client::metrics::gauge::Gauge;
-use prometheus_client::metrics::histogram::{Histogram, exponential_buckets_range};
-use prometheus_client::registry::Registry;
-use std::sync::Arc;
-use std::sync::atomic::AtomicU64;
+use prometheus_client::metrics::histogram::{
+    Histogram, exponential_buckets, exponential_buckets_range, linear_buckets,
+};
+use prometheus_client::registry::{Metric, Registry};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 pub const TIMEOUT_VALUE_US: f64 = std::time::Duration::from_secs(10).as_micros() as f64;
 
+/// Samples kept per endpoint for the response time quantiles when `summary_window` is unset
+const DEFAULT_SUMMARY_WINDOW: usize = 1000;
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
 pub enum PingStatus {
     Success,
     Timeout,
     Failure,
+    AssertionFailure,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
-#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
 pub enum FailureType {
     Dns,
+    Connect,
+    /// Connection setup exceeded `connect_timeout_millis`
+    ConnectTimeout,
+    Tls,
+    /// The server certificate did not match `pinned_cert_sha256`
+    PinMismatch,
+    Protocol,
     Other,
 }
 
+/// Phase a ping was in when it ran out of time
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum TimeoutCause {
+    Dns,
+    /// Connection setup: TCP connect, proxy and TLS or QUIC handshakes
+    Connect,
+    /// Waiting for the response or reading its body, or the TCP probe exchange
+    Response,
+    /// Connection setup or waiting for the response head, which reqwest does not tell apart
+    Request,
+    /// The group's `total_deadline_millis` ran out across the retry attempts of one tick
+    Deadline,
+}
+
+/// Wall-clock time at which `instant` was taken
+pub fn wall_clock(instant: Instant) -> SystemTime {
+    SystemTime::now() - instant.elapsed()
+}
+
+/// Seconds since the Unix epoch, 0 for times before it
+fn unix_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64())
+}
+
+/// Phase of an in-flight ping, read when its deadline hits to tell the [`TimeoutCause`]
+#[derive(Debug)]
+pub struct PingPhase(Mutex<TimeoutCause>);
+
+impl PingPhase {
+    pub fn new(phase: TimeoutCause) -> Self {
+        PingPhase(Mutex::new(phase))
+    }
+
+    pub fn enter(&self, phase: TimeoutCause) {
+        *self.0.lock().unwrap() = phase;
+    }
+
+    pub fn current(&self) -> TimeoutCause {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Estimated bytes of a TCP handshake and teardown, about seven packets of 60 bytes
+pub const TCP_CONNECT_BYTES: u64 = 7 * 60;
+
+/// Estimated bytes of a TLS handshake, most of them the server's certificate chain
+pub const TLS_HANDSHAKE_BYTES: u64 = 5 * 1024;
+
+/// Estimated bytes of a QUIC handshake, a TLS handshake in Initial packets padded to 1200 bytes
+pub const QUIC_HANDSHAKE_BYTES: u64 = TLS_HANDSHAKE_BYTES + 2 * 1200;
+
+/// Bytes a ping has exchanged so far, kept outside of the ping so that one that fails or times
+/// out still reports what it sent
+#[derive(Debug, Default)]
+pub struct ByteTally(AtomicU64);
+
+impl ByteTally {
+    pub fn add(&self, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Trace a histogram observation was made in, attached to its bucket as an exemplar
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TraceLabel {
+    pub trace_id: String,
+}
+
+impl TraceLabel {
+    /// Trace of the current span, `None` unless spans are exported with `otel`
+    fn current() -> Option<Self> {
+        let context = tracing::Span::current().context();
+        let span = context.span();
+        let span_context = span.span_context();
+        span_context.is_valid().then(|| TraceLabel {
+            trace_id: span_context.trace_id().to_string(),
+        })
+    }
+}
+
+/// Response time histograms of one kind, with a trace exemplar per bucket only when spans are
+/// exported with `otel`, so that other setups keep the plain exposition
+#[derive(Clone, Debug)]
+pub enum ResponseTimeHistograms<L> {
+    Plain(Family<L, Histogram>),
+    Exemplars(Family<L, HistogramWithExemplars<TraceLabel>>),
+}
+
+impl<L: Clone + Hash + Eq> ResponseTimeHistograms<L> {
+    fn new(exemplars: bool) -> Self {
+        match exemplars {
+            true => Self::Exemplars(Family::new_with_constructor(
+                PingMetrics::exemplar_histogram,
+            )),
+            false => Self::Plain(Family::new_with_constructor(PingMetrics::default_histogram)),
+        }
+    }
+
+    /// Observe `value` in the histogram of `label`, tagged with the current trace if exemplars
+    /// are kept
+    pub fn observe(&self, label: &L, value: f64) {
+        match self {
+            Self::Plain(family) => family.get_or_create(label).observe(value),
+            Self::Exemplars(family) => family
+                .get_or_create(label)
+                .observe(value, TraceLabel::current()),
+        }
+    }
+
+    pub fn clear(&self) {
+        match self {
+            Self::Plain(family) => family.clear(),
+            Self::Exemplars(family) => family.clear(),
+        }
+    }
+}
+
+impl<L: Clone + Hash + Eq + EncodeLabelSet> EncodeMetric for ResponseTimeHistograms<L> {
+    fn encode(&self, encoder: MetricEncoder) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Plain(family) => family.encode(encoder),
+            Self::Exemplars(family) => family.encode(encoder),
+        }
+    }
+
+    fn metric_type(&self) -> MetricType {
+        MetricType::Histogram
+    }
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct HttpPingLabel {
     pub url: String,
@@ -35,34 +196,294 @@ pub struct HttpPingLabel {
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
-#[allow(dead_code)]
 pub struct HttpPingFailureLabel {
     pub url: String,
     pub method: String,
     pub failure_type: FailureType,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct HttpPingTimeoutLabel {
+    pub url: String,
+    pub method: String,
+    pub cause: TimeoutCause,
+}
+
+/// Value of a captured response header listed in [`http_pinger::LABELED_HEADERS`]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct HttpResponseHeaderLabel {
+    pub url: String,
+    pub method: String,
+    pub header: String,
+    pub value: String,
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 #[allow(dead_code)]
 pub struct TcpPingLabel {
     pub host: String,
     pub port: u32,
+    /// Address the ping went to, only set with `tcp.ip_label`
+    pub ip: Option<String>,
+    /// Address the ping was sent from, only set with `tcp.local_ip_label`
+    pub local_ip: Option<String>,
     pub response: PingStatus,
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
-#[allow(dead_code)]
 pub struct TcpPingFailureLabel {
     pub host: String,
     pub port: u32,
     pub failure_type: FailureType,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TcpPingTimeoutLabel {
+    pub host: String,
+    pub port: u32,
+    pub cause: TimeoutCause,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct GrpcPingLabel {
+    pub url: String,
+    pub service: String,
+    pub response: PingStatus,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct WsPingLabel {
+    pub url: String,
+    pub response: PingStatus,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum WsFailureStage {
+    Handshake,
+    Frame,
+    Timeout,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct WsPingFailureLabel {
+    pub url: String,
+    pub stage: WsFailureStage,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DnsQueryLabel {
+    pub query_name: String,
+    pub record_type: String,
+    pub response: PingStatus,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DnsQueryFailureLabel {
+    pub query_name: String,
+    pub record_type: String,
+    /// Response code, e.g. `NXDomain` or `ServFail`, or `Timeout` / `Error` when no answer came
+    pub rcode: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct NtpServerLabel {
+    pub server: String,
+    pub port: u32,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct NtpPingLabel {
+    pub server: String,
+    pub port: u32,
+    pub response: PingStatus,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct HttpEndpointLabel {
+    pub url: String,
+    pub method: String,
+}
+
+impl HttpEndpointLabel {
+    /// `METHOD url`
+    pub fn target(&self) -> String {
+        format!("{} {}", self.method, self.url)
+    }
+}
+
+/// Endpoint with a response time objective, `kind` being `HTTP` or `TCP`
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct SloLabel {
+    pub kind: String,
+    pub target: String,
+}
+
+/// Ping task of any kind, `kind` being e.g. `HTTP` or `DNS`
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TaskLabel {
+    pub kind: String,
+    pub target: String,
+}
+
+/// Latest ping of an endpoint
+#[derive(Debug, Clone, Copy)]
+struct LastPing {
+    status: &'static str,
+    latency: Option<Duration>,
+    at: Instant,
+}
+
+/// Latest ping of an endpoint as listed by `/health?detailed=true`
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHealth {
+    pub kind: &'static str,
+    pub target: String,
+    /// `success`, `timeout` or `failure`
+    pub status: &'static str,
+    /// Response time of a successful ping
+    pub latency_us: Option<u64>,
+    /// Time since the ping completed
+    pub age_millis: u64,
+}
+
+/// Heartbeats a ping task publishes for `/debug/tasks`
+#[derive(Debug)]
+struct TaskState {
+    kind: &'static str,
+    target: String,
+    spawned: Instant,
+    /// Start of the tick in progress, `None` while the task waits for the next tick
+    pinging: Option<Instant>,
+    last_tick: Option<Instant>,
+    last_success: Option<Instant>,
+    last_failure: Option<Instant>,
+    backoff_multiplier: u32,
+    finished: bool,
+}
+
+/// Handle a ping task publishes its heartbeats through; dropping it marks the task finished,
+/// also when it panicked
+#[derive(Debug)]
+pub struct TaskHeartbeat(Arc<Mutex<TaskState>>);
+
+impl TaskHeartbeat {
+    /// Mark a tick started until the returned guard is dropped
+    pub fn tick(&self) -> TickGuard<'_> {
+        let mut state = self.0.lock().unwrap();
+        let now = Instant::now();
+        state.pinging = Some(now);
+        state.last_tick = Some(now);
+        TickGuard(self)
+    }
+
+    /// Record the outcome of a ping cycle
+    pub fn record(&self, success: bool) {
+        let mut state = self.0.lock().unwrap();
+        match success {
+            true => state.last_success = Some(Instant::now()),
+            false => state.last_failure = Some(Instant::now()),
+        }
+    }
+
+    pub fn set_backoff(&self, multiplier: u32) {
+        self.0.lock().unwrap().backoff_multiplier = multiplier;
+    }
+}
+
+impl Drop for TaskHeartbeat {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        state.pinging = None;
+        state.finished = true;
+    }
+}
+
+/// Tick in progress of a [`TaskHeartbeat`]
+#[derive(Debug)]
+pub struct TickGuard<'a>(&'a TaskHeartbeat);
+
+impl Drop for TickGuard<'_> {
+    fn drop(&mut self) {
+        self.0.0.lock().unwrap_or_else(|e| e.into_inner()).pinging = None;
+    }
+}
+
+/// State of a ping task as listed by `/debug/tasks`, ages in milliseconds
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    pub kind: &'static str,
+    pub target: String,
+    /// `waiting` for the next tick, `pinging` or `finished`
+    pub state: &'static str,
+    pub spawned_age_millis: u64,
+    /// Time spent in the tick in progress, a growing value pointing at a stuck probe
+    pub pinging_millis: Option<u64>,
+    pub last_tick_age_millis: Option<u64>,
+    pub last_success_age_millis: Option<u64>,
+    pub last_failure_age_millis: Option<u64>,
+    /// Interval multiplier of the failure backoff, 1 without backoff
+    pub backoff_multiplier: u32,
+}
+
+impl From<&TaskState> for TaskStatus {
+    fn from(state: &TaskState) -> Self {
+        let age = |at: Option<Instant>| at.map(|at| at.elapsed().as_millis() as u64);
+        TaskStatus {
+            kind: state.kind,
+            target: state.target.clone(),
+            state: match (state.finished, state.pinging) {
+                (true, _) => "finished",
+                (false, Some(_)) => "pinging",
+                (false, None) => "waiting",
+            },
+            spawned_age_millis: state.spawned.elapsed().as_millis() as u64,
+            pinging_millis: age(state.pinging),
+            last_tick_age_millis: age(state.last_tick),
+            last_success_age_millis: age(state.last_success),
+            last_failure_age_millis: age(state.last_failure),
+            backoff_multiplier: state.backoff_multiplier,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TcpEndpointLabel {
+    pub host: String,
+    pub port: u32,
+}
+
+impl TcpEndpointLabel {
+    /// `host:port`, or the SRV name of an SRV entry, whose port is 0
+    pub fn target(&self) -> String {
+        match self.port {
+            0 => self.host.clone(),
+            port => format!("{}:{}", self.host, port),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct ResolveLabel {
     pub host: String,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DnsRecordLabel {
+    pub host: String,
+    pub record_type: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DnsServerLabel {
+    pub server: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DnsServerQueryLabel {
+    pub server: String,
+    pub response: PingStatus,
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct ResolveErrorLabel {
     pub host: String,
@@ -82,19 +503,109 @@ pub struct PingMetrics {
     pub registry: Registry,
 
     // HTTP metrics - Gauge-based individual ping results
-    pub http_ping_response_time_histogram_us: Family<HttpPingLabel, Histogram>,
+    pub http_ping_response_time_histogram_us: ResponseTimeHistograms<HttpPingLabel>,
     pub http_ping_response_time_us: Family<HttpPingLabel, Gauge<f64, AtomicU64>>,
     pub http_ping_failure: Family<HttpPingLabel, Counter>,
+    pub http_ping_failure_by_type: Family<HttpPingFailureLabel, Counter>,
+    pub http_ping_timeout: Family<HttpPingTimeoutLabel, Counter>,
+    pub http_ping_backoff_multiplier: Family<HttpEndpointLabel, Gauge>,
+    pub http_resolve_time_us: Family<HttpEndpointLabel, Histogram>,
+    pub http_ping_attempts: Family<HttpEndpointLabel, Histogram>,
+    pub http_circuit_open: Family<HttpEndpointLabel, Gauge>,
+    pub http_body_bytes: Family<HttpEndpointLabel, Histogram>,
+    pub http_ttfb_us: Family<HttpEndpointLabel, Histogram>,
+    pub http_ping_cold_us: Family<HttpEndpointLabel, Histogram>,
+    pub http_ping_warm_us: Family<HttpEndpointLabel, Histogram>,
+    pub http_ping_sent_timestamp_seconds: Family<HttpEndpointLabel, Gauge<f64, AtomicU64>>,
+    pub tls_pin_mismatch: Family<HttpEndpointLabel, Counter>,
+    pub http_response_header: Family<HttpResponseHeaderLabel, Counter>,
 
     // TCP metrics - Gauge-based individual ping results
-    pub tcp_ping_response_time_histogram_us: Family<TcpPingLabel, Histogram>,
+    pub tcp_ping_response_time_histogram_us: ResponseTimeHistograms<TcpPingLabel>,
     pub tcp_ping_response_time_us: Family<TcpPingLabel, Gauge<f64, AtomicU64>>,
     pub tcp_ping_failure: Family<TcpPingLabel, Counter>,
+    pub tcp_ping_failure_by_type: Family<TcpPingFailureLabel, Counter>,
+    pub tcp_ping_timeout: Family<TcpPingTimeoutLabel, Counter>,
+    pub tcp_ping_backoff_multiplier: Family<TcpEndpointLabel, Gauge>,
+    pub tcp_ping_attempts: Family<TcpEndpointLabel, Histogram>,
+    pub tcp_probe_time_us: Family<TcpEndpointLabel, Histogram>,
+    pub tcp_circuit_open: Family<TcpEndpointLabel, Gauge>,
+    pub tcp_ping_sent_timestamp_seconds: Family<TcpEndpointLabel, Gauge<f64, AtomicU64>>,
+
+    // gRPC health check metrics
+    pub grpc_health_response_time_histogram_us: Family<GrpcPingLabel, Histogram>,
+    pub grpc_health_response_time_us: Family<GrpcPingLabel, Gauge<f64, AtomicU64>>,
+    pub grpc_health_failure: Family<GrpcPingLabel, Counter>,
+
+    // WebSocket metrics
+    pub ws_ping_response_time_histogram_us: Family<WsPingLabel, Histogram>,
+    pub ws_ping_response_time_us: Family<WsPingLabel, Gauge<f64, AtomicU64>>,
+    pub ws_pong_time_us: Family<WsPingLabel, Gauge<f64, AtomicU64>>,
+    pub ws_ping_failure: Family<WsPingFailureLabel, Counter>,
+
+    // DNS query probe metrics
+    pub dns_query_time_histogram_us: Family<DnsQueryLabel, Histogram>,
+    pub dns_query_time_us: Family<DnsQueryLabel, Gauge<f64, AtomicU64>>,
+    pub dns_query_failure: Family<DnsQueryFailureLabel, Counter>,
+
+    // NTP probe metrics
+    pub ntp_offset_seconds: Family<NtpServerLabel, Gauge<f64, AtomicU64>>,
+    pub ntp_stratum: Family<NtpServerLabel, Gauge>,
+    pub ntp_rtt_histogram_us: Family<NtpPingLabel, Histogram>,
+    pub ntp_rtt_us: Family<NtpPingLabel, Gauge<f64, AtomicU64>>,
+    pub ntp_ping_failure: Family<NtpPingLabel, Counter>,
 
     // DNS metrics
     pub resolve_time_histogram_us: Family<ResolveLabel, Histogram>,
     pub resolve_time_us: Family<ResolveLabel, Gauge<f64, AtomicU64>>,
     pub resolve_failure: Family<ResolveErrorLabel, Counter>,
+    pub resolve_total: Family<ResolveLabel, Counter>,
+    pub resolve_timeout: Family<ResolveLabel, Counter>,
+    pub dns_resolved_addresses: Family<ResolveLabel, Gauge>,
+    pub dns_attempts: Family<ResolveLabel, Histogram>,
+    pub dns_answer_count: Family<ResolveLabel, Histogram>,
+    pub dns_record_ttl_seconds: Family<DnsRecordLabel, Gauge>,
+    pub dns_server_query_time_histogram_us: Family<DnsServerLabel, Histogram>,
+    pub dns_server_query: Family<DnsServerQueryLabel, Counter>,
+
+    // Scheduling metrics
+    pub ping_skipped: Counter,
+    pub ping_cycle_failed: Family<TaskLabel, Counter>,
+    pub ping_rate_limited: Counter,
+    pub ping_retry_after: Counter,
+
+    // Byte budget metrics
+    pub probe_bytes: Counter,
+    pub budget_exhausted: Counter,
+    /// Only with `max_bytes_per_minute`
+    byte_budget: Option<ByteBudget>,
+
+    // Exporter self-metrics
+    pub metrics_scrape_duration_seconds: Histogram,
+
+    // Response time objectives, only for entries with `slo_millis`
+    pub slo_breach: Family<SloLabel, Counter>,
+    slos: Mutex<HashMap<(&'static str, String), Duration>>,
+
+    // Rolling response time quantiles, only with `metrics.use_summary`
+    http_summary: Option<ResponseTimeSummary<HttpEndpointLabel>>,
+    tcp_summary: Option<ResponseTimeSummary<TcpEndpointLabel>>,
+
+    // Smoothed response times, only with `metrics.ewma_alpha`
+    http_ewma: Option<ResponseTimeEwma<HttpEndpointLabel>>,
+    tcp_ewma: Option<ResponseTimeEwma<TcpEndpointLabel>>,
+
+    /// Set once any endpoint has been pinged successfully
+    ready: AtomicBool,
+    /// Latest ping of every endpoint keyed by probe kind and target, for the detailed health
+    last_pings: Mutex<BTreeMap<(&'static str, String), LastPing>>,
+    /// Heartbeats of every spawned ping task, kept across resets
+    tasks: Mutex<Vec<Arc<Mutex<TaskState>>>>,
+    /// Label TCP ping series with the resolved IP
+    tcp_ip_label: bool,
+    tcp_local_ip_label: bool,
+    /// Split HTTP response times by whether the connection was reused
+    http_cold_warm: bool,
 }
 
 pub type SharedMetrics = Arc<PingMetrics>;
@@ -103,25 +614,139 @@ impl PingMetrics {
     fn default_histogram() -> Histogram {
         Histogram::new(exponential_buckets_range(100.0, 2e6, 20))
     }
+
+    /// [`Self::default_histogram`] buckets, with an exemplar per bucket
+    fn exemplar_histogram() -> HistogramWithExemplars<TraceLabel> {
+        HistogramWithExemplars::new(exponential_buckets_range(100.0, 2e6, 20))
+    }
+
+    fn body_bytes_histogram() -> Histogram {
+        Histogram::new(exponential_buckets(256.0, 4.0, 10))
+    }
+
+    fn attempts_histogram() -> Histogram {
+        Histogram::new(linear_buckets(1.0, 1.0, 10))
+    }
+
+    /// Buckets for answer record counts, 0 included for empty answers
+    fn answer_count_histogram() -> Histogram {
+        Histogram::new([0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0])
+    }
 }
 
 impl Default for PingMetrics {
     fn default() -> Self {
-        let mut registry = Registry::default();
+        Self::with_registry(Registry::default(), &HashMap::new(), false)
+    }
+}
 
+/// Registry wrapper replacing the help text of the families named in `metric_help`
+struct HelpRegistry<'a> {
+    registry: &'a mut Registry,
+    help: &'a HashMap<String, String>,
+}
+
+impl HelpRegistry<'_> {
+    fn register(&mut self, name: impl Into<String>, help: impl Into<String>, metric: impl Metric) {
+        let name = name.into();
+        let help = match self.help.get(&name) {
+            Some(help) => help.clone(),
+            None => help.into(),
+        };
+        self.registry.register(name, help, metric);
+    }
+}
+
+impl PingMetrics {
+    /// Register every metric family in `registry`, with the help texts overridden by `help` and
+    /// trace exemplars on the response time histograms when `exemplars` is set
+    fn with_registry(mut inner: Registry, help: &HashMap<String, String>, exemplars: bool) -> Self {
+        let mut registry = HelpRegistry {
+            registry: &mut inner,
+            help,
+        };
         let http_ping_failure = Family::<HttpPingLabel, Counter>::default();
+        let http_ping_failure_by_type = Family::<HttpPingFailureLabel, Counter>::default();
+        let http_ping_timeout = Family::<HttpPingTimeoutLabel, Counter>::default();
         let tcp_ping_failure = Family::<TcpPingLabel, Counter>::default();
+        let tcp_ping_failure_by_type = Family::<TcpPingFailureLabel, Counter>::default();
+        let tcp_ping_timeout = Family::<TcpPingTimeoutLabel, Counter>::default();
         let resolve_failure = Family::<ResolveErrorLabel, Counter>::default();
+        let grpc_health_failure = Family::<GrpcPingLabel, Counter>::default();
+        let grpc_health_response_time_histogram_us =
+            Family::<GrpcPingLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let grpc_health_response_time_us =
+            Family::<GrpcPingLabel, Gauge<f64, AtomicU64>>::default();
+        let ws_ping_response_time_histogram_us =
+            Family::<WsPingLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let ws_ping_response_time_us = Family::<WsPingLabel, Gauge<f64, AtomicU64>>::default();
+        let ws_pong_time_us = Family::<WsPingLabel, Gauge<f64, AtomicU64>>::default();
+        let ws_ping_failure = Family::<WsPingFailureLabel, Counter>::default();
+        let dns_query_time_histogram_us =
+            Family::<DnsQueryLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let dns_query_time_us = Family::<DnsQueryLabel, Gauge<f64, AtomicU64>>::default();
+        let dns_query_failure = Family::<DnsQueryFailureLabel, Counter>::default();
+        let ntp_offset_seconds = Family::<NtpServerLabel, Gauge<f64, AtomicU64>>::default();
+        let ntp_stratum = Family::<NtpServerLabel, Gauge>::default();
+        let ntp_rtt_histogram_us =
+            Family::<NtpPingLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let ntp_rtt_us = Family::<NtpPingLabel, Gauge<f64, AtomicU64>>::default();
+        let ntp_ping_failure = Family::<NtpPingLabel, Counter>::default();
+        let resolve_total = Family::<ResolveLabel, Counter>::default();
+        let resolve_timeout = Family::<ResolveLabel, Counter>::default();
+        let ping_skipped = Counter::default();
+        let ping_cycle_failed = Family::<TaskLabel, Counter>::default();
+        let ping_rate_limited = Counter::default();
+        let ping_retry_after = Counter::default();
+        let probe_bytes = Counter::default();
+        let budget_exhausted = Counter::default();
+        let slo_breach = Family::<SloLabel, Counter>::default();
+        let metrics_scrape_duration_seconds =
+            Histogram::new(exponential_buckets_range(1e-4, 1.0, 12));
 
-        let http_ping_response_time_histogram_us =
-            Family::<HttpPingLabel, Histogram>::new_with_constructor(Self::default_histogram);
-        let tcp_ping_response_time_histogram_us =
-            Family::<TcpPingLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let http_ping_response_time_histogram_us = ResponseTimeHistograms::new(exemplars);
+        let tcp_ping_response_time_histogram_us = ResponseTimeHistograms::new(exemplars);
         let resolve_time_histogram_us =
             Family::<ResolveLabel, Histogram>::new_with_constructor(Self::default_histogram);
         let http_ping_response_time_us = Family::<HttpPingLabel, Gauge<f64, AtomicU64>>::default();
         let tcp_ping_response_time_us = Family::<TcpPingLabel, Gauge<f64, AtomicU64>>::default();
         let resolve_time_us = Family::<ResolveLabel, Gauge<f64, AtomicU64>>::default();
+        let dns_resolved_addresses = Family::<ResolveLabel, Gauge>::default();
+        let dns_attempts =
+            Family::<ResolveLabel, Histogram>::new_with_constructor(Self::attempts_histogram);
+        let dns_answer_count =
+            Family::<ResolveLabel, Histogram>::new_with_constructor(Self::answer_count_histogram);
+        let dns_record_ttl_seconds = Family::<DnsRecordLabel, Gauge>::default();
+        let dns_server_query_time_histogram_us =
+            Family::<DnsServerLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let dns_server_query = Family::<DnsServerQueryLabel, Counter>::default();
+        let http_ping_backoff_multiplier = Family::<HttpEndpointLabel, Gauge>::default();
+        let http_resolve_time_us =
+            Family::<HttpEndpointLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let tcp_ping_backoff_multiplier = Family::<TcpEndpointLabel, Gauge>::default();
+        let http_ping_attempts =
+            Family::<HttpEndpointLabel, Histogram>::new_with_constructor(Self::attempts_histogram);
+        let tcp_probe_time_us =
+            Family::<TcpEndpointLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let tcp_ping_attempts =
+            Family::<TcpEndpointLabel, Histogram>::new_with_constructor(Self::attempts_histogram);
+        let http_circuit_open = Family::<HttpEndpointLabel, Gauge>::default();
+        let tls_pin_mismatch = Family::<HttpEndpointLabel, Counter>::default();
+        let http_response_header = Family::<HttpResponseHeaderLabel, Counter>::default();
+        let http_ping_sent_timestamp_seconds =
+            Family::<HttpEndpointLabel, Gauge<f64, AtomicU64>>::default();
+        let tcp_ping_sent_timestamp_seconds =
+            Family::<TcpEndpointLabel, Gauge<f64, AtomicU64>>::default();
+        let http_body_bytes = Family::<HttpEndpointLabel, Histogram>::new_with_constructor(
+            Self::body_bytes_histogram,
+        );
+        let http_ttfb_us =
+            Family::<HttpEndpointLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let http_ping_cold_us =
+            Family::<HttpEndpointLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let http_ping_warm_us =
+            Family::<HttpEndpointLabel, Histogram>::new_with_constructor(Self::default_histogram);
+        let tcp_circuit_open = Family::<TcpEndpointLabel, Gauge>::default();
 
         // HTTP metrics
         registry.register(
@@ -129,16 +754,81 @@ impl Default for PingMetrics {
             "Failure number of HTTP ping requests",
             http_ping_failure.clone(),
         );
+        registry.register(
+            "http_ping_failure_by_type",
+            "Failure number of HTTP ping requests by failure type: Dns, Connect, ConnectTimeout, Tls, PinMismatch, Protocol or Other",
+            http_ping_failure_by_type.clone(),
+        );
+        registry.register(
+            "http_ping_timeout",
+            "Timed out HTTP ping requests by the phase in flight: Dns, Connect, Response, Request for reqwest up to the response head, or Deadline when the retries of a tick ran past total_deadline_millis",
+            http_ping_timeout.clone(),
+        );
         registry.register(
             "http_ping_response_time_histogram_us",
-            "HTTP ping response time histogram in us - updates with each ping",
+            "HTTP ping total response time histogram in us, including any body read - updates with each ping",
             http_ping_response_time_histogram_us.clone(),
         );
         registry.register(
             "http_ping_response_time_us",
-            "HTTP ping response time in us - updates with each ping",
+            "HTTP ping total response time in us, including any body read - updates with each ping",
             http_ping_response_time_us.clone(),
         );
+        registry.register(
+            "http_ping_backoff_multiplier",
+            "Current HTTP ping interval multiplier caused by consecutive failures",
+            http_ping_backoff_multiplier.clone(),
+        );
+        registry.register(
+            "http_resolve_time_us",
+            "DNS resolve time histogram in us for HTTP pings - hyper backend only",
+            http_resolve_time_us.clone(),
+        );
+        registry.register(
+            "http_ping_attempts",
+            "Attempts used per HTTP ping tick - exhausted retries record the maximum",
+            http_ping_attempts.clone(),
+        );
+        registry.register(
+            "http_circuit_open",
+            "Whether the circuit breaker of an HTTP endpoint is open (1) or closed (0)",
+            http_circuit_open.clone(),
+        );
+        registry.register(
+            "http_body_bytes",
+            "HTTP response body bytes read per ping - present with read_body_bytes",
+            http_body_bytes.clone(),
+        );
+        registry.register(
+            "http_ttfb_us",
+            "HTTP time to first byte histogram in us - until the response head arrived",
+            http_ttfb_us.clone(),
+        );
+        registry.register(
+            "http_ping_cold_us",
+            "HTTP response time histogram in us of pings over a fresh connection - present with compare_cold_warm",
+            http_ping_cold_us.clone(),
+        );
+        registry.register(
+            "http_ping_warm_us",
+            "HTTP response time histogram in us of pings over a reused connection - present with compare_cold_warm",
+            http_ping_warm_us.clone(),
+        );
+        registry.register(
+            "http_ping_sent_timestamp_seconds",
+            "Wall-clock time the latest HTTP ping was sent, in seconds since the Unix epoch",
+            http_ping_sent_timestamp_seconds.clone(),
+        );
+        registry.register(
+            "tls_pin_mismatch",
+            "HTTP pings whose server certificate did not match the entry's pinned_cert_sha256",
+            tls_pin_mismatch.clone(),
+        );
+        registry.register(
+            "http_response_header",
+            "HTTP responses by the value of captured low-cardinality headers: Server, X-Cache and CF-Cache-Status",
+            http_response_header.clone(),
+        );
 
         // TCP metrics
         registry.register(
@@ -146,6 +836,16 @@ impl Default for PingMetrics {
             "Failure number of TCP ping requests",
             tcp_ping_failure.clone(),
         );
+        registry.register(
+            "tcp_ping_failure_by_type",
+            "Failure number of TCP ping requests by failure type: Dns, Connect, Protocol or Other",
+            tcp_ping_failure_by_type.clone(),
+        );
+        registry.register(
+            "tcp_ping_timeout",
+            "Timed out TCP ping requests by the phase in flight: Dns, Connect, Response (the probe exchange), or Deadline when the retries of a tick ran past total_deadline_millis",
+            tcp_ping_timeout.clone(),
+        );
         registry.register(
             "tcp_ping_response_time_histogram_us",
             "TCP ping response time histogram in us - updates with each ping",
@@ -156,6 +856,31 @@ impl Default for PingMetrics {
             "TCP ping response time in us - updates with each ping",
             tcp_ping_response_time_us.clone(),
         );
+        registry.register(
+            "tcp_probe_time_us",
+            "TCP probe exchange time histogram in us, after the connection was established - present with probe",
+            tcp_probe_time_us.clone(),
+        );
+        registry.register(
+            "tcp_ping_backoff_multiplier",
+            "Current TCP ping interval multiplier caused by consecutive failures",
+            tcp_ping_backoff_multiplier.clone(),
+        );
+        registry.register(
+            "tcp_ping_attempts",
+            "Attempts used per TCP ping tick - exhausted retries record the maximum",
+            tcp_ping_attempts.clone(),
+        );
+        registry.register(
+            "tcp_circuit_open",
+            "Whether the circuit breaker of a TCP endpoint is open (1) or closed (0)",
+            tcp_circuit_open.clone(),
+        );
+        registry.register(
+            "tcp_ping_sent_timestamp_seconds",
+            "Wall-clock time the latest TCP ping was sent, in seconds since the Unix epoch",
+            tcp_ping_sent_timestamp_seconds.clone(),
+        );
 
         // DNS metrics
         registry.register(
@@ -163,6 +888,92 @@ impl Default for PingMetrics {
             "DNS resolution error count - present when DNS is timed",
             resolve_failure.clone(),
         );
+        registry.register(
+            "resolve",
+            "DNS resolution count, successful or not - present when DNS is timed",
+            resolve_total.clone(),
+        );
+        registry.register(
+            "resolve_timeout",
+            "DNS resolution timeout count, also counted in resolve_failure - present when DNS is timed",
+            resolve_timeout.clone(),
+        );
+        registry.register(
+            "grpc_health_response_time_histogram_us",
+            "gRPC health check RPC round trip time histogram in us - updates with each ping",
+            grpc_health_response_time_histogram_us.clone(),
+        );
+        registry.register(
+            "grpc_health_response_time_us",
+            "gRPC health check RPC round trip time in us, 10s on failure - updates with each ping",
+            grpc_health_response_time_us.clone(),
+        );
+        registry.register(
+            "grpc_health_failure",
+            "Failure number of gRPC health checks, including services not reported as SERVING",
+            grpc_health_failure.clone(),
+        );
+        registry.register(
+            "ws_ping_response_time_histogram_us",
+            "WebSocket time to an established connection histogram in us - updates with each ping",
+            ws_ping_response_time_histogram_us.clone(),
+        );
+        registry.register(
+            "ws_ping_response_time_us",
+            "WebSocket time to an established connection in us, 10s on failure - updates with each ping",
+            ws_ping_response_time_us.clone(),
+        );
+        registry.register(
+            "ws_pong_time_us",
+            "WebSocket ping frame round trip time in us - present when ping_frame is set",
+            ws_pong_time_us.clone(),
+        );
+        registry.register(
+            "ws_ping_failure",
+            "Failure number of WebSocket pings by stage: handshake, frame exchange or timeout",
+            ws_ping_failure.clone(),
+        );
+        registry.register(
+            "dns_query_time_histogram_us",
+            "DNS probe query time histogram in us - updates with each query",
+            dns_query_time_histogram_us.clone(),
+        );
+        registry.register(
+            "dns_query_time_us",
+            "DNS probe query time in us, 10s on failure - updates with each query",
+            dns_query_time_us.clone(),
+        );
+        registry.register(
+            "dns_query_failure",
+            "Failure number of DNS probe queries by response code",
+            dns_query_failure.clone(),
+        );
+        registry.register(
+            "ntp_offset_seconds",
+            "Clock offset of the NTP server relative to the local clock in seconds, positive when \
+             the server is ahead - updates with each successful ping",
+            ntp_offset_seconds.clone(),
+        );
+        registry.register(
+            "ntp_stratum",
+            "Stratum reported by the NTP server - updates with each successful ping",
+            ntp_stratum.clone(),
+        );
+        registry.register(
+            "ntp_rtt_histogram_us",
+            "NTP round-trip delay histogram in us, server processing time excluded",
+            ntp_rtt_histogram_us.clone(),
+        );
+        registry.register(
+            "ntp_rtt_us",
+            "NTP round-trip delay in us, 10s on failure - updates with each ping",
+            ntp_rtt_us.clone(),
+        );
+        registry.register(
+            "ntp_ping_failure",
+            "Failure number of NTP pings",
+            ntp_ping_failure.clone(),
+        );
         registry.register(
             "resolve_time_histogram_us",
             "DNS resolve time histogram in us - present when DNS is timed",
@@ -173,37 +984,540 @@ impl Default for PingMetrics {
             "DNS resolve time in us - updates with each ping",
             resolve_time_us.clone(),
         );
+        registry.register(
+            "dns_resolved_addresses",
+            "Number of addresses returned by the last successful lookup - present when DNS is timed",
+            dns_resolved_addresses.clone(),
+        );
+        registry.register(
+            "dns_attempts",
+            "DNS lookup attempts per resolution - present when dns_retries is set",
+            dns_attempts.clone(),
+        );
+        registry.register(
+            "dns_answer_count",
+            "Addresses in each lookup answer, 0 for an answer without any - present when DNS is timed",
+            dns_answer_count.clone(),
+        );
+        registry.register(
+            "dns_record_ttl_seconds",
+            "Lowest TTL per record type in the last successful lookup, CNAMEs included - present when DNS is timed",
+            dns_record_ttl_seconds.clone(),
+        );
+        registry.register(
+            "dns_server_query_time_histogram_us",
+            "Query time histogram in us per configured name server - present with nameservers",
+            dns_server_query_time_histogram_us.clone(),
+        );
+        registry.register(
+            "dns_server_query",
+            "Queries per configured name server by outcome, NXDOMAIN answers count as success - present with nameservers",
+            dns_server_query.clone(),
+        );
+
+        // Scheduling metrics
+        registry.register(
+            "ping_skipped",
+            "Ticks skipped because no concurrency permit was available within the interval",
+            ping_skipped.clone(),
+        );
+        registry.register(
+            "ping_cycle_failed",
+            "Ping ticks of any kind in which no attempt succeeded, counted once per tick",
+            ping_cycle_failed.clone(),
+        );
+        registry.register(
+            "ping_rate_limited",
+            "Ticks skipped because the host rate limit left no slot within the interval",
+            ping_rate_limited.clone(),
+        );
+        registry.register(
+            "ping_retry_after",
+            "HTTP pings answered with 429 or 503 and a Retry-After header, deferring the next tick",
+            ping_retry_after.clone(),
+        );
+
+        // Byte budget metrics
+        registry.register(
+            "probe_bytes",
+            "Approximate bytes sent and received by pings, including estimated TCP, TLS and QUIC handshake bytes",
+            probe_bytes.clone(),
+        );
+        registry.register(
+            "budget_exhausted",
+            "Minutes in which max_bytes_per_minute was used up, pausing pings until the next one",
+            budget_exhausted.clone(),
+        );
+        registry.register(
+            "metrics_scrape_duration_seconds",
+            "Time taken to encode all metric families for a /metrics scrape",
+            metrics_scrape_duration_seconds.clone(),
+        );
+        registry.register(
+            "slo_breach",
+            "Successful pings slower than their entry's slo_millis, by probe kind and target",
+            slo_breach.clone(),
+        );
 
         Self {
-            registry,
+            registry: inner,
             http_ping_failure,
+            http_ping_failure_by_type,
+            http_ping_timeout,
             http_ping_response_time_histogram_us,
             http_ping_response_time_us,
+            http_ping_backoff_multiplier,
+            http_resolve_time_us,
+            http_ping_attempts,
+            http_circuit_open,
+            http_body_bytes,
+            http_ttfb_us,
+            http_ping_cold_us,
+            http_ping_warm_us,
+            http_ping_sent_timestamp_seconds,
+            tls_pin_mismatch,
+            http_response_header,
             tcp_ping_response_time_histogram_us,
             tcp_ping_response_time_us,
             tcp_ping_failure,
+            tcp_ping_failure_by_type,
+            tcp_ping_timeout,
+            tcp_ping_backoff_multiplier,
+            tcp_ping_attempts,
+            tcp_probe_time_us,
+            tcp_circuit_open,
+            tcp_ping_sent_timestamp_seconds,
+            grpc_health_response_time_histogram_us,
+            grpc_health_response_time_us,
+            grpc_health_failure,
+            ws_ping_response_time_histogram_us,
+            ws_ping_response_time_us,
+            ws_pong_time_us,
+            ws_ping_failure,
+            dns_query_time_histogram_us,
+            dns_query_time_us,
+            dns_query_failure,
+            ntp_offset_seconds,
+            ntp_stratum,
+            ntp_rtt_histogram_us,
+            ntp_rtt_us,
+            ntp_ping_failure,
             resolve_time_histogram_us,
             resolve_time_us,
             resolve_failure,
+            resolve_total,
+            resolve_timeout,
+            dns_resolved_addresses,
+            dns_attempts,
+            dns_answer_count,
+            dns_record_ttl_seconds,
+            dns_server_query_time_histogram_us,
+            dns_server_query,
+            ping_skipped,
+            ping_cycle_failed,
+            ping_rate_limited,
+            ping_retry_after,
+            probe_bytes,
+            budget_exhausted,
+            byte_budget: None,
+            metrics_scrape_duration_seconds,
+            slo_breach,
+            slos: Mutex::new(HashMap::new()),
+            ready: AtomicBool::new(false),
+            last_pings: Mutex::new(BTreeMap::new()),
+            tasks: Mutex::new(Vec::new()),
+            tcp_ip_label: false,
+            tcp_local_ip_label: false,
+            http_cold_warm: false,
+            http_summary: None,
+            tcp_summary: None,
+            http_ewma: None,
+            tcp_ewma: None,
         }
     }
 }
 
 impl PingMetrics {
+    pub fn new(config: &PingerConfig) -> Self {
+        let global_labels = config
+            .global_labels
+            .iter()
+            .map(|(name, value)| (name.clone().into(), value.clone().into()));
+        let mut metrics = Self {
+            tcp_ip_label: config.tcp.ip_label,
+            tcp_local_ip_label: config.tcp.local_ip_label,
+            http_cold_warm: config.http.compare_cold_warm,
+            byte_budget: config.max_bytes_per_minute.map(ByteBudget::new),
+            ..Self::with_registry(
+                Registry::with_labels(global_labels),
+                &config.metric_help,
+                config.otel.is_some(),
+            )
+        };
+        if config.metrics.use_summary {
+            let window = config
+                .metrics
+                .summary_window
+                .unwrap_or(DEFAULT_SUMMARY_WINDOW)
+                .max(1);
+            let mut registry = HelpRegistry {
+                registry: &mut metrics.registry,
+                help: &config.metric_help,
+            };
+            let http_summary = ResponseTimeSummary::register(&mut registry, "http", window);
+            let tcp_summary = ResponseTimeSummary::register(&mut registry, "tcp", window);
+            metrics.http_summary = Some(http_summary);
+            metrics.tcp_summary = Some(tcp_summary);
+        }
+        if let Some(alpha) = config.metrics.ewma_alpha {
+            let mut registry = HelpRegistry {
+                registry: &mut metrics.registry,
+                help: &config.metric_help,
+            };
+            let http_ewma = ResponseTimeEwma::register(&mut registry, "http", alpha);
+            let tcp_ewma = ResponseTimeEwma::register(&mut registry, "tcp", alpha);
+            metrics.http_ewma = Some(http_ewma);
+            metrics.tcp_ewma = Some(tcp_ewma);
+        }
+        metrics
+    }
+
+    /// Whether the response time histograms carry trace exemplars, which only the OpenMetrics
+    /// exposition format can express
+    pub fn has_exemplars(&self) -> bool {
+        matches!(
+            self.http_ping_response_time_histogram_us,
+            ResponseTimeHistograms::Exemplars(_)
+        )
+    }
+
+    /// Whether at least one ping has succeeded since startup
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    fn record_last_ping(
+        &self,
+        kind: &'static str,
+        target: String,
+        status: &PingStatus,
+        latency: Option<Duration>,
+    ) {
+        let ping = LastPing {
+            status: match status {
+                PingStatus::Success => "success",
+                PingStatus::Timeout => "timeout",
+                PingStatus::Failure | PingStatus::AssertionFailure => "failure",
+            },
+            latency,
+            at: Instant::now(),
+        };
+        self.last_pings.lock().unwrap().insert((kind, target), ping);
+    }
+
+    /// Count pings of `target` slower than `slo` in `slo_breach`, which starts out at 0
+    pub fn set_slo(&self, kind: &'static str, target: String, slo: Duration) {
+        let _ = self.slo_breach.get_or_create(&SloLabel {
+            kind: kind.to_string(),
+            target: target.clone(),
+        });
+        self.slos.lock().unwrap().insert((kind, target), slo);
+    }
+
+    fn record_slo(&self, kind: &'static str, target: &str, latency: Option<Duration>) {
+        let Some(latency) = latency else {
+            return;
+        };
+        let slos = self.slos.lock().unwrap();
+        if slos
+            .get(&(kind, target.to_string()))
+            .is_some_and(|slo| latency > *slo)
+        {
+            self.slo_breach
+                .get_or_create(&SloLabel {
+                    kind: kind.to_string(),
+                    target: target.to_string(),
+                })
+                .inc();
+        }
+    }
+
+    /// Latest ping of every endpoint pinged since startup or the last reset
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        let last_pings = self.last_pings.lock().unwrap();
+        last_pings
+            .iter()
+            .map(|((kind, target), ping)| EndpointHealth {
+                kind,
+                target: target.clone(),
+                status: ping.status,
+                latency_us: ping.latency.map(|latency| latency.as_micros() as u64),
+                age_millis: ping.at.elapsed().as_millis() as u64,
+            })
+            .collect()
+    }
+
+    /// Register a spawned ping task of `target`, which publishes its heartbeats through the
+    /// returned handle
+    pub fn register_task(&self, kind: &'static str, target: String) -> TaskHeartbeat {
+        let state = Arc::new(Mutex::new(TaskState {
+            kind,
+            target,
+            spawned: Instant::now(),
+            pinging: None,
+            last_tick: None,
+            last_success: None,
+            last_failure: None,
+            backoff_multiplier: 1,
+            finished: false,
+        }));
+        self.tasks.lock().unwrap().push(state.clone());
+        TaskHeartbeat(state)
+    }
+
+    /// State of every ping task spawned since startup
+    pub fn task_states(&self) -> Vec<TaskStatus> {
+        let tasks = self.tasks.lock().unwrap();
+        tasks
+            .iter()
+            .map(|state| TaskStatus::from(&*state.lock().unwrap_or_else(|e| e.into_inner())))
+            .collect()
+    }
+
+    /// Clear every metric family and counter; readiness is kept
+    pub fn reset(&self) {
+        self.http_ping_failure.clear();
+        self.http_ping_failure_by_type.clear();
+        self.http_ping_timeout.clear();
+        self.http_ping_response_time_histogram_us.clear();
+        self.http_ping_response_time_us.clear();
+        self.http_ping_backoff_multiplier.clear();
+        self.http_resolve_time_us.clear();
+        self.http_ping_attempts.clear();
+        self.http_circuit_open.clear();
+        self.http_body_bytes.clear();
+        self.http_ttfb_us.clear();
+        self.http_ping_cold_us.clear();
+        self.http_ping_warm_us.clear();
+        self.http_ping_sent_timestamp_seconds.clear();
+        self.tls_pin_mismatch.clear();
+        self.http_response_header.clear();
+        self.tcp_ping_response_time_histogram_us.clear();
+        self.tcp_ping_response_time_us.clear();
+        self.tcp_ping_failure.clear();
+        self.tcp_ping_failure_by_type.clear();
+        self.tcp_ping_timeout.clear();
+        self.tcp_ping_backoff_multiplier.clear();
+        self.tcp_ping_attempts.clear();
+        self.tcp_probe_time_us.clear();
+        self.tcp_circuit_open.clear();
+        self.tcp_ping_sent_timestamp_seconds.clear();
+        self.grpc_health_response_time_histogram_us.clear();
+        self.grpc_health_response_time_us.clear();
+        self.grpc_health_failure.clear();
+        self.ws_ping_response_time_histogram_us.clear();
+        self.ws_ping_response_time_us.clear();
+        self.ws_pong_time_us.clear();
+        self.ws_ping_failure.clear();
+        self.dns_query_time_histogram_us.clear();
+        self.dns_query_time_us.clear();
+        self.dns_query_failure.clear();
+        self.ntp_offset_seconds.clear();
+        self.ntp_stratum.clear();
+        self.ntp_rtt_histogram_us.clear();
+        self.ntp_rtt_us.clear();
+        self.ntp_ping_failure.clear();
+        self.resolve_time_histogram_us.clear();
+        self.resolve_time_us.clear();
+        self.resolve_failure.clear();
+        self.resolve_total.clear();
+        self.resolve_timeout.clear();
+        self.dns_resolved_addresses.clear();
+        self.dns_attempts.clear();
+        self.dns_answer_count.clear();
+        self.dns_record_ttl_seconds.clear();
+        self.dns_server_query_time_histogram_us.clear();
+        self.dns_server_query.clear();
+        self.ping_skipped.inner().store(0, Ordering::Relaxed);
+        self.ping_cycle_failed.clear();
+        self.ping_rate_limited.inner().store(0, Ordering::Relaxed);
+        self.ping_retry_after.inner().store(0, Ordering::Relaxed);
+        self.probe_bytes.inner().store(0, Ordering::Relaxed);
+        self.budget_exhausted.inner().store(0, Ordering::Relaxed);
+        self.last_pings.lock().unwrap().clear();
+        self.slo_breach.clear();
+        for (kind, target) in self.slos.lock().unwrap().keys() {
+            let _ = self.slo_breach.get_or_create(&SloLabel {
+                kind: kind.to_string(),
+                target: target.clone(),
+            });
+        }
+        if let Some(summary) = &self.http_summary {
+            summary.clear();
+        }
+        if let Some(summary) = &self.tcp_summary {
+            summary.clear();
+        }
+        if let Some(ewma) = &self.http_ewma {
+            ewma.clear();
+        }
+        if let Some(ewma) = &self.tcp_ewma {
+            ewma.clear();
+        }
+    }
+
+    /// Whether the byte budget leaves room for pings in the current minute, always true
+    /// without `max_bytes_per_minute`
+    pub fn budget_allows(&self) -> bool {
+        self.byte_budget
+            .as_ref()
+            .is_none_or(|budget| !budget.window().exhausted)
+    }
+
+    /// Count `bytes` exchanged by a ping against the byte budget
+    pub(crate) fn record_bytes(&self, bytes: u64) {
+        self.probe_bytes.inc_by(bytes);
+        let Some(budget) = &self.byte_budget else {
+            return;
+        };
+        let mut window = budget.window();
+        window.used += bytes;
+        if window.used >= budget.limit && !window.exhausted {
+            window.exhausted = true;
+            self.budget_exhausted.inc();
+            warn!(
+                "Byte budget of {} bytes per minute is used up, pausing pings until the next minute",
+                budget.limit
+            );
+        }
+    }
+
     pub fn record_http_ping(&self, response: &http_pinger::PingResponse) {
+        self.record_bytes(response.bytes);
         let label = HttpPingLabel::from(response.clone());
+        let latency = match &response.result {
+            http_pinger::PingResult::Success { total_time, .. } => Some(*total_time),
+            http_pinger::PingResult::Connected { connect_time, .. } => Some(*connect_time),
+            _ => None,
+        };
+        let target = format!("{} {}", label.method, label.url);
+        self.record_slo("HTTP", &target, latency);
+        self.record_last_ping("HTTP", target, &label.status, latency);
+        self.http_ping_sent_timestamp_seconds
+            .get_or_create(&HttpEndpointLabel {
+                url: label.url.clone(),
+                method: label.method.clone(),
+            })
+            .set(unix_seconds(response.sent_at));
+        for (header, value) in &response.headers {
+            if http_pinger::LABELED_HEADERS.contains(header) {
+                self.http_response_header
+                    .get_or_create(&HttpResponseHeaderLabel {
+                        url: label.url.clone(),
+                        method: label.method.clone(),
+                        header: header.to_string(),
+                        value: value.clone(),
+                    })
+                    .inc();
+            }
+        }
 
         // Record individual ping response time in us
-        if let http_pinger::PingResult::Success { response_time, .. } = &response.result {
+        let measured = match &response.result {
+            http_pinger::PingResult::Success {
+                ttfb,
+                total_time,
+                dns_time,
+                body_bytes,
+                ..
+            } => Some((*total_time, Some(*ttfb), *dns_time, *body_bytes)),
+            // A connect-only ping has no response, its connect time stands for the total
+            http_pinger::PingResult::Connected {
+                connect_time,
+                dns_time,
+                ..
+            } => Some((*connect_time, None, *dns_time, None)),
+            _ => None,
+        };
+        if let Some((total_time, ttfb, dns_time, body_bytes)) = measured {
             self.http_ping_response_time_histogram_us
-                .get_or_create(&label)
-                .observe(response_time.as_micros() as f64);
+                .observe(&label, total_time.as_micros() as f64);
             self.http_ping_response_time_us
                 .get_or_create(&label)
-                .set(response_time.as_micros() as f64);
+                .set(total_time.as_micros() as f64);
+            self.ready.store(true, Ordering::Relaxed);
+            let endpoint = HttpEndpointLabel {
+                url: label.url.clone(),
+                method: label.method.clone(),
+            };
+            if let Some(ttfb) = ttfb {
+                self.http_ttfb_us
+                    .get_or_create(&endpoint)
+                    .observe(ttfb.as_micros() as f64);
+            }
+            if let http_pinger::PingResult::Success {
+                connection_reused: Some(reused),
+                ..
+            } = &response.result
+                && self.http_cold_warm
+            {
+                let family = if *reused {
+                    &self.http_ping_warm_us
+                } else {
+                    &self.http_ping_cold_us
+                };
+                family
+                    .get_or_create(&endpoint)
+                    .observe(total_time.as_micros() as f64);
+            }
+            if let Some(summary) = &self.http_summary {
+                summary.observe(&endpoint, total_time.as_micros() as f64);
+            }
+            if let Some(ewma) = &self.http_ewma {
+                ewma.observe(&endpoint, total_time.as_micros() as f64);
+            }
+            if let Some(dns_time) = dns_time {
+                self.http_resolve_time_us
+                    .get_or_create(&endpoint)
+                    .observe(dns_time.as_micros() as f64);
+            }
+            if let Some(body_bytes) = body_bytes {
+                self.http_body_bytes
+                    .get_or_create(&endpoint)
+                    .observe(body_bytes as f64);
+            }
         } else {
             // Record failure count
             self.http_ping_failure.get_or_create(&label).inc();
+            match &response.result {
+                http_pinger::PingResult::Failure { failure_type, .. } => {
+                    self.http_ping_failure_by_type
+                        .get_or_create(&HttpPingFailureLabel {
+                            url: label.url.clone(),
+                            method: label.method.clone(),
+                            failure_type: *failure_type,
+                        })
+                        .inc();
+                    if *failure_type == FailureType::PinMismatch {
+                        self.tls_pin_mismatch
+                            .get_or_create(&HttpEndpointLabel {
+                                url: label.url.clone(),
+                                method: label.method.clone(),
+                            })
+                            .inc();
+                    }
+                }
+                http_pinger::PingResult::Timeout { cause } => {
+                    self.http_ping_timeout
+                        .get_or_create(&HttpPingTimeoutLabel {
+                            url: label.url.clone(),
+                            method: label.method.clone(),
+                            cause: *cause,
+                        })
+                        .inc();
+                }
+                _ => {}
+            }
             self.http_ping_response_time_us
                 .get_or_create(&label)
                 .set(TIMEOUT_VALUE_US);
@@ -211,27 +1525,540 @@ impl PingMetrics {
     }
 
     pub fn record_tcp_ping(&self, result: &tcp_pinger::TcpPingResult) {
-        let label = TcpPingLabel::from(result.clone());
+        self.record_bytes(result.bytes);
+        let mut label = TcpPingLabel::from(result.clone());
+        if !self.tcp_ip_label {
+            label.ip = None;
+        }
+        if !self.tcp_local_ip_label {
+            label.local_ip = None;
+        }
+        let latency = match &result.response {
+            tcp_pinger::TcpPingResponse::Success {
+                established_time, ..
+            } => Some(*established_time),
+            _ => None,
+        };
+        let endpoint = TcpEndpointLabel {
+            host: label.host.clone(),
+            port: label.port,
+        };
+        self.record_slo("TCP", &endpoint.target(), latency);
+        self.record_last_ping("TCP", endpoint.target(), &label.response, latency);
+        self.tcp_ping_sent_timestamp_seconds
+            .get_or_create(&endpoint)
+            .set(unix_seconds(result.sent_at));
 
         // Record duration if available - convert to us for higher precision
         if let tcp_pinger::TcpPingResponse::Success {
-            established_time, ..
+            established_time,
+            probe_time,
+            ..
         } = &result.response
         {
             self.tcp_ping_response_time_histogram_us
-                .get_or_create(&label)
-                .observe(established_time.as_micros() as f64);
+                .observe(&label, established_time.as_micros() as f64);
             self.tcp_ping_response_time_us
                 .get_or_create(&label)
                 .set(established_time.as_micros() as f64);
+            self.ready.store(true, Ordering::Relaxed);
+            let endpoint = TcpEndpointLabel {
+                host: label.host.clone(),
+                port: label.port,
+            };
+            if let Some(probe_time) = probe_time {
+                self.tcp_probe_time_us
+                    .get_or_create(&endpoint)
+                    .observe(probe_time.as_micros() as f64);
+            }
+            if let Some(summary) = &self.tcp_summary {
+                summary.observe(&endpoint, established_time.as_micros() as f64);
+            }
+            if let Some(ewma) = &self.tcp_ewma {
+                ewma.observe(&endpoint, established_time.as_micros() as f64);
+            }
         } else {
             // Record failure count
             self.tcp_ping_failure.get_or_create(&label).inc();
+            match &result.response {
+                tcp_pinger::TcpPingResponse::Failure { failure_type, .. } => {
+                    self.tcp_ping_failure_by_type
+                        .get_or_create(&TcpPingFailureLabel {
+                            host: label.host.clone(),
+                            port: label.port,
+                            failure_type: *failure_type,
+                        })
+                        .inc();
+                }
+                tcp_pinger::TcpPingResponse::Timeout { cause } => {
+                    self.tcp_ping_timeout
+                        .get_or_create(&TcpPingTimeoutLabel {
+                            host: label.host.clone(),
+                            port: label.port,
+                            cause: *cause,
+                        })
+                        .inc();
+                }
+                _ => {}
+            }
             self.tcp_ping_response_time_us
                 .get_or_create(&label)
                 .set(TIMEOUT_VALUE_US);
         }
     }
+
+    pub fn record_grpc_ping(&self, response: &grpc_pinger::GrpcPingResponse) {
+        self.record_bytes(response.bytes);
+        let label = GrpcPingLabel::from(response);
+        let latency = match &response.result {
+            grpc_pinger::GrpcPingResult::Serving { rtt, .. } => Some(*rtt),
+            _ => None,
+        };
+        let target = grpc_target(&label.url, &label.service);
+        self.record_last_ping("gRPC", target, &label.response, latency);
+        if let grpc_pinger::GrpcPingResult::Serving { rtt, .. } = &response.result {
+            self.grpc_health_response_time_histogram_us
+                .get_or_create(&label)
+                .observe(rtt.as_micros() as f64);
+            self.grpc_health_response_time_us
+                .get_or_create(&label)
+                .set(rtt.as_micros() as f64);
+            self.ready.store(true, Ordering::Relaxed);
+        } else {
+            self.grpc_health_failure.get_or_create(&label).inc();
+            self.grpc_health_response_time_us
+                .get_or_create(&label)
+                .set(TIMEOUT_VALUE_US);
+        }
+    }
+
+    pub fn record_ws_ping(&self, response: &ws_pinger::WsPingResponse) {
+        self.record_bytes(response.bytes);
+        let label = WsPingLabel::from(response);
+        let latency = match &response.result {
+            ws_pinger::WsPingResult::Connected { handshake_time, .. } => Some(*handshake_time),
+            _ => None,
+        };
+        self.record_last_ping("WebSocket", label.url.clone(), &label.response, latency);
+        match &response.result {
+            ws_pinger::WsPingResult::Connected {
+                handshake_time,
+                pong_time,
+            } => {
+                self.ws_ping_response_time_histogram_us
+                    .get_or_create(&label)
+                    .observe(handshake_time.as_micros() as f64);
+                self.ws_ping_response_time_us
+                    .get_or_create(&label)
+                    .set(handshake_time.as_micros() as f64);
+                if let Some(pong_time) = pong_time {
+                    self.ws_pong_time_us
+                        .get_or_create(&label)
+                        .set(pong_time.as_micros() as f64);
+                }
+                self.ready.store(true, Ordering::Relaxed);
+            }
+            result => {
+                if let Some(stage) = result.failure_stage() {
+                    self.ws_ping_failure
+                        .get_or_create(&WsPingFailureLabel {
+                            url: label.url.clone(),
+                            stage,
+                        })
+                        .inc();
+                }
+                self.ws_ping_response_time_us
+                    .get_or_create(&label)
+                    .set(TIMEOUT_VALUE_US);
+            }
+        }
+    }
+
+    pub fn record_dns_query(&self, response: &dns_pinger::DnsPingResponse) {
+        self.record_bytes(response.bytes);
+        let label = DnsQueryLabel::from(response);
+        let latency = match &response.result {
+            dns_pinger::DnsPingResult::Answered { query_time, .. } => Some(*query_time),
+            _ => None,
+        };
+        let target = format!("{} {}", label.query_name, label.record_type);
+        self.record_last_ping("DNS", target, &label.response, latency);
+        let rcode = match &response.result {
+            dns_pinger::DnsPingResult::Answered { query_time, .. } => {
+                self.dns_query_time_histogram_us
+                    .get_or_create(&label)
+                    .observe(query_time.as_micros() as f64);
+                self.dns_query_time_us
+                    .get_or_create(&label)
+                    .set(query_time.as_micros() as f64);
+                self.ready.store(true, Ordering::Relaxed);
+                return;
+            }
+            dns_pinger::DnsPingResult::Failure {
+                rcode: Some(rcode), ..
+            } => format!("{:?}", rcode),
+            dns_pinger::DnsPingResult::Failure { rcode: None, .. } => "Error".to_string(),
+            dns_pinger::DnsPingResult::Timeout => "Timeout".to_string(),
+        };
+        self.dns_query_failure
+            .get_or_create(&DnsQueryFailureLabel {
+                query_name: label.query_name.clone(),
+                record_type: label.record_type.clone(),
+                rcode,
+            })
+            .inc();
+        self.dns_query_time_us
+            .get_or_create(&label)
+            .set(TIMEOUT_VALUE_US);
+    }
+
+    pub fn record_ntp_ping(&self, response: &ntp_pinger::NtpPingResponse) {
+        self.record_bytes(response.bytes);
+        let label = NtpPingLabel::from(response);
+        let server = NtpServerLabel {
+            server: label.server.clone(),
+            port: label.port,
+        };
+        let latency = match &response.result {
+            ntp_pinger::NtpPingResult::Synced { rtt, .. } => Some(*rtt),
+            _ => None,
+        };
+        let target = ntp_pinger::ntp_target(&response.server, response.port);
+        self.record_last_ping("NTP", target, &label.response, latency);
+        match &response.result {
+            ntp_pinger::NtpPingResult::Synced {
+                offset,
+                rtt,
+                stratum,
+            } => {
+                self.ntp_rtt_histogram_us
+                    .get_or_create(&label)
+                    .observe(rtt.as_micros() as f64);
+                self.ntp_rtt_us
+                    .get_or_create(&label)
+                    .set(rtt.as_micros() as f64);
+                self.ntp_offset_seconds.get_or_create(&server).set(*offset);
+                self.ntp_stratum
+                    .get_or_create(&server)
+                    .set((*stratum).into());
+                self.ready.store(true, Ordering::Relaxed);
+            }
+            _ => {
+                self.ntp_ping_failure.get_or_create(&label).inc();
+                self.ntp_rtt_us.get_or_create(&label).set(TIMEOUT_VALUE_US);
+            }
+        }
+    }
+
+    pub fn record_http_backoff(&self, endpoint: &HttpEndpointLabel, multiplier: u32) {
+        self.http_ping_backoff_multiplier
+            .get_or_create(endpoint)
+            .set(multiplier.into());
+    }
+
+    pub fn record_tcp_backoff(&self, endpoint: &TcpEndpointLabel, multiplier: u32) {
+        self.tcp_ping_backoff_multiplier
+            .get_or_create(endpoint)
+            .set(multiplier.into());
+    }
+
+    pub fn record_http_circuit(&self, endpoint: &HttpEndpointLabel, open: bool) {
+        self.http_circuit_open
+            .get_or_create(endpoint)
+            .set(open.into());
+    }
+
+    pub fn record_tcp_circuit(&self, endpoint: &TcpEndpointLabel, open: bool) {
+        self.tcp_circuit_open
+            .get_or_create(endpoint)
+            .set(open.into());
+    }
+
+    /// Count a tick of the `kind` task of `target` in which no attempt succeeded
+    pub fn record_cycle_failed(&self, kind: &str, target: &str) {
+        self.ping_cycle_failed
+            .get_or_create(&TaskLabel {
+                kind: kind.to_string(),
+                target: target.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn record_http_attempts(&self, endpoint: &HttpEndpointLabel, attempts: u8) {
+        self.http_ping_attempts
+            .get_or_create(endpoint)
+            .observe(attempts.into());
+    }
+
+    pub fn record_tcp_attempts(&self, endpoint: &TcpEndpointLabel, attempts: u8) {
+        self.tcp_ping_attempts
+            .get_or_create(endpoint)
+            .observe(attempts.into());
+    }
+
+    /// Record the lowest TTL of each record type `lookup` returned for `host`
+    pub fn record_dns_records(&self, host: &str, lookup: &Lookup) {
+        let mut ttls = BTreeMap::new();
+        for record in lookup.record_iter() {
+            ttls.entry(record.record_type())
+                .and_modify(|ttl: &mut u32| *ttl = (*ttl).min(record.ttl()))
+                .or_insert(record.ttl());
+        }
+        for (record_type, ttl) in ttls {
+            let label = DnsRecordLabel {
+                host: host.to_string(),
+                record_type: record_type.to_string(),
+            };
+            self.dns_record_ttl_seconds
+                .get_or_create(&label)
+                .set(ttl.into());
+        }
+    }
+
+    pub fn record_dns_answer_count(&self, host: &str, count: usize) {
+        self.dns_answer_count
+            .get_or_create(&ResolveLabel {
+                host: host.to_string(),
+            })
+            .observe(count as f64);
+    }
+
+    pub fn record_dns_server_query(&self, server: SocketAddr, time: Duration, status: PingStatus) {
+        let server = server.to_string();
+        if status == PingStatus::Success {
+            self.dns_server_query_time_histogram_us
+                .get_or_create(&DnsServerLabel {
+                    server: server.clone(),
+                })
+                .observe(time.as_micros() as f64);
+        }
+        self.dns_server_query
+            .get_or_create(&DnsServerQueryLabel {
+                server,
+                response: status,
+            })
+            .inc();
+    }
+
+    pub fn record_dns_attempts(&self, host: &str, attempts: u8) {
+        self.dns_attempts
+            .get_or_create(&ResolveLabel {
+                host: host.to_string(),
+            })
+            .observe(attempts.into());
+    }
+}
+
+/// Bytes pings may exchange per minute, counted in fixed one-minute windows
+#[derive(Debug)]
+struct ByteBudget {
+    limit: u64,
+    window: Mutex<BudgetWindow>,
+}
+
+#[derive(Debug)]
+struct BudgetWindow {
+    start: Instant,
+    used: u64,
+    /// Set once `used` reached the limit, until the window ends
+    exhausted: bool,
+}
+
+impl ByteBudget {
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn new(limit: u64) -> Self {
+        ByteBudget {
+            limit,
+            window: Mutex::new(BudgetWindow {
+                start: Instant::now(),
+                used: 0,
+                exhausted: false,
+            }),
+        }
+    }
+
+    /// The current window, starting a fresh one once a minute has passed
+    fn window(&self) -> std::sync::MutexGuard<'_, BudgetWindow> {
+        let mut window = self.window.lock().unwrap();
+        if window.start.elapsed() >= Self::WINDOW {
+            *window = BudgetWindow {
+                start: Instant::now(),
+                used: 0,
+                exhausted: false,
+            };
+        }
+        window
+    }
+}
+
+/// Exponentially weighted moving average of the successful ping response times of each
+/// endpoint
+#[derive(Debug)]
+struct ResponseTimeEwma<L> {
+    /// Weight of the newest sample
+    alpha: f64,
+    averages: Mutex<HashMap<L, f64>>,
+    gauge: Family<L, Gauge<f64, AtomicU64>>,
+}
+
+impl<L> ResponseTimeEwma<L>
+where
+    L: Clone + std::fmt::Debug + Hash + Eq + EncodeLabelSet + Send + Sync + 'static,
+{
+    fn register(registry: &mut HelpRegistry, kind: &str, alpha: f64) -> Self {
+        let ewma = Self {
+            alpha,
+            averages: Mutex::default(),
+            gauge: Family::default(),
+        };
+        registry.register(
+            format!("{}_ping_response_time_ewma_us", kind),
+            format!(
+                "{} ping response time in us, exponentially smoothed over successful pings with alpha {}",
+                kind.to_uppercase(),
+                alpha
+            ),
+            ewma.gauge.clone(),
+        );
+        ewma
+    }
+
+    fn observe(&self, label: &L, value: f64) {
+        let mut averages = self.averages.lock().unwrap();
+        // The first sample seeds the average
+        let average = averages
+            .entry(label.clone())
+            .and_modify(|average| *average += self.alpha * (value - *average))
+            .or_insert(value);
+        self.gauge.get_or_create(label).set(*average);
+    }
+
+    fn clear(&self) {
+        self.averages.lock().unwrap().clear();
+        self.gauge.clear();
+    }
+}
+
+/// Response time quantiles over the most recent successful pings of each endpoint
+#[derive(Debug)]
+struct ResponseTimeSummary<L> {
+    window: usize,
+    samples: Mutex<HashMap<L, VecDeque<f64>>>,
+    p50: Family<L, Gauge<f64, AtomicU64>>,
+    p90: Family<L, Gauge<f64, AtomicU64>>,
+    p99: Family<L, Gauge<f64, AtomicU64>>,
+}
+
+impl<L> ResponseTimeSummary<L>
+where
+    L: Clone + std::fmt::Debug + Hash + Eq + EncodeLabelSet + Send + Sync + 'static,
+{
+    fn register(registry: &mut HelpRegistry, kind: &str, window: usize) -> Self {
+        let summary = Self {
+            window,
+            samples: Mutex::default(),
+            p50: Family::default(),
+            p90: Family::default(),
+            p99: Family::default(),
+        };
+        for (quantile, family) in [
+            ("p50", &summary.p50),
+            ("p90", &summary.p90),
+            ("p99", &summary.p99),
+        ] {
+            registry.register(
+                format!("{}_ping_response_time_{}_us", kind, quantile),
+                format!(
+                    "{} {} of the last {} successful ping response times in us",
+                    kind.to_uppercase(),
+                    quantile,
+                    window
+                ),
+                family.clone(),
+            );
+        }
+        summary
+    }
+
+    fn observe(&self, label: &L, value: f64) {
+        let mut samples = self.samples.lock().unwrap();
+        let window = samples.entry(label.clone()).or_default();
+        if window.len() == self.window {
+            window.pop_front();
+        }
+        window.push_back(value);
+
+        let mut sorted: Vec<f64> = window.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        // Nearest-rank quantile
+        let rank = |q: f64| sorted[((q * sorted.len() as f64).ceil() as usize).max(1) - 1];
+        self.p50.get_or_create(label).set(rank(0.50));
+        self.p90.get_or_create(label).set(rank(0.90));
+        self.p99.get_or_create(label).set(rank(0.99));
+    }
+
+    fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+        self.p50.clear();
+        self.p90.clear();
+        self.p99.clear();
+    }
+}
+
+impl From<&grpc_pinger::GrpcPingResponse> for GrpcPingLabel {
+    fn from(response: &grpc_pinger::GrpcPingResponse) -> Self {
+        GrpcPingLabel {
+            url: response.url.to_string(),
+            service: response.service.clone(),
+            response: match response.result {
+                grpc_pinger::GrpcPingResult::Serving { .. } => PingStatus::Success,
+                grpc_pinger::GrpcPingResult::Timeout => PingStatus::Timeout,
+                _ => PingStatus::Failure,
+            },
+        }
+    }
+}
+
+impl From<&ws_pinger::WsPingResponse> for WsPingLabel {
+    fn from(response: &ws_pinger::WsPingResponse) -> Self {
+        WsPingLabel {
+            url: response.url.to_string(),
+            response: match response.result {
+                ws_pinger::WsPingResult::Connected { .. } => PingStatus::Success,
+                ws_pinger::WsPingResult::Timeout => PingStatus::Timeout,
+                _ => PingStatus::Failure,
+            },
+        }
+    }
+}
+
+impl From<&dns_pinger::DnsPingResponse> for DnsQueryLabel {
+    fn from(response: &dns_pinger::DnsPingResponse) -> Self {
+        DnsQueryLabel {
+            query_name: response.query_name.clone(),
+            record_type: response.record_type.to_string(),
+            response: match response.result {
+                dns_pinger::DnsPingResult::Answered { .. } => PingStatus::Success,
+                dns_pinger::DnsPingResult::Timeout => PingStatus::Timeout,
+                _ => PingStatus::Failure,
+            },
+        }
+    }
+}
+
+impl From<&ntp_pinger::NtpPingResponse> for NtpPingLabel {
+    fn from(response: &ntp_pinger::NtpPingResponse) -> Self {
+        NtpPingLabel {
+            server: response.server.clone(),
+            port: response.port.into(),
+            response: match response.result {
+                ntp_pinger::NtpPingResult::Synced { .. } => PingStatus::Success,
+                ntp_pinger::NtpPingResult::Timeout => PingStatus::Timeout,
+                _ => PingStatus::Failure,
+            },
+        }
+    }
 }
 
 impl From<http_pinger::PingResponse> for HttpPingLabel {
@@ -243,13 +2070,19 @@ impl From<http_pinger::PingResponse> for HttpPingLabel {
             ..
         } = response;
         let response = match &result {
-            http_pinger::PingResult::Success { .. } => PingStatus::Success,
-            http_pinger::PingResult::Failure(_) => PingStatus::Failure,
-            http_pinger::PingResult::Timeout => PingStatus::Timeout,
+            http_pinger::PingResult::Success { .. } | http_pinger::PingResult::Connected { .. } => {
+                PingStatus::Success
+            }
+            http_pinger::PingResult::Failure { .. } => PingStatus::Failure,
+            http_pinger::PingResult::AssertionFailure { .. } => PingStatus::AssertionFailure,
+            http_pinger::PingResult::Timeout { .. } => PingStatus::Timeout,
         };
 
         let status_code = match result {
-            http_pinger::PingResult::Success { http_status, .. } => Some(http_status as u32),
+            http_pinger::PingResult::Success { http_status, .. }
+            | http_pinger::PingResult::AssertionFailure { http_status, .. } => {
+                Some(http_status as u32)
+            }
             _ => None,
         };
 
@@ -266,23 +2099,39 @@ impl From<tcp_pinger::TcpPingResult> for TcpPingLabel {
     fn from(result: tcp_pinger::TcpPingResult) -> Self {
         let tcp_pinger::TcpPingResult {
             address: (host, port),
+            zone,
+            resolved_ip,
             response,
             ..
         } = result;
         TcpPingLabel {
-            host: String::from(host.to_str()),
+            host: match zone {
+                Some(zone) => format!("{}%{}", host.to_str(), zone),
+                None => String::from(host.to_str()),
+            },
             port: port.into(),
+            ip: (!resolved_ip.is_unspecified()).then(|| resolved_ip.to_string()),
+            local_ip: match &response {
+                tcp_pinger::TcpPingResponse::Success {
+                    local_addr: Some(local_addr),
+                    ..
+                } => Some(local_addr.ip().to_string()),
+                _ => None,
+            },
             response: match response {
                 tcp_pinger::TcpPingResponse::Success { .. } => PingStatus::Success,
-                tcp_pinger::TcpPingResponse::Failure(_) => PingStatus::Failure,
-                tcp_pinger::TcpPingResponse::Timeout => PingStatus::Timeout,
+                tcp_pinger::TcpPingResponse::Failure { .. } => PingStatus::Failure,
+                tcp_pinger::TcpPingResponse::Timeout { .. } => PingStatus::Timeout,
             },
         }
     }
 }
 
 impl ResolveErrorType {
-    fn new(error: &(dyn std::error::Error + 'static)) -> Self {
+    pub fn new(error: &(dyn std::error::Error + 'static)) -> Self {
+        if error.is::<ResolveDeadline>() {
+            return ResolveErrorType::Timeout;
+        }
         match error.downcast_ref::<ResolveError>() {
             Some(error) => match error.kind() {
                 ResolveErrorKind::Proto(proto_error) => match proto_error.kind() {
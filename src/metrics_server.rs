@@ -1,21 +1,128 @@
-use crate::metric::SharedMetrics;
-use axum::{Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use crate::config::CorsPolicy;
+use crate::metric::{EndpointHealth, SharedMetrics, TaskStatus};
+use crate::reload::Reloader;
+use axum::extract::Query;
+use axum::http::{HeaderValue, header};
+use axum::response::Response;
+use axum::{
+    Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get,
+    routing::post,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::service::TowerToHyperService;
 use prometheus_client::encoding::text::encode;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::net::UnixListener;
 use tokio_util::sync::CancellationToken;
-use tower_http::cors::CorsLayer;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{error, info};
 
-pub fn create_metrics_router(metrics: SharedMetrics) -> Router {
-    Router::new()
-        .route("/metrics", get(metrics_handler))
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Address the metrics server listens on
+#[derive(Debug, Clone)]
+pub enum MetricsBind {
+    Tcp { host: String, port: u16 },
+    Unix(PathBuf),
+}
+
+impl MetricsBind {
+    /// Parse one `--bind` address, where `unix:/path/to.sock` selects a Unix domain socket
+    pub fn new(bind: &str, port: u16) -> Self {
+        match bind.strip_prefix("unix:") {
+            Some(path) => MetricsBind::Unix(PathBuf::from(path)),
+            None => MetricsBind::Tcp {
+                host: bind.to_string(),
+                port,
+            },
+        }
+    }
+
+    /// Parse a comma-separated `--bind` list, TCP addresses sharing `port`
+    pub fn parse_list(binds: &str, port: u16) -> Vec<Self> {
+        binds
+            .split(',')
+            .map(str::trim)
+            .filter(|bind| !bind.is_empty())
+            .map(|bind| Self::new(bind, port))
+            .collect()
+    }
+}
+
+impl Display for MetricsBind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // IPv6 literals need brackets to be told apart from the port
+            MetricsBind::Tcp { host, port } if host.contains(':') => {
+                write!(f, "http://[{}]:{}", host, port)
+            }
+            MetricsBind::Tcp { host, port } => write!(f, "http://{}:{}", host, port),
+            MetricsBind::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub fn create_metrics_router(
+    metrics: SharedMetrics,
+    cors: &CorsPolicy,
+    allow_reset: bool,
+    reload: Option<Reloader>,
+    debug: bool,
+) -> Result<Router, Box<dyn std::error::Error + Send + Sync>> {
+    let mut router = Router::new()
+        // Only the exposition output is large enough to benefit from compression
+        .route(
+            "/metrics",
+            get(metrics_handler).layer(CompressionLayer::new().gzip(true)),
+        )
         .route("/health", get(health_handler))
-        .layer(CorsLayer::permissive())
-        .with_state(metrics)
+        .route("/livez", get(livez_handler))
+        .route("/readyz", get(readyz_handler));
+    if allow_reset {
+        router = router.route("/metrics/reset", post(reset_handler));
+    }
+    if let Some(reloader) = reload {
+        router = router.route("/reload", post(reload_handler).with_state(reloader));
+    }
+    if debug {
+        router = router.route("/debug/tasks", get(tasks_handler));
+    }
+
+    let router = match cors {
+        CorsPolicy::Disabled => router,
+        CorsPolicy::Permissive => router.layer(CorsLayer::permissive()),
+        CorsPolicy::Origins(origins) => {
+            let origins = origins
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin))
+                .collect::<Result<Vec<_>, _>>()?;
+            router.layer(CorsLayer::new().allow_origin(AllowOrigin::list(origins)))
+        }
+    };
+    Ok(router.with_state(metrics))
 }
 
 async fn metrics_handler(State(metrics): State<SharedMetrics>) -> impl IntoResponse {
     let mut buffer = String::new();
 
-    match encode(&mut buffer, &metrics.registry) {
+    let begin = Instant::now();
+    let encoded = encode(&mut buffer, &metrics.registry);
+    metrics
+        .metrics_scrape_duration_seconds
+        .observe(begin.elapsed().as_secs_f64());
+    match encoded {
+        // OpenMetrics is the only exposition format carrying the exemplars
+        Ok(_) if metrics.has_exemplars() => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)],
+            buffer,
+        )
+            .into_response(),
         Ok(_) => (StatusCode::OK, buffer).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -25,30 +132,213 @@ async fn metrics_handler(State(metrics): State<SharedMetrics>) -> impl IntoRespo
     }
 }
 
-async fn health_handler() -> impl IntoResponse {
+async fn reset_handler(State(metrics): State<SharedMetrics>) -> impl IntoResponse {
+    metrics.reset();
+    (StatusCode::OK, "{\"status\": \"reset\"}")
+}
+
+#[derive(Debug, Serialize)]
+struct ReloadError {
+    error: String,
+}
+
+async fn reload_handler(State(reloader): State<Reloader>) -> Response {
+    let config = match reloader.load().await {
+        Ok(config) => config,
+        Err(e) => {
+            let error = format!("{:#}", e);
+            return (StatusCode::BAD_REQUEST, Json(ReloadError { error })).into_response();
+        }
+    };
+    match reloader.apply(config).await {
+        Some(Ok(summary)) => Json(summary).into_response(),
+        Some(Err(error)) => (StatusCode::BAD_REQUEST, Json(ReloadError { error })).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "{\"status\": \"not running\"}",
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DebugTasks {
+    tasks: Vec<TaskStatus>,
+}
+
+async fn tasks_handler(State(metrics): State<SharedMetrics>) -> Json<DebugTasks> {
+    Json(DebugTasks {
+        tasks: metrics.task_states(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct HealthQuery {
+    #[serde(default)]
+    detailed: bool,
+}
+
+/// Detailed health of every pinged endpoint, `degraded` when any latest ping did not succeed
+#[derive(Debug, Serialize)]
+struct DetailedHealth {
+    status: &'static str,
+    endpoints: Vec<EndpointHealth>,
+}
+
+async fn health_handler(
+    State(metrics): State<SharedMetrics>,
+    Query(query): Query<HealthQuery>,
+) -> Response {
+    if !query.detailed {
+        return livez_handler().await.into_response();
+    }
+    let endpoints = metrics.endpoint_health();
+    let status = match endpoints
+        .iter()
+        .all(|endpoint| endpoint.status == "success")
+    {
+        true => "ok",
+        false => "degraded",
+    };
+    Json(DetailedHealth { status, endpoints }).into_response()
+}
+
+async fn livez_handler() -> impl IntoResponse {
     (StatusCode::OK, "{\"status\": \"ok\"}")
 }
 
+async fn readyz_handler(State(metrics): State<SharedMetrics>) -> impl IntoResponse {
+    if metrics.is_ready() {
+        (StatusCode::OK, "{\"status\": \"ready\"}")
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "{\"status\": \"no successful ping yet\"}",
+        )
+    }
+}
+
+/// Serve the metrics router on every address in `binds` until cancelled
 pub async fn start_metrics_server(
     metrics: SharedMetrics,
-    host: String,
-    port: u16,
+    binds: Vec<MetricsBind>,
+    cors: CorsPolicy,
+    allow_reset: bool,
+    reload: Option<Reloader>,
+    debug: bool,
     cancel: CancellationToken,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let app = create_metrics_router(metrics);
+    let app = create_metrics_router(metrics, &cors, allow_reset, reload, debug)?;
+
+    let listeners: Vec<_> = binds
+        .into_iter()
+        .map(|bind| tokio::spawn(serve(bind, app.clone(), cancel.clone())))
+        .collect();
+    for listener in listeners {
+        listener.await??;
+    }
+    Ok(())
+}
 
-    let bind_address = format!("{}:{}", host, port);
-    let listener = tokio::net::TcpListener::bind(&bind_address).await?;
+/// Serve the router on one address until cancelled
+async fn serve(
+    bind: MetricsBind,
+    app: Router,
+    cancel: CancellationToken,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    info!("Metrics server starting on {}", bind);
+    info!("Metrics available at: {}/metrics", bind);
+    info!(
+        "Health check available at: {}/livez and {}/readyz",
+        bind, bind
+    );
 
-    println!("Metrics server starting on http://{}", bind_address);
-    println!("Metrics available at: http://{}:{}/metrics", host, port);
-    println!("Health check available at: http://{}:{}/health", host, port);
+    match bind {
+        MetricsBind::Tcp { host, port } => {
+            let listener = tokio::net::TcpListener::bind((host.as_str(), port)).await?;
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move {
-            cancel.cancelled().await;
-        })
-        .await?;
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async move {
+                    cancel.cancelled().await;
+                })
+                .await?;
+        }
+        MetricsBind::Unix(path) => serve_unix(&path, app, cancel).await?,
+    }
 
     Ok(())
 }
+
+/// Serve the router on a Unix domain socket until cancelled, removing the socket file afterwards
+async fn serve_unix(
+    path: &Path,
+    app: Router,
+    cancel: CancellationToken,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Clean up a stale socket left by a previous run, but never clobber a regular file
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(format!("{} exists and is not a socket", path.display()).into());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+
+    loop {
+        let stream = tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    error!("Failed to accept metrics connection: {}", e);
+                    continue;
+                }
+            },
+        };
+
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                error!("Metrics connection error: {}", e);
+            }
+        });
+    }
+
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PingerConfig;
+    use crate::metric::PingMetrics;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn content_type(config: &str) -> String {
+        let config: PingerConfig = serde_json::from_str(config).unwrap();
+        let metrics = Arc::new(PingMetrics::new(&config));
+        let router =
+            create_metrics_router(metrics, &CorsPolicy::Disabled, false, None, false).unwrap();
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn openmetrics_is_only_served_with_exemplars() {
+        assert!(content_type("{}").await.starts_with("text/plain"));
+        let otel = r#"{"otel": {"endpoint": "http://localhost:4318/v1/traces"}}"#;
+        assert_eq!(content_type(otel).await, OPENMETRICS_CONTENT_TYPE);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::NtpPingerEntry;
+use crate::metric::ByteTally;
+use crate::resolver::{Resolve, ResolveDeadline, resolve_str};
+use anyhow::Result;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tracing::{Instrument, info_span, instrument};
+
+/// Seconds from the NTP era 0 epoch, 1900-01-01, to the Unix epoch
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const PACKET_LEN: usize = 48;
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct NtpPingResponse {
+    pub server: String,
+    pub port: u16,
+    pub send_time: Instant,
+    /// 1-based retry attempt that produced this response
+    pub attempt: u8,
+    pub result: NtpPingResult,
+    /// UDP payload bytes of the request and the answers received, 0 when nothing was sent
+    pub bytes: u64,
+}
+
+impl NtpPingResponse {
+    pub fn is_success(&self) -> bool {
+        matches!(self.result, NtpPingResult::Synced { .. })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum NtpPingResult {
+    /// The server answered; `offset` is how far the server clock is ahead of the local one
+    /// in seconds and `rtt` the round-trip delay without the server's processing time
+    Synced {
+        offset: f64,
+        rtt: Duration,
+        stratum: u8,
+    },
+    /// No usable answer, e.g. a failed lookup, a kiss-o'-death or an unsynchronized server
+    Failure {
+        reason: String,
+    },
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct NtpPinger {
+    server: String,
+    port: u16,
+    timeout: Duration,
+    resolver: Arc<dyn Resolve>,
+}
+
+impl NtpPinger {
+    pub fn new(entry: NtpPingerEntry, timeout: Duration, resolver: Arc<dyn Resolve>) -> Self {
+        NtpPinger {
+            server: entry.server.trim().to_string(),
+            port: entry.port,
+            timeout,
+            resolver,
+        }
+    }
+
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// `server:port`, IPv6 addresses in brackets
+    pub fn target(&self) -> String {
+        ntp_target(&self.server, self.port)
+    }
+
+    fn response(&self, begin: Instant, result: NtpPingResult) -> NtpPingResponse {
+        NtpPingResponse {
+            server: self.server.clone(),
+            port: self.port,
+            send_time: begin,
+            attempt: 1,
+            result,
+            bytes: 0,
+        }
+    }
+
+    fn wrap_err<E: std::fmt::Display>(&self, e: E, begin: Instant) -> NtpPingResponse {
+        let reason = e.to_string();
+        self.response(begin, NtpPingResult::Failure { reason })
+    }
+
+    /// Timeout response for a tick whose attempts ran past the total deadline
+    pub fn deadline_exceeded(&self, begin: Instant) -> NtpPingResponse {
+        self.response(begin, NtpPingResult::Timeout)
+    }
+
+    async fn resolve(&self, deadline: Instant) -> Result<IpAddr> {
+        match self.server.parse::<IpAddr>() {
+            Ok(ip) => Ok(ip),
+            Err(_) => resolve_str(self.resolver.as_ref(), &self.server, deadline).await,
+        }
+    }
+
+    #[instrument(fields(server = %self.server, port = self.port), skip(self, bytes))]
+    async fn ping_inner(&self, bytes: &ByteTally) -> Result<NtpPingResponse> {
+        let begin = Instant::now();
+        let ip = match self.resolve(begin + self.timeout).await {
+            Ok(ip) => ip,
+            Err(e) if e.is::<ResolveDeadline>() => {
+                return Ok(self.response(begin, NtpPingResult::Timeout));
+            }
+            Err(e) => return Ok(self.wrap_err(e, begin)),
+        };
+        let addr = SocketAddr::new(ip, self.port);
+        match self
+            .exchange(addr, bytes)
+            .instrument(info_span!("sntp", %addr))
+            .await
+        {
+            Ok(result) => Ok(self.response(begin, result)),
+            Err(e) => Ok(self.wrap_err(e, begin)),
+        }
+    }
+
+    /// Send one SNTP client request to `addr` and compute offset and delay from the answer,
+    /// counting the datagrams sent and received in `bytes`
+    async fn exchange(&self, addr: SocketAddr, bytes: &ByteTally) -> Result<NtpPingResult> {
+        let bind: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(addr).await?;
+
+        let mut request = [0u8; PACKET_LEN];
+        // LI 0, version 4, mode 3 (client)
+        request[0] = 0x23;
+        let sent = Instant::now();
+        let t1 = ntp_timestamp(SystemTime::now());
+        request[40..48].copy_from_slice(&t1.to_be_bytes());
+        socket.send(&request).await?;
+        bytes.add(PACKET_LEN as u64);
+
+        let mut packet = [0u8; 1024];
+        loop {
+            let len = socket.recv(&mut packet).await?;
+            bytes.add(len as u64);
+            // The receive time is derived from the monotonic clock, so that a clock step
+            // during the exchange doesn't skew the delay
+            let t4 = t1.wrapping_add(ntp_duration(sent.elapsed()));
+            // Late answers to earlier requests are told apart by the echoed transmit time
+            if len < PACKET_LEN || read_timestamp(&packet, 24) != t1 {
+                continue;
+            }
+            let mode = packet[0] & 0x07;
+            if mode != 4 {
+                anyhow::bail!("Unexpected NTP mode {} in answer", mode);
+            }
+            let stratum = packet[1];
+            if stratum == 0 {
+                let code = String::from_utf8_lossy(&packet[12..16]).into_owned();
+                anyhow::bail!("Kiss-o'-death from server: {}", code.trim_end_matches('\0'));
+            }
+            if packet[0] >> 6 == 3 {
+                anyhow::bail!("Server clock is not synchronized");
+            }
+            let t2 = read_timestamp(&packet, 32);
+            let t3 = read_timestamp(&packet, 40);
+            // Differences are taken modulo 2^64, which also holds across an NTP era change
+            let diff = |a: u64, b: u64| i128::from(a.wrapping_sub(b) as i64);
+            let offset = (diff(t2, t1) + diff(t3, t4)) as f64 / 2.0 / 2f64.powi(32);
+            let delay = (diff(t4, t1) - diff(t3, t2)).max(0) as f64 / 2f64.powi(32);
+            return Ok(NtpPingResult::Synced {
+                offset,
+                rtt: Duration::from_secs_f64(delay),
+                stratum,
+            });
+        }
+    }
+
+    pub async fn ping(&self) -> Result<NtpPingResponse> {
+        let begin = Instant::now();
+        let bytes = ByteTally::default();
+        let response = match tokio::time::timeout(self.timeout, self.ping_inner(&bytes)).await {
+            Ok(result) => result?,
+            Err(_) => self.response(begin, NtpPingResult::Timeout),
+        };
+        // Responses are built before knowing what was sent, so every outcome takes the tally
+        Ok(NtpPingResponse {
+            bytes: bytes.total(),
+            ..response
+        })
+    }
+}
+
+/// `server:port`, IPv6 addresses in brackets
+pub fn ntp_target(server: &str, port: u16) -> String {
+    match server.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, port),
+        _ => format!("{}:{}", server, port),
+    }
+}
+
+/// 32.32 fixed point NTP timestamp of `time`
+fn ntp_timestamp(time: SystemTime) -> u64 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() + NTP_UNIX_OFFSET;
+    (seconds << 32).wrapping_add(ntp_duration(Duration::from_nanos(
+        since_epoch.subsec_nanos().into(),
+    )))
+}
+
+/// `duration` in 32.32 fixed point seconds
+fn ntp_duration(duration: Duration) -> u64 {
+    ((duration.as_nanos() << 32) / 1_000_000_000) as u64
+}
+
+fn read_timestamp(packet: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(packet[at..at + 8].try_into().unwrap())
+}
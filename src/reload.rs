@@ -0,0 +1,68 @@
+use crate::config::{ConfigFormat, PingerConfig};
+use anyhow::Result;
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+
+/// Outcome of a reload, with entries named `<kind> <target>`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadSummary {
+    /// Entries whose task was started, including changed ones
+    pub added: Vec<String>,
+    /// Entries whose task was stopped, including the old version of changed ones
+    pub removed: Vec<String>,
+    /// Entries whose pinger could not be created
+    pub failed: usize,
+}
+
+/// A validated config handed to the run for its entries to be applied
+pub struct ReloadRequest {
+    pub config: PingerConfig,
+    pub reply: oneshot::Sender<Result<ReloadSummary, String>>,
+}
+
+/// Receiving end of a [`Reloader`], passed to [`crate::run_with`] through
+/// [`crate::RunOptions::reload`]
+pub type ReloadReceiver = mpsc::Receiver<ReloadRequest>;
+
+/// Re-reads the config files a run was started with and applies their entries to it
+#[derive(Debug, Clone)]
+pub struct Reloader {
+    config_paths: Vec<String>,
+    format: Option<ConfigFormat>,
+    requests: mpsc::Sender<ReloadRequest>,
+}
+
+impl Reloader {
+    pub fn new(config_paths: Vec<String>, format: Option<ConfigFormat>) -> (Self, ReloadReceiver) {
+        // Reloads are applied one at a time, a second one waits for the first
+        let (requests, receiver) = mpsc::channel(1);
+        let reloader = Reloader {
+            config_paths,
+            format,
+            requests,
+        };
+        (reloader, receiver)
+    }
+
+    /// Read and validate the config files again
+    pub async fn load(&self) -> Result<PingerConfig> {
+        if self.config_paths.iter().any(|path| path == "-") {
+            return Err(anyhow::anyhow!(
+                "a config read from stdin cannot be reloaded"
+            ));
+        }
+        let (mut config, _) = crate::load_config(&self.config_paths, self.format).await?;
+        crate::validate_config(&mut config)?;
+        Ok(config)
+    }
+
+    /// Hand `config` to the run, `None` once the run no longer takes reloads
+    pub async fn apply(&self, config: PingerConfig) -> Option<Result<ReloadSummary, String>> {
+        let (reply, result) = oneshot::channel();
+        self.requests
+            .send(ReloadRequest { config, reply })
+            .await
+            .ok()?;
+        result.await.ok()
+    }
+}
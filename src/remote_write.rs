@@ -0,0 +1,232 @@
+use crate::config::RemoteWriteConfig;
+use crate::metric::SharedMetrics;
+use anyhow::{Context, Result};
+use prometheus_client::encoding::text::encode;
+use prost::Message;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// `prometheus.WriteRequest` from the remote-write 1.0 protocol
+#[derive(Clone, PartialEq, Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Sample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+/// Spawn a task pushing a snapshot of every metric family to `config.url` each interval
+pub fn spawn(
+    config: RemoteWriteConfig,
+    metrics: SharedMetrics,
+    cancel: CancellationToken,
+) -> Result<JoinHandle<()>> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        headers.insert(
+            HeaderName::try_from(name).with_context(|| format!("Invalid header name {}", name))?,
+            HeaderValue::try_from(value)
+                .with_context(|| format!("Invalid value for header {}", name))?,
+        );
+    }
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-protobuf"),
+    );
+    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("snappy"));
+    headers.insert(
+        "X-Prometheus-Remote-Write-Version",
+        HeaderValue::from_static("0.1.0"),
+    );
+    // A hanging receiver must not hold up the pushes after it
+    let interval = Duration::from_millis(config.interval_millis);
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .timeout(interval)
+        .build()?;
+
+    let task = tokio::spawn(async move {
+        let mut tick = tokio::time::interval(interval);
+        // Pushes missed while the receiver was slow are dropped instead of sent in a burst
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tick.tick() => {
+                    if let Err(e) = push(&client, &config.url, &metrics).await {
+                        warn!("Remote write to {} failed: {:#}", config.url, e);
+                    }
+                }
+            }
+        }
+    });
+    Ok(task)
+}
+
+async fn push(client: &reqwest::Client, url: &str, metrics: &SharedMetrics) -> Result<()> {
+    let mut text = String::new();
+    encode(&mut text, &metrics.registry)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+    let request = WriteRequest {
+        timeseries: text
+            .lines()
+            .filter_map(parse_sample)
+            .map(|(labels, value)| TimeSeries {
+                labels,
+                samples: vec![Sample { value, timestamp }],
+            })
+            .collect(),
+    };
+    let series = request.timeseries.len();
+    let body = snap::raw::Encoder::new().compress_vec(&request.encode_to_vec())?;
+
+    let response = client.post(url).body(body).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let reason = response.text().await.unwrap_or_default();
+        anyhow::bail!("{}: {}", status, reason.trim());
+    }
+    debug!("Remote write pushed {} series to {}", series, url);
+    Ok(())
+}
+
+/// Parse one text exposition sample line into its sorted labels, `__name__` included, and value
+fn parse_sample(line: &str) -> Option<(Vec<Label>, f64)> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let name_end = line.find(['{', ' '])?;
+    let mut labels = vec![Label {
+        name: "__name__".into(),
+        value: line[..name_end].into(),
+    }];
+
+    let mut rest = &line[name_end..];
+    if let Some(mut set) = rest.strip_prefix('{') {
+        loop {
+            set = set.trim_start_matches(',');
+            if let Some(after) = set.strip_prefix('}') {
+                rest = after;
+                break;
+            }
+            let (name, after) = set.split_once("=\"")?;
+            let mut value = String::new();
+            let mut chars = after.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (_, '\\') => match chars.next()?.1 {
+                        'n' => value.push('\n'),
+                        c => value.push(c),
+                    },
+                    (i, '"') => break i,
+                    (_, c) => value.push(c),
+                }
+            };
+            // Prometheus treats an empty label value as an absent label
+            if !value.is_empty() {
+                labels.push(Label {
+                    name: name.into(),
+                    value,
+                });
+            }
+            set = &after[end + 1..];
+        }
+    }
+    let value = rest.split_whitespace().next()?.parse().ok()?;
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+    Some((labels, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(sample: &(Vec<Label>, f64)) -> Vec<(&str, &str)> {
+        sample
+            .0
+            .iter()
+            .map(|label| (label.name.as_str(), label.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        assert!(parse_sample("").is_none());
+        assert!(parse_sample("# HELP ping_skipped Ticks skipped.").is_none());
+        assert!(parse_sample("# TYPE ping_skipped counter").is_none());
+        assert!(parse_sample("# EOF").is_none());
+    }
+
+    #[test]
+    fn samples_without_labels_keep_their_name() {
+        let sample = parse_sample("ping_skipped_total 3").unwrap();
+        assert_eq!(labels(&sample), [("__name__", "ping_skipped_total")]);
+        assert_eq!(sample.1, 3.0);
+
+        let sample = parse_sample("ping_skipped_total{} 4").unwrap();
+        assert_eq!(labels(&sample), [("__name__", "ping_skipped_total")]);
+        assert_eq!(sample.1, 4.0);
+    }
+
+    #[test]
+    fn escaped_label_values_are_unescaped_and_labels_sorted() {
+        let sample =
+            parse_sample(r#"http_ping{url="http://a/?q=\"x\",y",method="GET",note="a\nb\\"} 1.5"#)
+                .unwrap();
+        assert_eq!(
+            labels(&sample),
+            [
+                ("__name__", "http_ping"),
+                ("method", "GET"),
+                ("note", "a\nb\\"),
+                ("url", "http://a/?q=\"x\",y"),
+            ]
+        );
+        assert_eq!(sample.1, 1.5);
+    }
+
+    #[test]
+    fn empty_label_values_are_dropped() {
+        let sample = parse_sample(r#"tcp_ping{host="a",ip=""} 2"#).unwrap();
+        assert_eq!(labels(&sample), [("__name__", "tcp_ping"), ("host", "a")]);
+    }
+
+    #[test]
+    fn exemplars_after_the_value_are_ignored() {
+        let sample = parse_sample(
+            r#"http_ping_bucket{le="100.0"} 7 # {trace_id="4bf92f"} 87.0 1700000000.5"#,
+        )
+        .unwrap();
+        assert_eq!(
+            labels(&sample),
+            [("__name__", "http_ping_bucket"), ("le", "100.0")]
+        );
+        assert_eq!(sample.1, 7.0);
+    }
+}
@@ -1,42 +1,243 @@
 mod hickory_wrapper;
+mod resolver_pool;
 mod timed_resolver;
 
-use crate::config::PingerConfig;
+use crate::config::{PingerConfig, default_dns_timeout_millis};
 use crate::metric::SharedMetrics;
-use hickory_wrapper::build;
+use hickory_wrapper::{DnsRetry, build};
 use reqwest::dns::Name;
-use std::fmt::Debug;
+use resolver_pool::{PoolMember, ResolverPool};
+use std::fmt::{Debug, Display};
 use std::net::IpAddr;
+use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use timed_resolver::TimedResolver;
 
-pub trait Resolve: reqwest::dns::Resolve + Debug {}
+pub trait Resolve: reqwest::dns::Resolve + Debug {
+    /// Look up the SRV records of `name`
+    fn resolve_srv(&self, name: String) -> SrvResolving;
+
+    /// Note that a lookup of `name` was cut off by the deadline of [`resolve_all`] after
+    /// `elapsed`, before the resolver itself gave up on it
+    fn report_deadline(&self, _name: &str, _elapsed: Duration) {}
+}
+
+/// Target of an SRV record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvTarget {
+    /// Host name, without the trailing dot
+    pub host: String,
+    pub port: u16,
+    pub priority: u16,
+    pub weight: u16,
+}
+
+pub type SrvResolving = Pin<Box<dyn Future<Output = anyhow::Result<Vec<SrvTarget>>> + Send>>;
 
 pub fn build_resolver(
     config: &PingerConfig,
     metric: SharedMetrics,
 ) -> anyhow::Result<Arc<dyn Resolve>> {
-    let hickory = build(
-        if config.measure_dns_stats { 0 } else { 10 },
-        10,
-        Duration::from_millis(config.dns_timeout_millis),
-    )?;
+    let cache_size = config
+        .dns_cache_size
+        .unwrap_or(if config.measure_dns_stats { 0 } else { 10 });
+    let timeout = Duration::from_millis(config.dns_timeout_millis);
+    let retry = (config.dns_retries > 0).then(|| DnsRetry {
+        retries: config.dns_retries,
+        base_delay: Duration::from_millis(config.dns_retry_delay_millis.unwrap_or(50)),
+        timeout,
+        metrics: Arc::clone(&metric),
+    });
+    let build_for = |nameserver| {
+        build(
+            nameserver,
+            cache_size,
+            10,
+            timeout,
+            config.dns_min_ttl_secs.map(Duration::from_secs),
+            config.dns_max_ttl_secs.map(Duration::from_secs),
+            retry.clone(),
+            config.measure_dns_stats.then(|| Arc::clone(&metric)),
+        )
+    };
 
+    let resolver: Arc<dyn Resolve> = match config.nameservers.as_slice() {
+        [] => timed(build_for(None)?, config, &metric),
+        [nameserver] => {
+            let addr = nameserver.socket_addr().map_err(|e| anyhow::anyhow!(e))?;
+            timed(build_for(Some(addr))?, config, &metric)
+        }
+        nameservers => {
+            let mut members = Vec::new();
+            for nameserver in nameservers {
+                let address = nameserver.socket_addr().map_err(|e| anyhow::anyhow!(e))?;
+                members.push(PoolMember {
+                    address,
+                    weight: nameserver.weight,
+                    resolver: build_for(Some(address))?,
+                });
+            }
+            timed(
+                ResolverPool::new(members, timeout, Arc::clone(&metric)),
+                config,
+                &metric,
+            )
+        }
+    };
+    Ok(resolver)
+}
+
+tokio::task_local! {
+    /// Set while a lookup runs on the untimed path
+    static UNTIMED: ();
+}
+
+/// Run `future` on the untimed resolver path, keeping the lookups it makes out of the DNS
+/// metrics, e.g. for warmup pings
+pub(crate) async fn untimed<F: Future>(future: F) -> F::Output {
+    UNTIMED.scope((), future).await
+}
+
+/// Whether the running lookup was started within [`untimed`]
+fn is_untimed() -> bool {
+    UNTIMED.try_with(|_| ()).is_ok()
+}
+
+/// Resolver querying the system name servers with the default settings, for pingers built
+/// without a configuration
+pub fn system_resolver() -> anyhow::Result<Arc<dyn Resolve>> {
+    let timeout = Duration::from_millis(default_dns_timeout_millis());
+    Ok(Arc::new(build(
+        None, 10, 10, timeout, None, None, None, None,
+    )?))
+}
+
+fn timed<R>(resolver: R, config: &PingerConfig, metric: &SharedMetrics) -> Arc<dyn Resolve>
+where
+    R: Resolve + Send + Sync + 'static,
+{
     if config.measure_dns_stats {
-        Ok(Arc::new(TimedResolver::new(hickory, Arc::clone(&metric))))
+        Arc::new(TimedResolver::new(resolver, Arc::clone(metric)))
     } else {
-        Ok(Arc::new(hickory))
+        Arc::new(resolver)
+    }
+}
+
+/// A lookup ran past the deadline given to [`resolve_all`]
+#[derive(Debug)]
+pub struct ResolveDeadline {
+    name: String,
+}
+
+impl Display for ResolveDeadline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DNS lookup of {} ran past its deadline", self.name)
+    }
+}
+
+impl std::error::Error for ResolveDeadline {}
+
+/// Resolve `name` to every address it has, failing when there are none or with a timeout
+/// error once `deadline` passes, whatever timeout the resolver itself applies
+pub async fn resolve_all(
+    resolver: &dyn Resolve,
+    name: &str,
+    deadline: Instant,
+) -> anyhow::Result<Vec<IpAddr>> {
+    let begin = Instant::now();
+    let lookup = resolver.resolve(Name::from_str(name)?);
+    let addrs = match tokio::time::timeout_at(deadline.into(), lookup).await {
+        Ok(addrs) => addrs.map_err(|e| anyhow::anyhow!(e))?,
+        Err(_) => {
+            resolver.report_deadline(name, begin.elapsed());
+            return Err(ResolveDeadline {
+                name: name.to_string(),
+            }
+            .into());
+        }
+    };
+    let ips: Vec<IpAddr> = addrs.map(|sock_addr| sock_addr.ip()).collect();
+    if ips.is_empty() {
+        anyhow::bail!("no dns record for {}", name);
     }
+    Ok(ips)
 }
 
-pub async fn resolve_str(resolver: &dyn Resolve, name: &str) -> anyhow::Result<IpAddr> {
-    let sock_addr = resolver
-        .resolve(Name::from_str(name)?)
-        .await
-        .map_err(|e| anyhow::anyhow!(e))?
-        .next()
-        .ok_or(anyhow::anyhow!("no dns record for {}", name))?;
-    Ok(sock_addr.ip())
+/// Resolve `name` to its first address before `deadline`
+pub async fn resolve_str(
+    resolver: &dyn Resolve,
+    name: &str,
+    deadline: Instant,
+) -> anyhow::Result<IpAddr> {
+    Ok(resolve_all(resolver, name, deadline).await?[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metric::{PingMetrics, ResolveErrorLabel, ResolveErrorType, ResolveLabel};
+
+    #[tokio::test]
+    async fn untimed_lookups_stay_out_of_the_metrics() {
+        let config: PingerConfig = serde_json::from_str("{}").unwrap();
+        let metrics = Arc::new(PingMetrics::new(&config));
+        let timeout = Duration::from_secs(1);
+        let inner = build(None, 0, 10, timeout, None, None, None, None).unwrap();
+        let resolver = TimedResolver::new(inner, Arc::clone(&metrics));
+        let lookups = || {
+            let label = ResolveLabel {
+                host: "localhost".to_string(),
+            };
+            metrics.resolve_total.get_or_create(&label).get()
+        };
+
+        let name = || Name::from_str("localhost").unwrap();
+        let lookup = reqwest::dns::Resolve::resolve(&resolver, name());
+        assert!(untimed(lookup).await.unwrap().next().is_some());
+        assert_eq!(lookups(), 0);
+        let lookup = reqwest::dns::Resolve::resolve(&resolver, name());
+        assert!(lookup.await.unwrap().next().is_some());
+        assert_eq!(lookups(), 1);
+    }
+
+    /// Resolver whose lookups never finish
+    #[derive(Debug)]
+    struct Hanging;
+
+    impl reqwest::dns::Resolve for Hanging {
+        fn resolve(&self, _name: Name) -> reqwest::dns::Resolving {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    impl Resolve for Hanging {
+        fn resolve_srv(&self, _name: String) -> SrvResolving {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn lookups_cut_off_by_the_deadline_count_as_timeouts() {
+        let config: PingerConfig = serde_json::from_str("{}").unwrap();
+        let metrics = Arc::new(PingMetrics::new(&config));
+        let resolver = TimedResolver::new(Hanging, Arc::clone(&metrics));
+
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let error = resolve_all(&resolver, "example.com", deadline)
+            .await
+            .unwrap_err();
+        assert!(error.is::<ResolveDeadline>());
+
+        let label = ResolveLabel {
+            host: "example.com".to_string(),
+        };
+        assert_eq!(metrics.resolve_timeout.get_or_create(&label).get(), 1);
+        let error_label = ResolveErrorLabel {
+            host: "example.com".to_string(),
+            error_type: ResolveErrorType::Timeout,
+        };
+        assert_eq!(metrics.resolve_failure.get_or_create(&error_label).get(), 1);
+    }
 }
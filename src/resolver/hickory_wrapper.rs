@@ -1,15 +1,90 @@
 use crate::Resolve;
-use hickory_resolver::Resolver;
-use hickory_resolver::config::ResolverOpts;
-use hickory_resolver::lookup_ip::LookupIpIntoIter;
+use crate::metric::SharedMetrics;
+use crate::resolver::{SrvResolving, SrvTarget, is_untimed};
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::lookup_ip::{LookupIp, LookupIpIntoIter};
 use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::{ProtoError, ProtoErrorKind};
+use hickory_resolver::{ResolveError, ResolveErrorKind, Resolver};
 use reqwest::dns::Addrs;
 use std::net::SocketAddr;
 use std::time::Duration;
-use tracing::info;
+use tokio::time::Instant;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone)]
-pub struct HickoryWrapper(Resolver<TokioConnectionProvider>);
+pub struct HickoryWrapper {
+    resolver: Resolver<TokioConnectionProvider>,
+    retry: Option<DnsRetry>,
+    /// Reports the records behind every answered lookup, set when DNS is timed
+    record_metrics: Option<SharedMetrics>,
+}
+
+/// Retries of lookups failing transiently, bounded by the DNS timeout
+#[derive(Debug, Clone)]
+pub struct DnsRetry {
+    pub retries: u8,
+    pub base_delay: Duration,
+    pub timeout: Duration,
+    pub metrics: SharedMetrics,
+}
+
+impl DnsRetry {
+    /// Delay before retry `n` (0-based): the base delay doubled `n` times, with its upper half
+    /// randomized so that concurrent lookups do not retry in lockstep
+    fn delay(&self, n: u8) -> Duration {
+        let max = self.base_delay.saturating_mul(1 << n.min(16));
+        let half = max.as_millis() as u64 / 2;
+        Duration::from_millis(half + rand::random_range(0..=half))
+    }
+
+    async fn lookup(
+        &self,
+        resolver: &Resolver<TokioConnectionProvider>,
+        name: &str,
+    ) -> Result<LookupIp, ResolveError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut attempts = 0;
+        let result = loop {
+            attempts += 1;
+            let result = match tokio::time::timeout_at(deadline, resolver.lookup_ip(name)).await {
+                Ok(result) => result,
+                Err(_) => Err(ProtoError::from(ProtoErrorKind::Timeout).into()),
+            };
+            match result {
+                Err(e) if attempts <= self.retries && is_transient(&e) => {
+                    let retry_at = Instant::now() + self.delay(attempts - 1);
+                    if retry_at >= deadline {
+                        break Err(e);
+                    }
+                    warn!("Retrying lookup of {} after: {}", name, e);
+                    tokio::time::sleep_until(retry_at).await;
+                }
+                result => break result,
+            }
+        };
+        if !is_untimed() {
+            self.metrics.record_dns_attempts(name, attempts);
+        }
+        result
+    }
+}
+
+/// Whether a lookup may succeed when retried; definite answers that a name or record does not
+/// exist are final
+pub(super) fn is_transient(error: &ResolveError) -> bool {
+    match error.kind() {
+        ResolveErrorKind::Proto(error) => match error.kind() {
+            ProtoErrorKind::NoRecordsFound { response_code, .. } => !matches!(
+                *response_code,
+                ResponseCode::NXDomain | ResponseCode::NoError
+            ),
+            _ => true,
+        },
+        _ => true,
+    }
+}
 
 struct SocketAddrIter {
     iter: LookupIpIntoIter,
@@ -25,9 +100,28 @@ impl Iterator for SocketAddrIter {
 
 impl reqwest::dns::Resolve for HickoryWrapper {
     fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
-        let resolver = self.0.clone();
+        let resolver = self.resolver.clone();
+        let retry = self.retry.clone();
+        let record_metrics = self.record_metrics.clone();
         Box::pin(async move {
-            let result = resolver.lookup_ip(name.as_str()).await?;
+            let result = match retry {
+                Some(retry) => retry.lookup(&resolver, name.as_str()).await,
+                None => resolver.lookup_ip(name.as_str()).await,
+            };
+            if let Some(metrics) = record_metrics.filter(|_| !is_untimed()) {
+                match &result {
+                    Ok(lookup) => {
+                        metrics.record_dns_records(name.as_str(), lookup.as_lookup());
+                        metrics.record_dns_answer_count(name.as_str(), lookup.iter().count());
+                    }
+                    // NXDOMAIN and NODATA answers fail the lookup, but were still answered
+                    Err(e) if e.is_no_records_found() => {
+                        metrics.record_dns_answer_count(name.as_str(), 0);
+                    }
+                    Err(_) => {}
+                }
+            }
+            let result = result?;
             let iter: Addrs = Box::new(SocketAddrIter {
                 iter: result.into_iter(),
             });
@@ -36,22 +130,59 @@ impl reqwest::dns::Resolve for HickoryWrapper {
     }
 }
 
-impl Resolve for HickoryWrapper {}
+impl Resolve for HickoryWrapper {
+    fn resolve_srv(&self, name: String) -> SrvResolving {
+        let resolver = self.resolver.clone();
+        Box::pin(async move {
+            let lookup = resolver.srv_lookup(name.as_str()).await?;
+            Ok(lookup
+                .iter()
+                .map(|srv| SrvTarget {
+                    host: srv.target().to_utf8().trim_end_matches('.').to_string(),
+                    port: srv.port(),
+                    priority: srv.priority(),
+                    weight: srv.weight(),
+                })
+                .collect())
+        })
+    }
+}
 
+/// Build a resolver querying `nameserver`, or the system name servers when `None`
+#[allow(clippy::too_many_arguments)]
 pub fn build(
+    nameserver: Option<SocketAddr>,
     cache_size: usize,
     num_concurrent_reqs: usize,
     timeout: Duration,
+    min_ttl: Option<Duration>,
+    max_ttl: Option<Duration>,
+    retry: Option<DnsRetry>,
+    record_metrics: Option<SharedMetrics>,
 ) -> anyhow::Result<HickoryWrapper> {
     let mut options = ResolverOpts::default();
     options.cache_size = cache_size;
     options.num_concurrent_reqs = num_concurrent_reqs;
     options.timeout = timeout;
+    options.positive_min_ttl = min_ttl;
+    options.positive_max_ttl = max_ttl;
 
-    let hickory = Resolver::builder(TokioConnectionProvider::default())?
-        .with_options(options)
-        .build();
+    let builder = match nameserver {
+        Some(addr) => {
+            let servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+            Resolver::builder_with_config(
+                ResolverConfig::from_parts(None, vec![], servers),
+                TokioConnectionProvider::default(),
+            )
+        }
+        None => Resolver::builder(TokioConnectionProvider::default())?,
+    };
+    let hickory = builder.with_options(options).build();
 
     info!("Hickory DNS config: {:?}", hickory.config());
-    Ok(HickoryWrapper(hickory))
+    Ok(HickoryWrapper {
+        resolver: hickory,
+        retry,
+        record_metrics,
+    })
 }
@@ -0,0 +1,149 @@
+use super::hickory_wrapper::{HickoryWrapper, is_transient};
+use crate::Resolve;
+use crate::metric::{PingStatus, ResolveErrorType, SharedMetrics};
+use crate::resolver::{SrvResolving, is_untimed};
+use hickory_resolver::ResolveError;
+use hickory_resolver::proto::{ProtoError, ProtoErrorKind};
+use reqwest::dns::Name;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Upstream name server of a [`ResolverPool`]
+#[derive(Debug)]
+pub struct PoolMember {
+    pub address: SocketAddr,
+    /// Share of lookups sent to this server first, 0 for a failover-only server
+    pub weight: u32,
+    pub resolver: HickoryWrapper,
+}
+
+/// Spreads lookups across name servers by smooth weighted round-robin; a server that times out
+/// or fails transiently hands the lookup to the remaining servers in turn
+#[derive(Debug, Clone)]
+pub struct ResolverPool {
+    members: Arc<Vec<PoolMember>>,
+    /// Time each server gets to answer before the next one is tried
+    timeout: Duration,
+    /// Current weight of every member, the highest one is picked next
+    current: Arc<Mutex<Vec<i64>>>,
+    metrics: SharedMetrics,
+}
+
+impl ResolverPool {
+    pub fn new(members: Vec<PoolMember>, timeout: Duration, metrics: SharedMetrics) -> Self {
+        ResolverPool {
+            timeout,
+            current: Arc::new(Mutex::new(vec![0; members.len()])),
+            members: Arc::new(members),
+            metrics,
+        }
+    }
+
+    /// Record a lookup sent to `address`, unless it took the untimed path
+    fn record(&self, address: SocketAddr, time: Duration, status: PingStatus) {
+        if !is_untimed() {
+            self.metrics.record_dns_server_query(address, time, status);
+        }
+    }
+
+    /// Member indices in the order the next lookup tries them
+    fn order(&self) -> Vec<usize> {
+        let mut current = self.current.lock().unwrap();
+        let mut total = 0;
+        for (current, member) in current.iter_mut().zip(self.members.iter()) {
+            *current += i64::from(member.weight);
+            total += i64::from(member.weight);
+        }
+        let first = (0..self.members.len())
+            .filter(|&i| self.members[i].weight > 0)
+            .rev()
+            .max_by_key(|&i| current[i])
+            .unwrap_or(0);
+        current[first] -= total;
+        let mut order = vec![first];
+        order.extend((0..self.members.len()).filter(|&i| i != first));
+        order
+    }
+}
+
+impl reqwest::dns::Resolve for ResolverPool {
+    fn resolve(&self, name: Name) -> reqwest::dns::Resolving {
+        let pool = self.clone();
+        Box::pin(async move {
+            let mut last_error = None;
+            for i in pool.order() {
+                let member = &pool.members[i];
+                let begin = Instant::now();
+                let lookup = reqwest::dns::Resolve::resolve(
+                    &member.resolver,
+                    Name::from_str(name.as_str())?,
+                );
+                let result = match tokio::time::timeout(pool.timeout, lookup).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        Err(ResolveError::from(ProtoError::from(ProtoErrorKind::Timeout)).into())
+                    }
+                };
+                let elapsed = begin.elapsed();
+                let e = match result {
+                    Ok(addrs) => {
+                        pool.record(member.address, elapsed, PingStatus::Success);
+                        return Ok(addrs);
+                    }
+                    Err(e) => e,
+                };
+                // Definite answers that a name does not exist are final and count as healthy
+                let resolve_error = e.downcast_ref::<ResolveError>();
+                if resolve_error.is_some_and(|e| !is_transient(e)) {
+                    pool.record(member.address, elapsed, PingStatus::Success);
+                    return Err(e);
+                }
+                let status = match ResolveErrorType::new(e.as_ref()) {
+                    ResolveErrorType::Timeout => PingStatus::Timeout,
+                    _ => PingStatus::Failure,
+                };
+                pool.record(member.address, elapsed, status);
+                warn!(
+                    "Name server {} failed to resolve {}: {}",
+                    member.address,
+                    name.as_str(),
+                    e
+                );
+                last_error = Some(e);
+            }
+            Err(last_error.expect("a resolver pool has at least one member"))
+        })
+    }
+}
+
+impl Resolve for ResolverPool {
+    fn resolve_srv(&self, name: String) -> SrvResolving {
+        let pool = self.clone();
+        Box::pin(async move {
+            let mut last_error = None;
+            for i in pool.order() {
+                let member = &pool.members[i];
+                let lookup = member.resolver.resolve_srv(name.clone());
+                let e = match tokio::time::timeout(pool.timeout, lookup).await {
+                    Ok(Ok(targets)) => return Ok(targets),
+                    Ok(Err(e)) => e,
+                    Err(_) => ResolveError::from(ProtoError::from(ProtoErrorKind::Timeout)).into(),
+                };
+                if e.downcast_ref::<ResolveError>()
+                    .is_some_and(|e| !is_transient(e))
+                {
+                    return Err(e);
+                }
+                warn!(
+                    "Name server {} failed to look up SRV {}: {}",
+                    member.address, name, e
+                );
+                last_error = Some(e);
+            }
+            Err(last_error.expect("a resolver pool has at least one member"))
+        })
+    }
+}
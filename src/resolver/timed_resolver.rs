@@ -1,9 +1,13 @@
 use crate::Resolve;
 use crate::metric::PingMetrics;
 use crate::metric::ResolveErrorLabel;
+use crate::metric::ResolveErrorType;
 use crate::metric::ResolveLabel;
 use crate::metric::TIMEOUT_VALUE_US;
+use crate::resolver::{ResolveDeadline, SrvResolving, is_untimed};
+use reqwest::dns::Addrs;
 use std::fmt::Debug;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::error;
@@ -15,6 +19,8 @@ pub trait TimeReporter: Debug {
         time: Duration,
         err: Option<&(dyn std::error::Error + 'static)>,
     );
+
+    fn report_addresses(&self, name: String, count: usize);
 }
 
 impl TimeReporter for PingMetrics {
@@ -26,14 +32,17 @@ impl TimeReporter for PingMetrics {
     ) {
         let label = ResolveLabel { host: name };
         let time = time.as_micros() as f64;
+        self.resolve_total.get_or_create(&label).inc();
 
         if let Some(err) = err {
             self.resolve_time_us
                 .get_or_create(&label)
                 .set(TIMEOUT_VALUE_US);
-            self.resolve_failure
-                .get_or_create(&ResolveErrorLabel::new(label, err))
-                .inc();
+            let error_label = ResolveErrorLabel::new(label.clone(), err);
+            if error_label.error_type == ResolveErrorType::Timeout {
+                self.resolve_timeout.get_or_create(&label).inc();
+            }
+            self.resolve_failure.get_or_create(&error_label).inc();
         } else {
             self.resolve_time_histogram_us
                 .get_or_create(&label)
@@ -41,6 +50,12 @@ impl TimeReporter for PingMetrics {
             self.resolve_time_us.get_or_create(&label).set(time);
         }
     }
+
+    fn report_addresses(&self, name: String, count: usize) {
+        self.dns_resolved_addresses
+            .get_or_create(&ResolveLabel { host: name })
+            .set(count as i64);
+    }
 }
 
 #[derive(Debug)]
@@ -64,19 +79,44 @@ impl<R: Resolve + Send + Sync, T: TimeReporter + Send + Sync> reqwest::dns::Reso
         Box::pin(async move {
             let begin = Instant::now();
             let result = fut.await;
-            match &result {
-                Ok(_) => reporter.report_time(str_name, begin.elapsed(), None),
+            if is_untimed() {
+                return result;
+            }
+            let elapsed = begin.elapsed();
+            match result {
+                Ok(addrs) => {
+                    let addrs: Vec<SocketAddr> = addrs.collect();
+                    reporter.report_time(str_name.clone(), elapsed, None);
+                    reporter.report_addresses(str_name, addrs.len());
+                    Ok(Box::new(addrs.into_iter()) as Addrs)
+                }
                 Err(e) => {
                     error!("Failed to resolve {}: {}", str_name, e);
-                    reporter.report_time(str_name, begin.elapsed(), Some(e.as_ref()))
+                    reporter.report_time(str_name, elapsed, Some(e.as_ref()));
+                    Err(e)
                 }
             }
-            result
         })
     }
 }
 
-impl<R: Resolve + Send + Sync, T: TimeReporter + Send + Sync> Resolve for TimedResolver<R, T> {}
+impl<R: Resolve + Send + Sync, T: TimeReporter + Send + Sync> Resolve for TimedResolver<R, T> {
+    fn resolve_srv(&self, name: String) -> SrvResolving {
+        self.resolver.resolve_srv(name)
+    }
+
+    fn report_deadline(&self, name: &str, elapsed: Duration) {
+        if is_untimed() {
+            return;
+        }
+        let deadline = ResolveDeadline {
+            name: name.to_string(),
+        };
+        error!("Failed to resolve {}: {}", name, deadline);
+        self.reporter
+            .report_time(name.to_string(), elapsed, Some(&deadline));
+    }
+}
 
 impl<R, T> TimedResolver<R, T>
 where
@@ -0,0 +1,164 @@
+use crate::dns_pinger::{DnsPingResponse, DnsPingResult};
+use crate::grpc_pinger::{GrpcPingResponse, GrpcPingResult};
+use crate::http_pinger::{PingResponse, PingResult};
+use crate::metric::{HttpEndpointLabel, TcpEndpointLabel};
+use crate::ntp_pinger::{NtpPingResponse, NtpPingResult};
+use crate::tcp_pinger::{TcpPingResponse, TcpPingResult};
+use crate::ws_pinger::{WsPingResponse, WsPingResult};
+use hickory_resolver::proto::rr::{Name, RecordType};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+pub type SummarySender = mpsc::UnboundedSender<Sample>;
+
+/// Outcome of one ping cycle, `latency` is `None` unless it succeeded
+#[derive(Debug)]
+pub struct Sample {
+    target: String,
+    latency: Option<Duration>,
+}
+
+impl Sample {
+    pub fn http(endpoint: &HttpEndpointLabel, response: Option<&PingResponse>) -> Self {
+        let latency = response.and_then(|response| match &response.result {
+            PingResult::Success { total_time, .. } => Some(*total_time),
+            PingResult::Connected { connect_time, .. } => Some(*connect_time),
+            _ => None,
+        });
+        Self {
+            target: format!("HTTP {} {}", endpoint.method, endpoint.url),
+            latency,
+        }
+    }
+
+    pub fn grpc(url: &url::Url, service: &str, response: Option<&GrpcPingResponse>) -> Self {
+        let latency = response.and_then(|response| match &response.result {
+            GrpcPingResult::Serving { rtt, .. } => Some(*rtt),
+            _ => None,
+        });
+        Self {
+            target: grpc_target(url.as_str(), service),
+            latency,
+        }
+    }
+
+    pub fn ws(url: &url::Url, response: Option<&WsPingResponse>) -> Self {
+        let latency = response.and_then(|response| match &response.result {
+            WsPingResult::Connected { handshake_time, .. } => Some(*handshake_time),
+            _ => None,
+        });
+        Self {
+            target: format!("WS {}", url),
+            latency,
+        }
+    }
+
+    pub fn dns(
+        query_name: &Name,
+        record_type: RecordType,
+        response: Option<&DnsPingResponse>,
+    ) -> Self {
+        let latency = response.and_then(|response| match &response.result {
+            DnsPingResult::Answered { query_time, .. } => Some(*query_time),
+            _ => None,
+        });
+        Self {
+            target: format!("DNS {} {}", query_name, record_type),
+            latency,
+        }
+    }
+
+    pub fn ntp(target: &str, response: Option<&NtpPingResponse>) -> Self {
+        let latency = response.and_then(|response| match &response.result {
+            NtpPingResult::Synced { rtt, .. } => Some(*rtt),
+            _ => None,
+        });
+        Self {
+            target: format!("NTP {}", target),
+            latency,
+        }
+    }
+
+    pub fn tcp(endpoint: &TcpEndpointLabel, response: Option<&TcpPingResult>) -> Self {
+        let latency = response.and_then(|response| match &response.response {
+            TcpPingResponse::Success {
+                established_time, ..
+            } => Some(*established_time),
+            _ => None,
+        });
+        Self {
+            target: format!("TCP {}", endpoint.target()),
+            latency,
+        }
+    }
+}
+
+/// Target name of a gRPC health check, the service is omitted when empty
+pub fn grpc_target(url: &str, service: &str) -> String {
+    format!("gRPC {} {}", url, service).trim_end().to_string()
+}
+
+#[derive(Debug, Default)]
+struct TargetStats {
+    sent: u64,
+    ok: u64,
+    min: Option<Duration>,
+    max: Duration,
+    total: Duration,
+}
+
+/// Per-target ping statistics, sorted by target
+#[derive(Debug, Default)]
+pub struct Summary {
+    targets: BTreeMap<String, TargetStats>,
+}
+
+impl Summary {
+    /// Collect samples until every sender is dropped
+    pub fn collect() -> (SummarySender, JoinHandle<Summary>) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Sample>();
+        let task = tokio::spawn(async move {
+            let mut summary = Summary::default();
+            while let Some(sample) = rx.recv().await {
+                summary.record(sample);
+            }
+            summary
+        });
+        (tx, task)
+    }
+
+    fn record(&mut self, sample: Sample) {
+        let stats = self.targets.entry(sample.target).or_default();
+        stats.sent += 1;
+        if let Some(latency) = sample.latency {
+            stats.ok += 1;
+            stats.min = Some(stats.min.map_or(latency, |min| min.min(latency)));
+            stats.max = stats.max.max(latency);
+            stats.total += latency;
+        }
+    }
+
+    /// Render as whitespace separated columns, one target per line and the target last as it
+    /// may contain spaces; latencies are in milliseconds, `-` without a successful ping
+    pub fn render(&self) -> String {
+        let ms = |d: Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+        let mut out = format!(
+            "{:>6} {:>6} {:>6} {:>10} {:>10} {:>10}  TARGET\n",
+            "SENT", "OK", "LOSS%", "MIN_MS", "AVG_MS", "MAX_MS"
+        );
+        for (target, stats) in &self.targets {
+            let loss = 100.0 * (stats.sent - stats.ok) as f64 / stats.sent as f64;
+            let (min, avg, max) = match stats.min {
+                Some(min) => (ms(min), ms(stats.total / stats.ok as u32), ms(stats.max)),
+                None => ("-".into(), "-".into(), "-".into()),
+            };
+            out.push_str(&format!(
+                "{:>6} {:>6} {:>6.1} {:>10} {:>10} {:>10}  {}\n",
+                stats.sent, stats.ok, loss, min, avg, max, target
+            ));
+        }
+        out
+    }
+}
@@ -1,21 +1,43 @@
-use crate::config::TcpPingerEntry;
-use crate::resolver::{Resolve, resolve_str};
+use crate::config::{TcpPingerConfig, TcpPingerEntry, TcpProbe};
+use crate::metric::{
+    ByteTally, FailureType, PingPhase, TCP_CONNECT_BYTES, TcpEndpointLabel, TimeoutCause,
+    wall_clock,
+};
+use crate::resolver::{Resolve, ResolveDeadline, SrvTarget, resolve_str, system_resolver};
 use anyhow::Result;
 use std::fmt::Debug;
-use std::net::{IpAddr, SocketAddr};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::net::TcpSocket;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream};
 use tokio_rustls::rustls::pki_types::ServerName;
-use tracing::instrument;
+use tokio_socks::tcp::Socks5Stream;
+use tracing::{debug, instrument};
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct TcpPingResult {
     pub address: (ServerName<'static>, u16),
+    /// Zone of a scoped IPv6 target as configured, e.g. `eth0`
+    pub zone: Option<String>,
+    /// Host and port an SRV entry was resolved to, `None` once no target could be looked up
+    pub srv_target: Option<(String, u16)>,
     pub resolved_ip: IpAddr,
     pub send_time: Instant,
+    /// Wall-clock counterpart of `send_time`, for correlating pings with external events
+    pub sent_at: SystemTime,
+    /// 1-based retry attempt that produced this result
+    pub attempt: u8,
     pub response: TcpPingResponse,
+    /// Estimated connection handshakes plus probe bytes written and reply bytes read
+    pub bytes: u64,
+}
+
+impl TcpPingResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self.response, TcpPingResponse::Success { .. })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,75 +45,469 @@ pub struct TcpPingResult {
 pub enum TcpPingResponse {
     Success {
         endpoint: SocketAddr,
+        /// Local end of the connection, the one to the proxy when going through SOCKS5
+        local_addr: Option<SocketAddr>,
         resolve_time: Option<Duration>,
         established_time: Duration,
+        /// Time of the post-connect probe exchange, `None` without a probe
+        probe_time: Option<Duration>,
     },
-    Failure(String),
-    Timeout,
+    Failure {
+        failure_type: FailureType,
+        reason: String,
+    },
+    /// The ping ran out of time during the `cause` phase
+    Timeout { cause: TimeoutCause },
 }
 
+/// Upper bound on the probe reply searched for the expected string
+const MAX_PROBE_REPLY: usize = 64 * 1024;
+
 #[derive(Debug, Clone, Copy)]
 enum ResolvePolicy {
     Always,
     Resolved(IpAddr),
+    /// Left to the SOCKS5 proxy
+    Remote,
+}
+
+/// SOCKS5 proxy through which TCP pings are tunneled
+#[derive(Debug, Clone, Copy)]
+pub struct Socks5Proxy {
+    pub address: SocketAddr,
+    pub remote_dns: bool,
+}
+
+impl Socks5Proxy {
+    pub fn from_config(config: &TcpPingerConfig) -> Option<Self> {
+        config.socks5_proxy.map(|address| Self {
+            address,
+            remote_dns: config.socks5_remote_dns,
+        })
+    }
+}
+
+/// Zone of a scoped IPv6 literal such as `fe80::1%eth0`
+#[derive(Debug, Clone)]
+pub struct Ipv6Zone {
+    /// As configured, an interface name or index
+    pub name: String,
+    pub scope_id: u32,
+}
+
+/// Parse a TCP host: a DNS name, an IP literal optionally in brackets, or an IPv6 literal
+/// with a zone such as `fe80::1%eth0`
+pub fn parse_host(host: &str) -> Result<(ServerName<'static>, Option<Ipv6Zone>)> {
+    // Accept IPv6 literals in URL form, e.g. `[::1]`
+    let literal = host.strip_prefix('[').and_then(|h| h.strip_suffix(']'));
+    let Some((addr, zone)) = literal.unwrap_or(host).split_once('%') else {
+        return Ok(match literal {
+            Some(literal) => (
+                ServerName::IpAddress(literal.parse::<IpAddr>()?.into()),
+                None,
+            ),
+            None => (ServerName::try_from(host.to_string())?, None),
+        });
+    };
+    let addr = addr
+        .parse::<Ipv6Addr>()
+        .map_err(|_| anyhow::anyhow!("a zone is only valid on an IPv6 address: {}", host))?;
+    let scope_id = match zone.parse::<u32>() {
+        Ok(index) => index,
+        Err(_) => interface_index(zone)?,
+    };
+    if scope_id == 0 {
+        anyhow::bail!("invalid zone {:?}", zone);
+    }
+    let zone = Ipv6Zone {
+        name: zone.to_string(),
+        scope_id,
+    };
+    Ok((ServerName::IpAddress(IpAddr::V6(addr).into()), Some(zone)))
+}
+
+/// Index of the network interface `name`
+#[cfg(unix)]
+fn interface_index(name: &str) -> Result<u32> {
+    let c_name = std::ffi::CString::new(name)?;
+    // SAFETY: `c_name` is a valid NUL-terminated string for the duration of the call
+    match unsafe { libc::if_nametoindex(c_name.as_ptr()) } {
+        0 => anyhow::bail!("unknown network interface {:?}", name),
+        index => Ok(index),
+    }
+}
+
+#[cfg(not(unix))]
+fn interface_index(name: &str) -> Result<u32> {
+    anyhow::bail!(
+        "interface names are not supported as zones on this platform, use the numeric index instead of {:?}",
+        name
+    )
+}
+
+/// Mark the packets of `socket` with `dscp` through the IPv4 TOS or IPv6 traffic class byte,
+/// leaving the ECN bits to the kernel
+#[cfg(unix)]
+fn set_dscp(socket: &TcpSocket, addr: SocketAddr, dscp: u8) -> Result<()> {
+    use std::os::fd::AsRawFd;
+    let (level, name) = match addr {
+        SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_TOS),
+        SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_TCLASS),
+    };
+    let value = libc::c_int::from(dscp) << 2;
+    // SAFETY: `value` lives for the duration of the call and its size is passed along
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            (&value as *const libc::c_int).cast(),
+            size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        anyhow::bail!(
+            "failed to set DSCP {}: {}",
+            dscp,
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_dscp(_socket: &TcpSocket, _addr: SocketAddr, _dscp: u8) -> Result<()> {
+    anyhow::bail!("DSCP marking is not supported on this platform")
+}
+
+/// Outcome of a connection attempt bounded by the connect timeout
+enum ConnectError {
+    Timeout(Duration),
+    Failed(anyhow::Error),
+}
+
+/// Options applied to probe sockets before connecting
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpSocketOptions {
+    pub source_addr: Option<IpAddr>,
+    pub nodelay: Option<bool>,
+    pub keepalive: Option<bool>,
+    pub dscp: Option<u8>,
+    /// Bound on connecting, including the SOCKS5 handshake
+    pub connect_timeout: Option<Duration>,
+}
+
+impl TcpSocketOptions {
+    pub fn from_config(config: &TcpPingerConfig) -> Self {
+        Self {
+            source_addr: config.source_addr,
+            nodelay: config.tcp_nodelay,
+            keepalive: config.keepalive,
+            dscp: config.dscp,
+            connect_timeout: config.connect_timeout_millis.map(Duration::from_millis),
+        }
+    }
+}
+
+/// Fluent construction of a TCP pinger outside of a configuration, e.g.
+/// `TcpPingerBuilder::new("example.com", 443).timeout(Duration::from_secs(1)).build().await`
+#[derive(Debug, Clone)]
+pub struct TcpPingerBuilder {
+    entry: TcpPingerEntry,
+    timeout: Duration,
+    measure_dns: bool,
+    socks5: Option<Socks5Proxy>,
+    socket: TcpSocketOptions,
+    /// The system resolver when unset
+    resolver: Option<Arc<dyn Resolve>>,
+}
+
+impl TcpPingerBuilder {
+    /// Builder for `host:port` with the defaults of a `tcp` config section
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        let config = TcpPingerConfig::default();
+        TcpPingerBuilder {
+            entry: TcpPingerEntry {
+                host: host.into(),
+                port,
+                srv: None,
+                ports: None,
+                probe: None,
+                slo_millis: None,
+            },
+            timeout: Duration::from_millis(config.timeout_millis),
+            measure_dns: false,
+            socks5: None,
+            socket: TcpSocketOptions::from_config(&config),
+            resolver: None,
+        }
+    }
+
+    /// Ping the targets of SRV record `name` instead of the host and port
+    pub fn srv(mut self, name: impl Into<String>) -> Self {
+        self.entry.srv = Some(name.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.socket.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Bytes written once connected and the reply expected to contain `expect`
+    pub fn probe(mut self, send: impl Into<String>, expect: Option<String>) -> Self {
+        self.entry.probe = Some(TcpProbe {
+            send: send.into(),
+            expect,
+        });
+        self
+    }
+
+    /// Resolve the host on every ping and report the lookup time
+    pub fn measure_dns(mut self, measure_dns: bool) -> Self {
+        self.measure_dns = measure_dns;
+        self
+    }
+
+    pub fn socks5(mut self, proxy: Socks5Proxy) -> Self {
+        self.socks5 = Some(proxy);
+        self
+    }
+
+    /// Socket settings beyond the ones of this builder, replacing those already set
+    pub fn socket(mut self, socket: TcpSocketOptions) -> Self {
+        self.socket = socket;
+        self
+    }
+
+    pub fn resolver(mut self, resolver: Arc<dyn Resolve>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Validate the settings and create the pinger, resolving the host unless `measure_dns`
+    pub async fn build(self) -> Result<TcpPinger> {
+        let target = self.entry.target();
+        if self.timeout.is_zero() {
+            anyhow::bail!("TCP {}: timeout must not be 0", target);
+        }
+        if let Some(connect_timeout) = self.socket.connect_timeout
+            && (connect_timeout.is_zero() || connect_timeout > self.timeout)
+        {
+            anyhow::bail!(
+                "TCP {}: connect timeout must be non-zero and at most the timeout",
+                target
+            );
+        }
+        if self.socket.dscp.is_some_and(|dscp| dscp > 63) {
+            anyhow::bail!("TCP {}: DSCP must be between 0 and 63", target);
+        }
+        let resolver = match self.resolver {
+            Some(resolver) => resolver,
+            None => system_resolver()?,
+        };
+        TcpPinger::new(
+            self.entry,
+            self.timeout,
+            self.measure_dns,
+            self.socks5,
+            self.socket,
+            resolver,
+        )
+        .await
+    }
+}
+
+/// Order SRV targets the way clients should try them: by ascending priority, and within a
+/// priority by a weighted random draw (RFC 2782); `.` targets, meaning the service is not
+/// available, are dropped
+fn srv_order(mut targets: Vec<SrvTarget>) -> Vec<SrvTarget> {
+    targets.retain(|target| !target.host.is_empty());
+    // Zero weights first, so that they are only drawn when the roll is 0
+    targets.sort_by_key(|target| (target.priority, target.weight));
+    let mut ordered = Vec::with_capacity(targets.len());
+    for group in targets.chunk_by(|a, b| a.priority == b.priority) {
+        let mut group = group.to_vec();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|target| u32::from(target.weight)).sum();
+            let mut roll = rand::random_range(0..=total);
+            let picked = group
+                .iter()
+                .position(|target| {
+                    let weight = u32::from(target.weight);
+                    if roll <= weight {
+                        return true;
+                    }
+                    roll -= weight;
+                    false
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(picked));
+        }
+    }
+    ordered
 }
 
 #[derive(Debug)]
 pub struct TcpPinger {
+    /// Target host, the SRV name for SRV entries
     host: ServerName<'static>,
+    zone: Option<Ipv6Zone>,
+    /// Target port, 0 for SRV entries
     port: u16,
+    /// Whether `host` names SRV records listing the targets
+    srv: bool,
     timeout: Duration,
     resolver: Arc<dyn Resolve>,
     policy: ResolvePolicy,
+    socks5: Option<Socks5Proxy>,
+    socket: TcpSocketOptions,
+    probe: Option<TcpProbe>,
 }
 
 impl TcpPinger {
-    fn wrap_soft_err<E: std::fmt::Display>(&self, e: E, begin: Instant) -> Result<TcpPingResult> {
+    fn wrap_soft_err<E: std::fmt::Display>(
+        &self,
+        failure_type: FailureType,
+        e: E,
+        begin: Instant,
+    ) -> Result<TcpPingResult> {
         Ok(TcpPingResult {
             address: (self.host.clone(), self.port),
+            zone: self.zone.as_ref().map(|zone| zone.name.clone()),
+            srv_target: None,
             resolved_ip: IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
             send_time: begin,
-            response: TcpPingResponse::Failure(e.to_string()),
+            sent_at: wall_clock(begin),
+            attempt: 1,
+            response: TcpPingResponse::Failure {
+                failure_type,
+                reason: e.to_string(),
+            },
+            bytes: 0,
         })
     }
 
-    fn wrap_timeout(&self, begin: Instant) -> Result<TcpPingResult> {
-        Ok(TcpPingResult {
+    fn wrap_timeout(&self, cause: TimeoutCause, begin: Instant) -> TcpPingResult {
+        TcpPingResult {
             address: (self.host.clone(), self.port),
+            zone: self.zone.as_ref().map(|zone| zone.name.clone()),
+            srv_target: None,
             resolved_ip: IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
             send_time: begin,
-            response: TcpPingResponse::Timeout,
-        })
+            sent_at: wall_clock(begin),
+            attempt: 1,
+            response: TcpPingResponse::Timeout { cause },
+            bytes: 0,
+        }
     }
 
-    #[instrument(fields(host = %self.host.to_str(), port = %self.port), skip(self))]
-    async fn resolve_addr(&self) -> Result<IpAddr> {
-        let host = &self.host;
+    /// Timeout result for a tick whose attempts ran past the total deadline
+    pub fn deadline_exceeded(&self, begin: Instant) -> TcpPingResult {
+        self.wrap_timeout(TimeoutCause::Deadline, begin)
+    }
 
+    /// Failure result for a ping that was not sent
+    pub fn failure<E: std::fmt::Display>(&self, e: E) -> TcpPingResult {
+        TcpPingResult {
+            address: (self.host.clone(), self.port),
+            zone: self.zone.as_ref().map(|zone| zone.name.clone()),
+            srv_target: None,
+            resolved_ip: IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+            send_time: Instant::now(),
+            sent_at: wall_clock(Instant::now()),
+            attempt: 1,
+            response: TcpPingResponse::Failure {
+                failure_type: FailureType::Other,
+                reason: e.to_string(),
+            },
+            bytes: 0,
+        }
+    }
+
+    pub fn endpoint_label(&self) -> TcpEndpointLabel {
+        let host = match &self.zone {
+            Some(zone) => format!("{}%{}", self.host.to_str(), zone.name),
+            None => self.host.to_str().into_owned(),
+        };
+        TcpEndpointLabel {
+            host,
+            port: self.port.into(),
+        }
+    }
+
+    /// Socket address of the target, carrying the scope of a zoned IPv6 literal
+    fn socket_addr(&self, ip: IpAddr, port: u16) -> SocketAddr {
+        match (ip, &self.zone) {
+            (IpAddr::V6(ip), Some(zone)) => SocketAddrV6::new(ip, port, 0, zone.scope_id).into(),
+            _ => SocketAddr::new(ip, port),
+        }
+    }
+
+    #[instrument(fields(host = %host.to_str()), skip(self, deadline))]
+    async fn resolve_addr(&self, host: &ServerName<'static>, deadline: Instant) -> Result<IpAddr> {
         match host {
             ServerName::IpAddress(ip) => Ok(IpAddr::from(*ip)),
             ServerName::DnsName(name) => {
-                Ok(resolve_str(self.resolver.as_ref(), name.as_ref()).await?)
+                Ok(resolve_str(self.resolver.as_ref(), name.as_ref(), deadline).await?)
             }
             _ => unreachable!("unexpected ServerName variant"),
         }
     }
 
     pub async fn new(
-        TcpPingerEntry { host, port }: TcpPingerEntry,
+        TcpPingerEntry {
+            host,
+            port,
+            srv,
+            probe,
+            ..
+        }: TcpPingerEntry,
         timeout: Duration,
         measure_dns: bool,
+        socks5: Option<Socks5Proxy>,
+        socket: TcpSocketOptions,
         resolver: Arc<dyn Resolve>,
     ) -> Result<Self> {
-        let host = ServerName::try_from(host)?;
+        let remote_dns = socks5.is_some_and(|proxy| proxy.remote_dns);
+        if let Some(srv) = srv {
+            // Targets are looked up on every ping, the proxy cannot do SRV lookups
+            return Ok(Self {
+                host: ServerName::try_from(srv.trim().to_string())?,
+                zone: None,
+                port: 0,
+                srv: true,
+                timeout,
+                resolver,
+                policy: match remote_dns {
+                    true => ResolvePolicy::Remote,
+                    false => ResolvePolicy::Always,
+                },
+                socks5,
+                socket,
+                probe,
+            });
+        }
+        if port == 0 {
+            anyhow::bail!("Port is missing for {}", host);
+        }
+        let (host, zone) = parse_host(&host)?;
 
         let resolve = match host.clone() {
             ServerName::IpAddress(ip) => ResolvePolicy::Resolved(IpAddr::from(ip)),
+            ServerName::DnsName(_) if remote_dns => ResolvePolicy::Remote,
             ServerName::DnsName(name) => {
                 if measure_dns {
                     ResolvePolicy::Always
                 } else {
-                    ResolvePolicy::Resolved(resolve_str(resolver.as_ref(), name.as_ref()).await?)
+                    let deadline = Instant::now() + timeout;
+                    let ip = resolve_str(resolver.as_ref(), name.as_ref(), deadline).await?;
+                    ResolvePolicy::Resolved(ip)
                 }
             }
             _ => unreachable!("unexpected ServerName variant"),
@@ -99,58 +515,292 @@ impl TcpPinger {
 
         Ok(Self {
             host,
+            zone,
             port,
+            srv: false,
             timeout,
             resolver: resolver as _,
             policy: resolve,
+            socks5,
+            socket,
+            probe,
         })
     }
 
-    #[instrument(fields(host = %self.host.to_str(), port = %self.port), skip(self))]
-    async fn ping_inner(&self) -> Result<TcpPingResult> {
+    /// Run `connect` within the configured connect timeout, if any
+    async fn with_connect_timeout<T>(
+        &self,
+        connect: impl Future<Output = Result<T>>,
+    ) -> std::result::Result<T, ConnectError> {
+        match self.socket.connect_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, connect).await {
+                Ok(result) => result.map_err(ConnectError::Failed),
+                Err(_) => Err(ConnectError::Timeout(timeout)),
+            },
+            None => connect.await.map_err(ConnectError::Failed),
+        }
+    }
+
+    fn wrap_connect_timeout(&self, timeout: Duration, begin: Instant) -> Result<TcpPingResult> {
+        let reason = format!("connect timed out after {}ms", timeout.as_millis());
+        self.wrap_soft_err(FailureType::ConnectTimeout, reason, begin)
+    }
+
+    /// Open a TCP connection to `addr` with the configured socket options
+    async fn connect(&self, addr: SocketAddr) -> Result<TcpStream> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        if let Some(nodelay) = self.socket.nodelay {
+            socket.set_nodelay(nodelay)?;
+        }
+        if let Some(keepalive) = self.socket.keepalive {
+            socket.set_keepalive(keepalive)?;
+        }
+        if let Some(dscp) = self.socket.dscp {
+            set_dscp(&socket, addr, dscp)?;
+        }
+        if let Some(source) = self.socket.source_addr {
+            if source.is_ipv4() != addr.is_ipv4() {
+                anyhow::bail!(
+                    "source address {} cannot reach {}: address family mismatch",
+                    source,
+                    addr
+                );
+            }
+            // A link-local source must be bound on the interface the target is reached through
+            let bind = match (source, addr) {
+                (IpAddr::V6(source), SocketAddr::V6(addr)) => {
+                    SocketAddrV6::new(source, 0, 0, addr.scope_id()).into()
+                }
+                _ => SocketAddr::new(source, 0),
+            };
+            socket.bind(bind)?;
+        }
+        Ok(socket.connect(addr).await?)
+    }
+
+    #[instrument(
+        fields(host = %self.host.to_str(), port = %self.port),
+        skip(self, phase, bytes, deadline)
+    )]
+    async fn ping_inner(
+        &self,
+        phase: &PingPhase,
+        bytes: &ByteTally,
+        deadline: Instant,
+    ) -> Result<TcpPingResult> {
+        if !self.srv {
+            return self
+                .ping_target(phase, bytes, &self.host, self.port, deadline)
+                .await;
+        }
+        let begin = Instant::now();
+        let srv = self.host.to_str();
+        let targets = match self.resolver.resolve_srv(srv.to_string()).await {
+            Ok(targets) => srv_order(targets),
+            Err(e) => return self.wrap_soft_err(FailureType::Dns, e, begin),
+        };
+        // Targets are tried in turn until one answers
+        let mut last = None;
+        for SrvTarget { host, port, .. } in targets {
+            let target = match ServerName::try_from(host.clone()) {
+                Ok(target) => target,
+                Err(e) => {
+                    debug!("Skipping SRV target {} of {}: {}", host, srv, e);
+                    continue;
+                }
+            };
+            let mut result = self
+                .ping_target(phase, bytes, &target, port, deadline)
+                .await?;
+            result.srv_target = Some((host, port));
+            if result.is_success() {
+                return Ok(result);
+            }
+            debug!("SRV target of {} failed: {:?}", srv, result);
+            last = Some(result);
+        }
+        match last {
+            Some(result) => Ok(result),
+            None => {
+                let reason = format!("no usable SRV targets for {}", srv);
+                self.wrap_soft_err(FailureType::Dns, reason, begin)
+            }
+        }
+    }
+
+    /// Ping `host`:`port`, the configured target or one listed by the SRV records
+    #[instrument(fields(host = %host.to_str(), port = %port), skip(self, phase, bytes, deadline))]
+    async fn ping_target(
+        &self,
+        phase: &PingPhase,
+        bytes: &ByteTally,
+        host: &ServerName<'static>,
+        port: u16,
+        deadline: Instant,
+    ) -> Result<TcpPingResult> {
         let mut resolve_time: Option<Duration> = None;
         let begin = Instant::now();
         let resolved_ip = match &self.policy {
-            ResolvePolicy::Always => match self.resolve_addr().await {
-                Ok(ip) => {
-                    resolve_time = Some(begin.elapsed());
-                    ip
+            ResolvePolicy::Always => {
+                phase.enter(TimeoutCause::Dns);
+                match self.resolve_addr(host, deadline).await {
+                    Ok(ip) => {
+                        resolve_time = Some(begin.elapsed());
+                        ip
+                    }
+                    // The lookup used up the ping's budget, which is a timeout like any other
+                    Err(e) if e.is::<ResolveDeadline>() => {
+                        return Ok(self.wrap_timeout(TimeoutCause::Dns, begin));
+                    }
+                    Err(e) => return self.wrap_soft_err(FailureType::Dns, e, begin),
                 }
-                Err(e) => return self.wrap_soft_err(e, begin),
-            },
+            }
             ResolvePolicy::Resolved(ip) => *ip,
+            ResolvePolicy::Remote => IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
         };
-        let socket_addr = SocketAddr::new(resolved_ip, self.port);
-        let socket = match resolved_ip {
-            IpAddr::V4(_) => TcpSocket::new_v4()?,
-            IpAddr::V6(_) => TcpSocket::new_v6()?,
-        };
+        phase.enter(TimeoutCause::Connect);
+        bytes.add(TCP_CONNECT_BYTES);
+        let socket_addr = self.socket_addr(resolved_ip, port);
 
-        if let Err(e) = socket.connect(socket_addr).await {
-            return self.wrap_soft_err(e, begin);
-        }
+        let (established_time, local_addr, probe) = if let Some(proxy) = &self.socks5 {
+            let connect = async {
+                let stream = self.connect(proxy.address).await?;
+                Ok(match self.policy {
+                    ResolvePolicy::Remote => {
+                        let target = (host.to_str().into_owned(), port);
+                        Socks5Stream::connect_with_socket(stream, target).await?
+                    }
+                    _ => Socks5Stream::connect_with_socket(stream, socket_addr).await?,
+                })
+            };
+            let mut stream = match self.with_connect_timeout(connect).await {
+                Ok(stream) => stream,
+                Err(ConnectError::Timeout(timeout)) => {
+                    return self.wrap_connect_timeout(timeout, begin);
+                }
+                Err(ConnectError::Failed(e)) => {
+                    let reason = format!("SOCKS5 proxy {}: {}", proxy.address, e);
+                    return self.wrap_soft_err(FailureType::Connect, reason, begin);
+                }
+            };
+            let established_time = begin.elapsed();
+            let local_addr = stream.local_addr().ok();
+            phase.enter(TimeoutCause::Response);
+            (
+                established_time,
+                local_addr,
+                self.run_probe(&mut stream, bytes).await,
+            )
+        } else {
+            let mut stream = match self.with_connect_timeout(self.connect(socket_addr)).await {
+                Ok(stream) => stream,
+                Err(ConnectError::Timeout(timeout)) => {
+                    return self.wrap_connect_timeout(timeout, begin);
+                }
+                Err(ConnectError::Failed(e)) => {
+                    return self.wrap_soft_err(FailureType::Connect, e, begin);
+                }
+            };
+            let established_time = begin.elapsed();
+            let local_addr = stream.local_addr().ok();
+            phase.enter(TimeoutCause::Response);
+            (
+                established_time,
+                local_addr,
+                self.run_probe(&mut stream, bytes).await,
+            )
+        };
+        let probe_time = match probe {
+            Ok(probe) => probe,
+            Err(e) => {
+                return self.wrap_soft_err(FailureType::Protocol, format!("Probe: {}", e), begin);
+            }
+        };
 
-        let established_time = begin.elapsed();
         Ok(TcpPingResult {
             address: (self.host.clone(), self.port),
+            zone: self.zone.as_ref().map(|zone| zone.name.clone()),
+            srv_target: None,
             resolved_ip,
             send_time: begin,
+            sent_at: wall_clock(begin),
+            attempt: 1,
             response: TcpPingResponse::Success {
                 endpoint: socket_addr,
+                local_addr,
                 resolve_time,
                 established_time,
+                probe_time,
             },
+            bytes: bytes.total(),
         })
     }
 
+    /// Exchange the configured probe over an established connection and return its duration,
+    /// counting the bytes written and read in `bytes`
+    async fn run_probe<S>(&self, stream: &mut S, bytes: &ByteTally) -> Result<Option<Duration>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let Some(probe) = &self.probe else {
+            return Ok(None);
+        };
+        let begin = Instant::now();
+        bytes.add(probe.send.len() as u64);
+        stream.write_all(probe.send.as_bytes()).await?;
+        stream.flush().await?;
+
+        let mut buf = [0u8; 1024];
+        let Some(expect) = &probe.expect else {
+            // Any reply will do, but a black-holed port must not pass for a healthy one
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                anyhow::bail!("connection closed without a reply");
+            }
+            bytes.add(n as u64);
+            return Ok(Some(begin.elapsed()));
+        };
+        let mut reply = Vec::new();
+        while !String::from_utf8_lossy(&reply).contains(expect.as_str()) {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                anyhow::bail!("connection closed before {:?} was received", expect);
+            }
+            bytes.add(n as u64);
+            reply.extend_from_slice(&buf[..n]);
+            if reply.len() > MAX_PROBE_REPLY {
+                anyhow::bail!(
+                    "{:?} not found in the first {} bytes",
+                    expect,
+                    MAX_PROBE_REPLY
+                );
+            }
+        }
+        Ok(Some(begin.elapsed()))
+    }
+
     #[instrument(fields(host = %self.host.to_str(), port = %self.port), skip(self))]
     pub async fn ping(&self) -> Result<TcpPingResult> {
         let task_submission_time = Instant::now();
+        let phase = PingPhase::new(match self.policy {
+            ResolvePolicy::Always => TimeoutCause::Dns,
+            _ if self.srv => TimeoutCause::Dns,
+            _ => TimeoutCause::Connect,
+        });
+        let deadline = task_submission_time + self.timeout;
+        let bytes = ByteTally::default();
         let result =
-            tokio::time::timeout(self.timeout, async move { self.ping_inner().await }).await;
+            tokio::time::timeout(self.timeout, self.ping_inner(&phase, &bytes, deadline)).await;
 
+        // Soft errors are built before knowing what was sent, so every outcome takes the tally
         match result {
-            Ok(Ok(res)) => Ok(res),
+            Ok(Ok(res)) => Ok(TcpPingResult {
+                bytes: bytes.total(),
+                ..res
+            }),
             Ok(Err(e)) => {
                 // This is not a soft error, but a failure to ping
                 panic!(
@@ -159,7 +809,193 @@ impl TcpPinger {
                     e
                 );
             }
-            Err(_) => self.wrap_timeout(task_submission_time),
+            Err(_) => Ok(TcpPingResult {
+                bytes: bytes.total(),
+                ..self.wrap_timeout(phase.current(), task_submission_time)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(host: &str) -> TcpPingerEntry {
+        TcpPingerEntry {
+            host: host.to_string(),
+            port: 443,
+            srv: None,
+            ports: None,
+            probe: None,
+            slo_millis: None,
+        }
+    }
+
+    async fn pinger(host: &str, measure_dns: bool) -> TcpPinger {
+        TcpPinger::new(
+            entry(host),
+            Duration::from_secs(1),
+            measure_dns,
+            None,
+            TcpSocketOptions::default(),
+            system_resolver().unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_host_accepts_bare_ipv6() {
+        let (host, zone) = parse_host("::1").unwrap();
+        assert_eq!(host, ServerName::from(IpAddr::from(Ipv6Addr::LOCALHOST)));
+        assert!(zone.is_none());
+    }
+
+    #[test]
+    fn parse_host_strips_brackets() {
+        let (host, zone) = parse_host("[::1]").unwrap();
+        assert_eq!(host, ServerName::from(IpAddr::from(Ipv6Addr::LOCALHOST)));
+        assert!(zone.is_none());
+    }
+
+    #[test]
+    fn parse_host_accepts_dns_names() {
+        let (host, zone) = parse_host("example.com").unwrap();
+        assert!(matches!(host, ServerName::DnsName(_)));
+        assert_eq!(host.to_str(), "example.com");
+        assert!(zone.is_none());
+    }
+
+    #[test]
+    fn parse_host_accepts_numeric_zones() {
+        for host in ["fe80::1%1", "[fe80::1%1]"] {
+            let (host, zone) = parse_host(host).unwrap();
+            assert_eq!(
+                host,
+                ServerName::from(IpAddr::from("fe80::1".parse::<Ipv6Addr>().unwrap()))
+            );
+            let zone = zone.unwrap();
+            assert_eq!(zone.name, "1");
+            assert_eq!(zone.scope_id, 1);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_host_resolves_interface_zones() {
+        let (_, zone) = parse_host("fe80::1%lo").unwrap();
+        let zone = zone.unwrap();
+        assert_eq!(zone.name, "lo");
+        assert_ne!(zone.scope_id, 0);
+    }
+
+    #[test]
+    fn parse_host_rejects_invalid_zones() {
+        assert!(parse_host("127.0.0.1%1").is_err());
+        assert!(parse_host("fe80::1%0").is_err());
+        assert!(parse_host("fe80::1%no-such-interface").is_err());
+    }
+
+    #[tokio::test]
+    async fn ip_literals_need_no_lookup() {
+        for host in ["::1", "[::1]"] {
+            let pinger = pinger(host, false).await;
+            assert!(matches!(
+                pinger.policy,
+                ResolvePolicy::Resolved(ip) if ip == IpAddr::from(Ipv6Addr::LOCALHOST)
+            ));
         }
     }
+
+    #[tokio::test]
+    async fn dns_names_are_resolved_per_ping_with_measure_dns() {
+        let pinger = pinger("example.com", true).await;
+        assert!(matches!(pinger.policy, ResolvePolicy::Always));
+        assert_eq!(pinger.endpoint_label().host, "example.com");
+    }
+
+    async fn local_pinger(port: u16) -> TcpPinger {
+        TcpPinger::new(
+            TcpPingerEntry {
+                port,
+                ..entry("127.0.0.1")
+            },
+            Duration::from_secs(1),
+            false,
+            None,
+            TcpSocketOptions::default(),
+            system_resolver().unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn connects_count_against_the_byte_budget() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let pinger = local_pinger(listener.local_addr().unwrap().port()).await;
+
+        let result = pinger.ping().await.unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.bytes, TCP_CONNECT_BYTES);
+    }
+
+    #[tokio::test]
+    async fn refused_connects_count_against_the_byte_budget() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let pinger = local_pinger(port).await;
+
+        let result = pinger.ping().await.unwrap();
+        assert!(!result.is_success());
+        assert_eq!(result.bytes, TCP_CONNECT_BYTES);
+    }
+
+    #[tokio::test]
+    async fn probes_without_a_reply_are_not_successes() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // The first connection is held open in silence, the second closed straight away
+        tokio::spawn(async move {
+            let (_silent, _) = listener.accept().await.unwrap();
+            let (closed, _) = listener.accept().await.unwrap();
+            drop(closed);
+            std::future::pending::<()>().await;
+        });
+        let pinger = TcpPinger::new(
+            TcpPingerEntry {
+                port,
+                probe: Some(TcpProbe {
+                    send: "PING\r\n".to_string(),
+                    expect: None,
+                }),
+                ..entry("127.0.0.1")
+            },
+            Duration::from_millis(200),
+            false,
+            None,
+            TcpSocketOptions::default(),
+            system_resolver().unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let result = pinger.ping().await.unwrap();
+        assert!(matches!(
+            result.response,
+            TcpPingResponse::Timeout {
+                cause: TimeoutCause::Response
+            }
+        ));
+        let result = pinger.ping().await.unwrap();
+        assert!(matches!(
+            result.response,
+            TcpPingResponse::Failure {
+                failure_type: FailureType::Protocol,
+                ..
+            }
+        ));
+    }
 }
@@ -0,0 +1,70 @@
+use crate::config::{Args, LogFormat, LogLevel, OtelConfig};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+const DEFAULT_SERVICE_NAME: &str = "pinger";
+
+/// Install the global tracing subscriber with the requested level and output format.
+///
+/// Logging stays off unless `--log-level`, `--debug` or `RUST_LOG` asks for it. When `otel`
+/// is set, spans are additionally exported over OTLP/HTTP regardless of the log level; the
+/// returned provider must be shut down before exit to flush pending spans.
+pub fn init(args: &Args, otel: Option<&OtelConfig>) -> anyhow::Result<Option<SdkTracerProvider>> {
+    let level = match (args.log_level, args.debug) {
+        (Some(level), _) => level,
+        (None, true) => LogLevel::Debug,
+        (None, false) => LogLevel::Off,
+    };
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::from(level).into())
+        .from_env_lossy();
+
+    let fmt_layer = match args.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
+
+    let provider = otel.map(build_tracer_provider).transpose()?;
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(DEFAULT_SERVICE_NAME))
+            .with_filter(LevelFilter::INFO)
+    });
+
+    tracing_subscriber::registry()
+        .with(fmt_layer.with_filter(filter))
+        .with(otel_layer)
+        .init();
+    Ok(provider)
+}
+
+fn build_tracer_provider(config: &OtelConfig) -> anyhow::Result<SdkTracerProvider> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.endpoint)
+        .build()?;
+    let service_name = config
+        .service_name
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build())
+}
+
+/// Flush and stop the trace exporter, if any
+pub fn shutdown(provider: Option<SdkTracerProvider>) {
+    if let Some(provider) = provider
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("Failed to shut down trace exporter: {}", e);
+    }
+}
@@ -0,0 +1,16 @@
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+/// TLS client configuration trusting the webpki roots and offering `alpn_protocols`, no ALPN
+/// extension being sent when empty
+pub fn client_config(alpn_protocols: &[&[u8]]) -> ClientConfig {
+    let mut root_cert_store = RootCertStore::empty();
+    root_cert_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let mut config = ClientConfig::builder()
+        .with_root_certificates(root_cert_store)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn_protocols
+        .iter()
+        .map(|protocol| protocol.to_vec())
+        .collect();
+    config
+}
@@ -0,0 +1,291 @@
+use crate::config::WsPingerEntry;
+use crate::http_pinger::{STATUS_LINE_BYTES, head_bytes, request_line_bytes};
+use crate::metric::{
+    ByteTally, FailureType, TCP_CONNECT_BYTES, TLS_HANDSHAKE_BYTES, WsFailureStage,
+};
+use crate::resolver::{Resolve, ResolveDeadline, resolve_str};
+use crate::tls;
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use hyper::body::Bytes;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tracing::{Instrument, info_span, instrument};
+use url::Host;
+
+/// A masked client ping frame carrying the 6-byte payload
+const PING_FRAME_BYTES: u64 = 12;
+
+/// The server's pong frame echoing the payload
+const PONG_FRAME_BYTES: u64 = 8;
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct WsPingResponse {
+    pub url: url::Url,
+    pub send_time: Instant,
+    /// 1-based retry attempt that produced this response
+    pub attempt: u8,
+    pub result: WsPingResult,
+    /// Approximate bytes of the estimated handshakes, the upgrade request and response heads
+    /// and the ping frames
+    pub bytes: u64,
+}
+
+impl WsPingResponse {
+    pub fn is_success(&self) -> bool {
+        matches!(self.result, WsPingResult::Connected { .. })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum WsPingResult {
+    /// The upgrade completed `handshake_time` after the ping started, DNS, TCP and TLS
+    /// included; `pong_time` is the ping frame round trip when one was sent
+    Connected {
+        handshake_time: Duration,
+        pong_time: Option<Duration>,
+    },
+    /// The connection could not be upgraded
+    HandshakeFailure {
+        failure_type: FailureType,
+        reason: String,
+    },
+    /// The upgrade completed but the ping frame was not answered
+    FrameFailure {
+        reason: String,
+    },
+    Timeout,
+}
+
+impl WsPingResult {
+    /// Stage a failed ping broke down in, `None` on success
+    pub fn failure_stage(&self) -> Option<WsFailureStage> {
+        match self {
+            WsPingResult::Connected { .. } => None,
+            WsPingResult::HandshakeFailure { .. } => Some(WsFailureStage::Handshake),
+            WsPingResult::FrameFailure { .. } => Some(WsFailureStage::Frame),
+            WsPingResult::Timeout => Some(WsFailureStage::Timeout),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WsPinger {
+    url: url::Url,
+    host: Host<String>,
+    port: u16,
+    ping_frame: bool,
+    /// Set for `wss` targets
+    tls_config: Option<Arc<ClientConfig>>,
+    timeout: Duration,
+    resolver: Arc<dyn Resolve>,
+}
+
+impl WsPinger {
+    pub fn new(
+        entry: WsPingerEntry,
+        timeout: Duration,
+        resolver: Arc<dyn Resolve>,
+    ) -> Result<Self> {
+        let url = entry.url.trim().parse::<url::Url>()?;
+        let Some(host) = url.host().map(|host| host.to_owned()) else {
+            anyhow::bail!("Invalid URL: Host is missing in {}", url);
+        };
+        let tls_config = match url.scheme() {
+            "ws" => None,
+            "wss" => Some(Arc::new(tls::client_config(&[]))),
+            scheme => anyhow::bail!("Unsupported WebSocket URL scheme: {}", scheme),
+        };
+        Ok(WsPinger {
+            port: url.port_or_known_default().unwrap(),
+            url,
+            host,
+            ping_frame: entry.ping_frame,
+            tls_config,
+            timeout,
+            resolver,
+        })
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    fn response(&self, begin: Instant, result: WsPingResult) -> WsPingResponse {
+        WsPingResponse {
+            url: self.url.clone(),
+            send_time: begin,
+            attempt: 1,
+            result,
+            bytes: 0,
+        }
+    }
+
+    /// Timeout response for a tick whose attempts ran past the total deadline
+    pub fn deadline_exceeded(&self, begin: Instant) -> WsPingResponse {
+        self.response(begin, WsPingResult::Timeout)
+    }
+
+    fn wrap_handshake_err<E: std::fmt::Display>(
+        &self,
+        failure_type: FailureType,
+        e: E,
+        begin: Instant,
+    ) -> WsPingResponse {
+        self.response(
+            begin,
+            WsPingResult::HandshakeFailure {
+                failure_type,
+                reason: e.to_string(),
+            },
+        )
+    }
+
+    async fn resolve(&self, deadline: Instant) -> Result<IpAddr> {
+        match &self.host {
+            Host::Domain(name) => resolve_str(self.resolver.as_ref(), name, deadline).await,
+            Host::Ipv4(ip) => Ok((*ip).into()),
+            Host::Ipv6(ip) => Ok((*ip).into()),
+        }
+    }
+
+    #[instrument(fields(url = %self.url), skip(self, bytes))]
+    async fn ping_inner(&self, bytes: &ByteTally) -> Result<WsPingResponse> {
+        let begin = Instant::now();
+        let ip = match self.resolve(begin + self.timeout).await {
+            Ok(ip) => ip,
+            Err(e) if e.is::<ResolveDeadline>() => {
+                return Ok(self.response(begin, WsPingResult::Timeout));
+            }
+            Err(e) => return Ok(self.wrap_handshake_err(FailureType::Dns, e, begin)),
+        };
+        let addr = SocketAddr::new(ip, self.port);
+        bytes.add(TCP_CONNECT_BYTES);
+        let tcp = match TcpStream::connect(addr)
+            .instrument(info_span!("connect", %addr))
+            .await
+        {
+            Ok(tcp) => tcp,
+            Err(e) => return Ok(self.wrap_handshake_err(FailureType::Connect, e, begin)),
+        };
+
+        let Some(tls_config) = &self.tls_config else {
+            return Ok(self.exchange(tcp, bytes, begin).await);
+        };
+        let server_name = match &self.host {
+            Host::Domain(name) => ServerName::try_from(name.clone())?,
+            Host::Ipv4(ip) => ServerName::from(IpAddr::from(*ip)),
+            Host::Ipv6(ip) => ServerName::from(IpAddr::from(*ip)),
+        };
+        bytes.add(TLS_HANDSHAKE_BYTES);
+        match TlsConnector::from(tls_config.clone())
+            .connect(server_name, tcp)
+            .instrument(info_span!("tls_handshake"))
+            .await
+        {
+            Ok(stream) => Ok(self.exchange(stream, bytes, begin).await),
+            Err(e) => Ok(self.wrap_handshake_err(FailureType::Tls, e, begin)),
+        }
+    }
+
+    /// Upgrade `stream` and, if configured, exchange a ping frame over it
+    async fn exchange<S>(&self, stream: S, bytes: &ByteTally, begin: Instant) -> WsPingResponse
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let request = match self.url.as_str().into_client_request() {
+            Ok(request) => request,
+            Err(e) => return self.wrap_handshake_err(FailureType::Protocol, e, begin),
+        };
+        let request_line = request_line_bytes(request.method(), request.uri().path());
+        bytes.add(head_bytes(request_line, request.headers()));
+        let mut ws = match tokio_tungstenite::client_async(request, stream)
+            .instrument(info_span!("upgrade"))
+            .await
+        {
+            Ok((ws, response)) => {
+                bytes.add(head_bytes(STATUS_LINE_BYTES, response.headers()));
+                ws
+            }
+            Err(e) => {
+                let failure_type = match &e {
+                    tungstenite::Error::Io(_) => FailureType::Connect,
+                    _ => FailureType::Protocol,
+                };
+                return self.wrap_handshake_err(failure_type, e, begin);
+            }
+        };
+        let handshake_time = begin.elapsed();
+
+        let pong_time = if self.ping_frame {
+            bytes.add(PING_FRAME_BYTES);
+            match Self::ping_frame(&mut ws)
+                .instrument(info_span!("ping_frame"))
+                .await
+            {
+                Ok(pong_time) => {
+                    bytes.add(PONG_FRAME_BYTES);
+                    Some(pong_time)
+                }
+                Err(e) => {
+                    let reason = e.to_string();
+                    return self.response(begin, WsPingResult::FrameFailure { reason });
+                }
+            }
+        } else {
+            None
+        };
+        // Best effort, the server's close frame is not awaited
+        let _ = ws.close(None).await;
+
+        self.response(
+            begin,
+            WsPingResult::Connected {
+                handshake_time,
+                pong_time,
+            },
+        )
+    }
+
+    /// Send a ping frame and wait for the matching pong, skipping any other message
+    async fn ping_frame<S>(ws: &mut tokio_tungstenite::WebSocketStream<S>) -> Result<Duration>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let payload = Bytes::from_static(b"pinger");
+        let begin = Instant::now();
+        ws.send(Message::Ping(payload.clone())).await?;
+        while let Some(message) = ws.next().await {
+            if let Message::Pong(data) = message?
+                && data == payload
+            {
+                return Ok(begin.elapsed());
+            }
+        }
+        anyhow::bail!("Connection closed before pong")
+    }
+
+    pub async fn ping(&self) -> Result<WsPingResponse> {
+        let begin = Instant::now();
+        let bytes = ByteTally::default();
+        let response = match tokio::time::timeout(self.timeout, self.ping_inner(&bytes)).await {
+            Ok(result) => result?,
+            Err(_) => self.response(begin, WsPingResult::Timeout),
+        };
+        // Responses are built before knowing what was sent, so every outcome takes the tally
+        Ok(WsPingResponse {
+            bytes: bytes.total(),
+            ..response
+        })
+    }
+}
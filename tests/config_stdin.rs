@@ -0,0 +1,55 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const CONFIG: &str = "\
+http:
+  timeout_millis: 1000
+  interval_millis: 2000
+  entries:
+    - url: http://127.0.0.1:8080/health
+tcp:
+  timeout_millis: 1000
+  interval_millis: 2000
+  entries:
+    - host: 127.0.0.1
+      port: 8080
+";
+
+fn check_from_stdin(args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pinger"))
+        .args(["--config", "-", "--check"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(CONFIG.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn yaml_config_is_read_from_stdin() {
+    let output = check_from_stdin(&["--config-format", "yaml"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("http://127.0.0.1:8080/health"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("127.0.0.1:8080"), "{}", stdout);
+    assert!(!stdout.contains("FAIL"), "{}", stdout);
+}
+
+#[test]
+fn stdin_config_requires_a_format() {
+    let output = check_from_stdin(&[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--config-format"));
+}
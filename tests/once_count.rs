@@ -0,0 +1,100 @@
+use std::io::Write;
+use std::net::TcpListener;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[test]
+fn once_pings_count_times() {
+    // Connects complete in the backlog, nothing needs to accept them
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let config = format!(
+        "\
+tcp:
+  timeout_millis: 1000
+  interval_millis: 100
+  entries:
+    - host: 127.0.0.1
+      port: {port}
+"
+    );
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pinger"))
+        .args(["--config", "-", "--config-format", "yaml", "--once"])
+        .args(["--count", "3"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(config.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Summary row: sent, ok, loss, latencies and the target
+    let target = format!("TCP 127.0.0.1:{}", port);
+    let row = stdout
+        .lines()
+        .find(|line| line.trim_start().starts_with(char::is_numeric) && line.ends_with(&target))
+        .unwrap_or_else(|| panic!("no summary row in {}", stdout));
+    let columns: Vec<&str> = row.split_whitespace().collect();
+    assert_eq!(columns[..2], ["3", "3"], "{}", stdout);
+}
+
+#[test]
+fn once_honours_total_deadline() {
+    // Connections are closed unanswered, which fails every attempt and retries it
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            drop(stream);
+        }
+    });
+    let config = format!(
+        "\
+http:
+  pinger: Hyper
+  timeout_millis: 500
+  interval_millis: 5000
+  retries: 5
+  retry_delay_millis: 400
+  total_deadline_millis: 700
+  entries:
+    - url: http://127.0.0.1:{port}/
+"
+    );
+
+    let begin = Instant::now();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pinger"))
+        .args(["--config", "-", "--config-format", "yaml", "--once"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(config.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // Five attempts 400ms apart would take 1.6s without the deadline
+    assert!(!output.status.success(), "{}", stdout);
+    assert!(begin.elapsed() < Duration::from_millis(1500), "{}", stdout);
+    assert!(stdout.contains("Deadline"), "{}", stdout);
+}